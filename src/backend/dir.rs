@@ -0,0 +1,301 @@
+use super::{xochitl, Backend, Body, Edit, Notes, Unreachable};
+use crate::Item;
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+
+/// A copy of the tablet's data directory on this machine.
+pub struct Dir {
+    root: PathBuf,
+}
+
+impl Dir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    async fn records(&self) -> Result<Vec<xochitl::Record>> {
+        if !self.root.is_dir() {
+            return Err(Unreachable(format!("{} is not a directory", self.root.display())).into());
+        }
+        let mut entries = tokio::fs::read_dir(&self.root)
+            .await
+            .with_context(|| format!("Could not read {}", self.root.display()))?;
+        let mut records = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|e| e != "metadata") {
+                continue;
+            }
+            let id = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let content = tokio::fs::read_to_string(self.root.join(format!("{}.content", id))).await.ok();
+            let mut size = None;
+            for ext in ["pdf", "epub"] {
+                if let Ok(meta) = tokio::fs::metadata(self.root.join(format!("{}.{}", id, ext))).await {
+                    size = Some(meta.len());
+                    break;
+                }
+            }
+            records.push(xochitl::Record {
+                id,
+                metadata: tokio::fs::read_to_string(&path).await?,
+                content,
+                size,
+            });
+        }
+        Ok(records)
+    }
+
+    /// The files `item` is drawn from.
+    async fn sources(&self, item: &Item) -> Result<xochitl::Sources> {
+        let mut sources = xochitl::Sources {
+            content: tokio::fs::read_to_string(self.root.join(format!("{}.content", item.id))).await.ok(),
+            pdf: tokio::fs::read(self.root.join(format!("{}.pdf", item.id))).await.ok(),
+            ..Default::default()
+        };
+        for (dir, ext) in [(item.id.clone(), "rm"), (format!("{}.highlights", item.id), "json")] {
+            let Ok(mut entries) = tokio::fs::read_dir(self.root.join(dir)).await else {
+                continue;
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if path.extension().is_none_or(|e| e != ext) {
+                    continue;
+                }
+                let page = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                let bytes = tokio::fs::read(&path).await.with_context(|| format!("Could not read {}", path.display()))?;
+                if ext == "rm" {
+                    sources.pages.insert(page, bytes);
+                } else {
+                    sources.highlights.insert(page, String::from_utf8(bytes)?);
+                }
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Deletes every file and folder of the items `ids`, metadata first so
+    /// nothing half-deleted shows up if this stops midway.
+    async fn remove(&self, ids: &[String]) -> Result<()> {
+        let mut entries = tokio::fs::read_dir(&self.root)
+            .await
+            .with_context(|| format!("Could not read {}", self.root.display()))?;
+        let mut doomed = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let owner = name.split_once('.').map_or(name.as_str(), |(id, _)| id);
+            if ids.iter().any(|id| id == owner) {
+                doomed.push((!name.ends_with(".metadata"), entry.path(), entry.file_type().await?.is_dir()));
+            }
+        }
+        doomed.sort();
+        for (_, path, is_dir) in doomed {
+            let removed = if is_dir { tokio::fs::remove_dir_all(&path).await } else { tokio::fs::remove_file(&path).await };
+            removed.with_context(|| format!("Could not delete {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, files: xochitl::Files) -> Result<()> {
+        for (name, bytes) in files {
+            let target = self.root.join(name);
+            tokio::fs::write(&target, bytes).await.with_context(|| format!("Could not write {}", target.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Backend for Dir {
+    fn list<'a>(&'a self, parent: &'a Option<String>) -> BoxFuture<'a, Result<Vec<Item>>> {
+        Box::pin(async move { Ok(xochitl::items(self.records().await?, parent)) })
+    }
+
+    fn library(&self) -> BoxFuture<'_, Result<Vec<Item>>> {
+        Box::pin(async move { Ok(xochitl::library(self.records().await?)) })
+    }
+
+    fn trash(&self) -> BoxFuture<'_, Result<Vec<Item>>> {
+        Box::pin(async move { Ok(xochitl::trash(self.records().await?)) })
+    }
+
+    fn download<'a>(&'a self, item: &'a Item) -> BoxFuture<'a, Result<Body>> {
+        Box::pin(async move { xochitl::render(item, self.sources(item).await?).await })
+    }
+
+    fn notes<'a>(&'a self, item: &'a Item) -> BoxFuture<'a, Result<Notes>> {
+        Box::pin(async move { xochitl::notes(item, self.sources(item).await?) })
+    }
+
+    fn upload<'a>(&'a self, parent: &'a Option<String>, path: &'a Path, file_name: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.write(xochitl::new_document(parent, path, file_name)?).await
+        })
+    }
+
+    fn can_edit(&self) -> bool {
+        true
+    }
+
+    fn edit<'a>(&'a self, item: &'a Item, edit: &'a Edit) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if let Edit::Purge = edit {
+                return self.remove(&xochitl::descendants(self.records().await?, &item.id)).await;
+            }
+            let path = self.root.join(format!("{}.metadata", item.id));
+            let metadata = tokio::fs::read_to_string(&path).await.with_context(|| format!("Could not read {}", path.display()))?;
+            let content = tokio::fs::read_to_string(self.root.join(format!("{}.content", item.id))).await.ok();
+            self.write(xochitl::apply(&item.id, &metadata, content.as_deref(), edit)?).await
+        })
+    }
+
+    fn mkdir<'a>(&'a self, parent: &'a Option<String>, name: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let (id, files) = xochitl::new_folder(parent, name)?;
+            self.write(files).await?;
+            Ok(id)
+        })
+    }
+
+    fn describe(&self) -> String {
+        self.root.display().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A data directory with `/Work/Old` and nothing else.
+    async fn tablet() -> (tempfile::TempDir, Dir, String, String) {
+        let root = tempfile::tempdir().unwrap();
+        let dir = Dir::new(root.path());
+        let work = dir.mkdir(&None, "Work").await.unwrap();
+        let old = dir.mkdir(&Some(work.clone()), "Old").await.unwrap();
+        (root, dir, work, old)
+    }
+
+    /// A PDF uploaded into `parent` as `name`.
+    async fn upload(dir: &Dir, parent: &Option<String>, name: &str) -> Item {
+        let source = tempfile::tempdir().unwrap();
+        let path = source.path().join(name);
+        std::fs::write(&path, b"%PDF-1.4 test").unwrap();
+        dir.upload(parent, &path, name).await.unwrap();
+        dir.list(parent).await.unwrap().into_iter().find(|i| i.visible_name == name.trim_end_matches(".pdf")).unwrap()
+    }
+
+    fn names(items: &[Item]) -> Vec<&str> {
+        items.iter().map(|i| i.visible_name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn lists_folders_and_uploads() {
+        let (root, dir, work, old) = tablet().await;
+        let paper = upload(&dir, &Some(work.clone()), "Paper.pdf").await;
+        upload(&dir, &None, "Top.pdf").await;
+
+        assert_eq!(names(&dir.list(&None).await.unwrap()), ["Work", "Top"]);
+        let inside = dir.list(&Some(work.clone())).await.unwrap();
+        assert_eq!(names(&inside), ["Old", "Paper"]);
+        assert!(inside[0].is_folder() && !inside[1].is_folder());
+        assert_eq!(paper.file_type.as_deref(), Some("pdf"));
+        assert_eq!(paper.size_bytes(), Some(13));
+        assert_eq!(std::fs::read(root.path().join(format!("{}.pdf", paper.id))).unwrap(), b"%PDF-1.4 test");
+
+        let library = dir.library().await.unwrap();
+        let parent = |name: &str| library.iter().find(|i| i.visible_name == name).unwrap().parent.clone();
+        assert_eq!(library.len(), 4);
+        assert_eq!(parent("Work"), "");
+        assert_eq!(parent("Old"), work);
+        assert_eq!(parent("Paper"), work);
+        assert!(dir.list(&Some(old)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn downloads_stored_pdfs() {
+        let (root, dir, work, _) = tablet().await;
+        let paper = upload(&dir, &Some(work), "Paper.pdf").await;
+        let body = dir.download(&paper).await.unwrap();
+        assert_eq!(body.len, Some(13));
+        let chunks: Vec<Vec<u8>> = futures::StreamExt::collect::<Vec<_>>(body.chunks).await.into_iter().map(Result::unwrap).collect();
+        assert_eq!(chunks.concat(), b"%PDF-1.4 test");
+
+        // Notebooks are rendered, but an imported PDF needs its file
+        std::fs::remove_file(root.path().join(format!("{}.pdf", paper.id))).unwrap();
+        assert!(dir.download(&paper).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn edits_keep_other_fields() {
+        let (root, dir, work, _) = tablet().await;
+        let paper = upload(&dir, &Some(work.clone()), "Paper.pdf").await;
+        let path = root.path().join(format!("{}.metadata", paper.id));
+        let mut meta: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        meta["futureField"] = "kept".into();
+        std::fs::write(&path, meta.to_string()).unwrap();
+
+        dir.edit(&paper, &Edit::Rename("Thesis".into())).await.unwrap();
+        dir.edit(&paper, &Edit::Pin(true)).await.unwrap();
+        dir.edit(&paper, &Edit::Tags { add: vec!["work".into(), "draft".into()], remove: Vec::new() }).await.unwrap();
+        dir.edit(&paper, &Edit::Tags { add: Vec::new(), remove: vec!["draft".into()] }).await.unwrap();
+        dir.edit(&paper, &Edit::Move(None)).await.unwrap();
+
+        let top = dir.list(&None).await.unwrap();
+        let thesis = top.iter().find(|i| i.id == paper.id).unwrap();
+        assert_eq!(thesis.visible_name, "Thesis");
+        assert!(thesis.pinned);
+        assert_eq!(thesis.tags, ["work"]);
+        assert!(dir.list(&Some(work)).await.unwrap().iter().all(|i| i.id != paper.id));
+        let meta: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(meta["futureField"], "kept");
+        assert_eq!(meta["metadatamodified"], true);
+    }
+
+    #[tokio::test]
+    async fn a_missing_directory_is_unreachable() {
+        let dir = Dir::new("/nonexistent/xochitl");
+        let e = dir.list(&None).await.unwrap_err();
+        assert!(e.is::<Unreachable>());
+    }
+
+    #[tokio::test]
+    async fn trash_remembers_where_items_came_from() {
+        let (_root, dir, work, _) = tablet().await;
+        let paper = upload(&dir, &Some(work.clone()), "Paper.pdf").await;
+
+        dir.edit(&paper, &Edit::Trash).await.unwrap();
+        let trash = dir.trash().await.unwrap();
+        assert_eq!(names(&trash), ["Paper"]);
+        assert_eq!(trash[0].origin.as_deref(), Some(work.as_str()));
+        assert!(dir.library().await.unwrap().iter().all(|i| i.id != paper.id));
+        assert!(dir.list(&Some(work.clone())).await.unwrap().iter().all(|i| i.id != paper.id));
+
+        // Restoring is a move back, which forgets the origin again
+        dir.edit(&trash[0], &Edit::Move(trash[0].origin.clone())).await.unwrap();
+        assert!(dir.trash().await.unwrap().is_empty());
+        let back = dir.list(&Some(work)).await.unwrap().into_iter().find(|i| i.id == paper.id).unwrap();
+        assert_eq!(back.origin, None);
+    }
+
+    #[tokio::test]
+    async fn purge_removes_everything_inside() {
+        let (root, dir, work, old) = tablet().await;
+        let inner = upload(&dir, &Some(old.clone()), "Inner.pdf").await;
+        let keep = upload(&dir, &None, "Keep.pdf").await;
+        let work_item = dir.list(&None).await.unwrap().into_iter().find(|i| i.id == work).unwrap();
+
+        dir.edit(&work_item, &Edit::Trash).await.unwrap();
+        // Only the trashed folder itself is in the trash; what is inside
+        // leaves the library with it
+        assert_eq!(names(&dir.trash().await.unwrap()), ["Work"]);
+        assert_eq!(names(&dir.library().await.unwrap()), ["Keep"]);
+
+        dir.edit(&work_item, &Edit::Purge).await.unwrap();
+        assert!(dir.trash().await.unwrap().is_empty());
+        let mut left: Vec<String> = std::fs::read_dir(root.path()).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        left.sort();
+        let mut expected = vec![format!("{}.content", keep.id), format!("{}.metadata", keep.id), format!("{}.pdf", keep.id)];
+        expected.sort();
+        assert_eq!(left, expected);
+        assert!(!left.iter().any(|name| name.starts_with(&inner.id) || name.starts_with(&old)));
+    }
+}
@@ -0,0 +1,364 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+// --- On-device document files ---
+//
+// `<id>.metadata` and `<id>.content` as xochitl writes them. Firmware
+// versions add, drop and retype fields, so everything but the name and type
+// is optional, and fields not modelled here are kept in `other` and written
+// back unchanged.
+
+/// `<id>.metadata`: what the document is called and where it lives.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    pub visible_name: String,
+    /// `DocumentType` or `CollectionType` (a folder).
+    #[serde(rename = "type")]
+    pub item_type: String,
+    /// ID of the containing folder; empty at the top level, `trash` once
+    /// trashed.
+    #[serde(default)]
+    pub parent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<Timestamp>,
+    /// Set when the document was deleted but not yet synced away.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Starred in the tablet UI.
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Metadata {
+    pub fn parse(text: &str) -> Result<Self> {
+        serde_json::from_str(text).context("Invalid .metadata file")
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// Milliseconds since the epoch. Written as a string, as xochitl does, but
+/// read from a number too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self(Utc::now().timestamp_millis())
+    }
+
+    pub fn to_datetime(self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp_millis(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(i64),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(ms) => Ok(Self(ms)),
+            Raw::Text(text) => text
+                .trim()
+                .parse()
+                .map(Self)
+                .map_err(|_| serde::de::Error::custom(format!("invalid timestamp `{}`", text))),
+        }
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+/// `<id>.content`: the document's format and pages.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    /// `pdf`, `epub`, or `notebook` (empty on some firmware) for notebooks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<String>,
+    /// 1 for a plain `pages` list, 2 for `cPages`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format_version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<u32>,
+    /// Page IDs in order, before firmware 3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pages: Option<Vec<String>>,
+    /// Page IDs in order, from firmware 3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub c_pages: Option<CPages>,
+    /// `portrait` or `landscape`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Tag>,
+    /// Tags given to single pages, each naming its page in `pageId`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub page_tags: Vec<Tag>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Content {
+    pub fn parse(text: &str) -> Result<Self> {
+        serde_json::from_str(text).context("Invalid .content file")
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    /// IDs of the pages still in the document, in order.
+    pub fn page_ids(&self) -> Vec<&str> {
+        self.page_sources().into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// IDs of the pages still in the document, in order, each with the page
+    /// of the imported PDF it shows (counting from 0). Pages added on the
+    /// tablet have none.
+    pub fn page_sources(&self) -> Vec<(&str, Option<usize>)> {
+        match (&self.pages, &self.c_pages) {
+            (Some(pages), _) => pages.iter().enumerate().map(|(i, id)| (id.as_str(), Some(i))).collect(),
+            (None, Some(c_pages)) => c_pages
+                .pages
+                .iter()
+                .filter(|p| p.deleted.is_none())
+                .map(|p| (p.id.as_str(), p.redirect()))
+                .collect(),
+            (None, None) => Vec::new(),
+        }
+    }
+
+    /// `pageCount` if present, otherwise the number of pages listed.
+    pub fn page_count(&self) -> Option<u32> {
+        let listed = self.page_ids().len();
+        self.page_count.or((listed > 0).then_some(listed as u32))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CPages {
+    #[serde(default)]
+    pub pages: Vec<CPage>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// One page of a `cPages` list. Removed pages stay listed with `deleted`
+/// set.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CPage {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<Value>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl CPage {
+    fn redirect(&self) -> Option<usize> {
+        let value = self.other.get("redir")?.get("value")?.as_i64()?;
+        usize::try_from(value).ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Tag {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// `<id>.highlights/<page>.json`: text marked with the highlighter on
+/// firmware 2, which snaps highlights to the text of the PDF or EPUB.
+/// Firmware 3 keeps them in the page's `.rm` file instead.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Highlights {
+    /// One list per layer.
+    #[serde(default)]
+    pub highlights: Vec<Vec<HighlightRange>>,
+}
+
+impl Highlights {
+    pub fn parse(text: &str) -> Result<Self> {
+        serde_json::from_str(text).context("Invalid .highlights file")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HighlightRange {
+    pub text: String,
+    #[serde(default)]
+    pub color: u32,
+    /// Offset of the text in the page's text.
+    #[serde(default)]
+    pub start: Option<u32>,
+    /// One rectangle per marked line.
+    #[serde(default)]
+    pub rects: Vec<HighlightRect>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct HighlightRect {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub width: f32,
+    #[serde(default)]
+    pub height: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA_V2: &str = r#"{
+        "deleted": false,
+        "lastModified": "1760000000000",
+        "lastOpened": "1760000001000",
+        "lastOpenedPage": 3,
+        "metadatamodified": false,
+        "modified": false,
+        "parent": "w1",
+        "pinned": true,
+        "synced": true,
+        "type": "DocumentType",
+        "version": 4,
+        "visibleName": "Paper"
+    }"#;
+
+    const CONTENT_V1: &str = r#"{
+        "extraMetadata": {"LastTool": "Ballpoint"},
+        "fileType": "notebook",
+        "fontName": "",
+        "lastOpenedPage": 0,
+        "lineHeight": -1,
+        "margins": 100,
+        "orientation": "portrait",
+        "pageCount": 2,
+        "pages": ["p1", "p2"],
+        "textScale": 1,
+        "transform": {}
+    }"#;
+
+    const CONTENT_V2: &str = r#"{
+        "cPages": {
+            "lastOpened": {"timestamp": "1:1", "value": "p2"},
+            "original": {"timestamp": "0:0", "value": -1},
+            "pages": [
+                {"id": "p1", "idx": {"timestamp": "1:2", "value": "ba"}, "redir": {"timestamp": "1:4", "value": 0}, "template": {"timestamp": "1:1", "value": "Blank"}},
+                {"id": "gone", "deleted": {"timestamp": "1:3", "value": 1}, "idx": {"timestamp": "1:2", "value": "bb"}},
+                {"id": "p2", "idx": {"timestamp": "1:2", "value": "bc"}}
+            ],
+            "uuids": [{"first": "abc", "second": 1}]
+        },
+        "fileType": "pdf",
+        "formatVersion": 2,
+        "orientation": "landscape",
+        "pageTags": [{"name": "urgent", "pageId": "p2", "timestamp": 1760000000000}],
+        "sizeInBytes": "12345",
+        "tags": [{"name": "work", "timestamp": 1760000000000}, {"name": "todo"}]
+    }"#;
+
+    /// Parses `text`, writes it back and checks nothing was lost.
+    fn round_trip<T>(text: &str, parse: fn(&str) -> Result<T>, write: fn(&T) -> Result<Vec<u8>>) -> T
+    where
+        T: PartialEq + std::fmt::Debug,
+    {
+        let parsed = parse(text).unwrap();
+        let written = String::from_utf8(write(&parsed).unwrap()).unwrap();
+        assert_eq!(parse(&written).unwrap(), parsed);
+        let original: Value = serde_json::from_str(text).unwrap();
+        let rewritten: Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(rewritten, original);
+        parsed
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let meta = round_trip(METADATA_V2, Metadata::parse, Metadata::to_json);
+        assert_eq!(meta.visible_name, "Paper");
+        assert_eq!(meta.item_type, "DocumentType");
+        assert_eq!(meta.parent, "w1");
+        assert!(meta.pinned && !meta.deleted);
+        assert_eq!(meta.last_modified, Some(Timestamp(1_760_000_000_000)));
+        assert_eq!(meta.other["version"], 4);
+    }
+
+    #[test]
+    fn metadata_tolerates_old_and_missing_fields() {
+        let meta = Metadata::parse(r#"{"visibleName": "Work", "type": "CollectionType", "lastModified": 1760000000000}"#).unwrap();
+        assert_eq!(meta.parent, "");
+        assert!(!meta.pinned && !meta.deleted);
+        assert_eq!(meta.last_modified.and_then(Timestamp::to_datetime).unwrap().to_rfc3339(), "2025-10-09T08:53:20+00:00");
+        // Numeric timestamps are written back the way current firmware expects
+        let written: Value = serde_json::from_slice(&meta.to_json().unwrap()).unwrap();
+        assert_eq!(written["lastModified"], "1760000000000");
+    }
+
+    #[test]
+    fn metadata_needs_a_name_and_type() {
+        assert!(Metadata::parse(r#"{"type": "DocumentType"}"#).is_err());
+        assert!(Metadata::parse(r#"{"visibleName": "x", "type": "DocumentType", "lastModified": "soon"}"#).is_err());
+    }
+
+    #[test]
+    fn content_v1_round_trips() {
+        let content = round_trip(CONTENT_V1, Content::parse, Content::to_json);
+        assert_eq!(content.file_type.as_deref(), Some("notebook"));
+        assert_eq!(content.orientation.as_deref(), Some("portrait"));
+        assert_eq!(content.page_ids(), ["p1", "p2"]);
+        assert_eq!(content.page_count(), Some(2));
+    }
+
+    #[test]
+    fn content_v2_round_trips() {
+        let content = round_trip(CONTENT_V2, Content::parse, Content::to_json);
+        assert_eq!(content.format_version, Some(2));
+        assert_eq!(content.page_sources(), [("p1", Some(0)), ("p2", None)]);
+        assert_eq!(content.page_count(), Some(2));
+        let tags: Vec<&str> = content.tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(tags, ["work", "todo"]);
+        assert_eq!(content.page_tags[0].name, "urgent");
+        assert_eq!(content.page_tags[0].other["pageId"], "p2");
+        assert_eq!(content.other["sizeInBytes"], "12345");
+    }
+
+    #[test]
+    fn highlights_parse() {
+        let text = r#"{"highlights": [[
+            {"color": 3, "length": 11, "rects": [{"height": 20.5, "width": 150, "x": 120.25, "y": 300}], "start": 42, "text": "Hello world"},
+            {"text": "no offset", "rects": []}
+        ]]}"#;
+        let ranges = &Highlights::parse(text).unwrap().highlights[0];
+        assert_eq!(ranges.len(), 2);
+        assert_eq!((ranges[0].text.as_str(), ranges[0].color, ranges[0].start), ("Hello world", 3, Some(42)));
+        assert_eq!((ranges[0].rects[0].x, ranges[0].rects[0].y), (120.25, 300.0));
+        assert_eq!(ranges[1].start, None);
+        assert!(Highlights::parse("{}").unwrap().highlights.is_empty());
+    }
+
+    #[test]
+    fn empty_content_is_accepted() {
+        let content = round_trip("{}", Content::parse, Content::to_json);
+        assert_eq!(content, Content::default());
+        assert_eq!(content.page_count(), None);
+    }
+}
@@ -0,0 +1,84 @@
+use super::{Backend, Body};
+use crate::Item;
+use anyhow::{anyhow, Context, Result};
+use futures::{future::BoxFuture, StreamExt};
+use reqwest::{multipart, Client};
+use std::path::Path;
+use tokio::sync::Mutex;
+
+/// The tablet's USB web interface.
+pub struct Http {
+    client: Client,
+    base_url: String,
+}
+
+/// Held from listing a folder until the request that depends on it is
+/// done, since the tablet remembers which folder was listed last. Shared by
+/// every `Http`, as the watch folders and the TUI each open their own.
+static LISTED: Mutex<()> = Mutex::const_new(());
+
+impl Http {
+    pub fn new(base_url: &str) -> Self {
+        Self { client: Client::new(), base_url: base_url.to_string() }
+    }
+
+    async fn fetch(&self, parent: &Option<String>) -> Result<Vec<Item>> {
+        let url = match parent {
+            Some(id) => format!("{}/documents/{}", self.base_url, id),
+            None => format!("{}/documents/", self.base_url),
+        };
+        let resp = self.client.get(&url).send().await?;
+        let items: Vec<Item> = resp.json().await?;
+        Ok(items)
+    }
+}
+
+impl Backend for Http {
+    fn list<'a>(&'a self, parent: &'a Option<String>) -> BoxFuture<'a, Result<Vec<Item>>> {
+        Box::pin(async move {
+            let _listed = LISTED.lock().await;
+            self.fetch(parent).await
+        })
+    }
+
+    fn download<'a>(&'a self, item: &'a Item) -> BoxFuture<'a, Result<Body>> {
+        Box::pin(async move {
+            let url = format!("{}/download/{}/pdf", self.base_url, item.id);
+            let resp = self.client.get(&url).send().await?.error_for_status()?;
+            let len = resp.content_length();
+            let mut received = 0u64;
+            let chunks = resp.bytes_stream().map(move |chunk| {
+                let chunk = chunk.with_context(|| format!("connection lost after {} bytes", received))?;
+                received += chunk.len() as u64;
+                Ok(chunk.to_vec())
+            });
+            Ok(Body { len, chunks: Box::pin(chunks) })
+        })
+    }
+
+    fn upload<'a>(&'a self, parent: &'a Option<String>, path: &'a Path, file_name: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let file_bytes = tokio::fs::read(path).await?;
+
+            // The web interface puts uploads in the folder listed last, so no
+            // other listing may come between
+            let _listed = LISTED.lock().await;
+            self.fetch(parent).await?;
+
+            let part = multipart::Part::bytes(file_bytes).file_name(file_name.to_string());
+            let form = multipart::Form::new().part("file", part);
+
+            let url = format!("{}/upload", self.base_url);
+            let resp = self.client.post(&url).multipart(form).send().await?;
+            if resp.status().is_success() {
+                Ok(())
+            } else {
+                Err(anyhow!("Upload failed with status: {}", resp.status()))
+            }
+        })
+    }
+
+    fn describe(&self) -> String {
+        self.base_url.clone()
+    }
+}
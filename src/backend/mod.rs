@@ -0,0 +1,241 @@
+use crate::{lines, Item};
+use anyhow::{anyhow, Result};
+use futures::{future::BoxFuture, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path, sync::Arc};
+
+mod dir;
+mod format;
+mod http;
+mod ssh;
+mod xochitl;
+
+pub use xochitl::Files;
+
+// --- Backends ---
+//
+// Everything that talks to a tablet goes through a `Backend`: the USB web
+// interface, the tablet's data directory over SSH, or a copy of that
+// directory on disk. Which one is used comes from the active profile.
+
+/// A document body on its way to disk.
+pub struct Body {
+    /// Announced length, if the source knows it up front.
+    pub len: Option<u64>,
+    pub chunks: BoxStream<'static, Result<Vec<u8>>>,
+}
+
+impl Body {
+    /// A body that is already in memory.
+    fn whole(bytes: Vec<u8>) -> Self {
+        Self {
+            len: Some(bytes.len() as u64),
+            chunks: Box::pin(futures::stream::once(async { Ok(bytes) })),
+        }
+    }
+}
+
+/// The tablet (or the directory standing in for it) could not be reached
+/// at all, as opposed to refusing one request.
+#[derive(Debug)]
+pub struct Unreachable(pub String);
+
+impl fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Unreachable {}
+
+/// A change to an existing document or folder.
+#[derive(Clone)]
+pub enum Edit {
+    Rename(String),
+    /// Into this folder, or the top level for `None`.
+    Move(Option<String>),
+    /// Into the tablet's trash, from where it can still be restored.
+    Trash,
+    /// Starred as a favourite, or not.
+    Pin(bool),
+    /// Tags to give the document and tags to take away.
+    Tags { add: Vec<String>, remove: Vec<String> },
+    /// Out of the trash and off the tablet for good, with everything
+    /// inside it.
+    Purge,
+}
+
+/// What was marked and written in a document.
+pub struct Notes {
+    /// Tags given to the document on the tablet.
+    pub tags: Vec<String>,
+    /// Every page, in order.
+    pub pages: Vec<PageNotes>,
+}
+
+/// What was marked and written on one page of a document.
+pub struct PageNotes {
+    /// Position in the document, from 1.
+    pub number: usize,
+    /// Page of the imported PDF or EPUB it shows, from 1. Pages added on
+    /// the tablet have none.
+    pub source: Option<usize>,
+    pub highlights: Vec<lines::Highlight>,
+    /// `None` for pages that were never drawn on.
+    pub strokes: Option<lines::Page>,
+}
+
+pub trait Backend: Send + Sync {
+    /// The documents and folders directly inside folder `parent`, or the
+    /// top level for `None`.
+    fn list<'a>(&'a self, parent: &'a Option<String>) -> BoxFuture<'a, Result<Vec<Item>>>;
+
+    /// Every document and folder outside the trash, each with its parent
+    /// set. Walks the folders one `list` at a time unless the backend can
+    /// read everything at once.
+    fn library(&self) -> BoxFuture<'_, Result<Vec<Item>>> {
+        Box::pin(async move {
+            let mut items = Vec::new();
+            let mut folders = vec![None];
+            while let Some(parent) = folders.pop() {
+                for mut item in self.list(&parent).await? {
+                    item.parent = parent.clone().unwrap_or_default();
+                    if item.is_folder() {
+                        folders.push(Some(item.id.clone()));
+                    }
+                    items.push(item);
+                }
+            }
+            Ok(items)
+        })
+    }
+
+    /// What is in the tablet's trash, newest first. The web interface does
+    /// not show it.
+    fn trash(&self) -> BoxFuture<'_, Result<Vec<Item>>> {
+        Box::pin(async move { Err(anyhow!("{} cannot see the trash; use an ssh or local profile", self.describe())) })
+    }
+
+    /// The PDF of document `item`.
+    fn download<'a>(&'a self, item: &'a Item) -> BoxFuture<'a, Result<Body>>;
+
+    /// The tags of `item` and the highlights and strokes of each of its
+    /// pages. Needs the document's own files, which the web interface does
+    /// not offer.
+    fn notes<'a>(&'a self, _item: &'a Item) -> BoxFuture<'a, Result<Notes>> {
+        Box::pin(async move { Err(anyhow!("{} cannot read highlights or pages; use an ssh or local profile", self.describe())) })
+    }
+
+    /// Uploads `path` into folder `parent`, named `file_name`.
+    fn upload<'a>(&'a self, parent: &'a Option<String>, path: &'a Path, file_name: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Whether `edit` and `mkdir` work here. The web interface can only add
+    /// documents.
+    fn can_edit(&self) -> bool {
+        false
+    }
+
+    /// Renames, moves, trashes, stars, tags or purges `item`.
+    fn edit<'a>(&'a self, _item: &'a Item, _edit: &'a Edit) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { Err(self.read_only()) })
+    }
+
+    /// Creates folder `name` inside `parent` and returns its ID.
+    fn mkdir<'a>(&'a self, _parent: &'a Option<String>, _name: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { Err(self.read_only()) })
+    }
+
+    fn read_only(&self) -> anyhow::Error {
+        anyhow!("{} cannot change documents; use an ssh or local profile", self.describe())
+    }
+
+    /// Makes finished changes show up on the tablet. Called once after a
+    /// batch rather than after every file.
+    fn settle(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Where this backend reads from, for status messages.
+    fn describe(&self) -> String;
+
+    /// The tablet's own files outside the data directory, if this backend
+    /// can reach them. Only SSH can.
+    fn system(&self) -> Option<&dyn System> {
+        None
+    }
+}
+
+/// Files of the tablet's system, such as page templates and splash screens,
+/// by absolute path. Changes show up after `Backend::settle`.
+pub trait System: Send + Sync {
+    /// Where the page templates and their `templates.json` live.
+    fn templates_dir(&self) -> &str;
+
+    /// Where the splash screens, such as `suspended.png`, live.
+    fn splash_dir(&self) -> &str;
+
+    /// The contents of each of `paths` that exists.
+    fn read<'a>(&'a self, paths: &'a [String]) -> BoxFuture<'a, Result<Files>>;
+
+    fn write<'a>(&'a self, path: &'a str, bytes: &'a [u8]) -> BoxFuture<'a, Result<()>>;
+
+    /// Deletes `paths`; missing ones are fine.
+    fn remove<'a>(&'a self, paths: &'a [String]) -> BoxFuture<'a, Result<()>>;
+}
+
+/// How to reach a tablet, chosen with `[device] profile` or `--profile`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "backend", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Profile {
+    /// The USB web interface.
+    Http { endpoint: String },
+    /// The data directory on the tablet, through the `ssh` command.
+    Ssh {
+        /// `user@host` or a `Host` from `~/.ssh/config`.
+        host: String,
+        #[serde(default = "ssh::default_path")]
+        path: String,
+        /// Restart the tablet's UI after uploads so they appear.
+        #[serde(default = "default_true")]
+        restart: bool,
+        /// Page templates and `templates.json`.
+        #[serde(default = "ssh::default_templates")]
+        templates: String,
+        /// Splash screens such as `suspended.png`.
+        #[serde(default = "ssh::default_splash")]
+        splash: String,
+    },
+    /// A copy of the data directory, e.g. a backup.
+    Local { path: String },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Profile {
+    pub fn validate(&self, name: &str) -> Result<()> {
+        match self {
+            Self::Http { endpoint } if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) => {
+                Err(anyhow!("`profiles.{}.endpoint` must start with http:// or https:// (got `{}`)", name, endpoint))
+            }
+            Self::Ssh { host, .. } if host.trim().is_empty() => Err(anyhow!("`profiles.{}.host` cannot be empty", name)),
+            Self::Ssh { path, .. } | Self::Local { path } if path.trim().is_empty() => {
+                Err(anyhow!("`profiles.{}.path` cannot be empty", name))
+            }
+            Self::Ssh { templates, splash, .. } => match [("templates", templates), ("splash", splash)].into_iter().find(|(_, dir)| !dir.starts_with('/')) {
+                Some((key, dir)) => Err(anyhow!("`profiles.{}.{}` must be an absolute path (got `{}`)", name, key, dir)),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn open(&self) -> Arc<dyn Backend> {
+        match self {
+            Self::Http { endpoint } => Arc::new(http::Http::new(endpoint.trim_end_matches('/'))),
+            Self::Ssh { host, path, restart, templates, splash } => Arc::new(ssh::Ssh::new(host, path, *restart, templates, splash)),
+            Self::Local { path } => Arc::new(dir::Dir::new(crate::expand_path(path))),
+        }
+    }
+}
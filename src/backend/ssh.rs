@@ -0,0 +1,357 @@
+use super::{xochitl, Backend, Body, Edit, Files, Notes, System, Unreachable};
+use crate::Item;
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use std::{
+    path::Path,
+    process::Stdio,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+pub fn default_path() -> String {
+    "/home/root/.local/share/remarkable/xochitl".into()
+}
+
+pub fn default_templates() -> String {
+    "/usr/share/remarkable/templates".into()
+}
+
+pub fn default_splash() -> String {
+    "/usr/share/remarkable".into()
+}
+
+/// Prints, for every document in the data directory, its id, metadata,
+/// content and stored file size on four lines. JSON has no raw newlines
+/// inside strings, so squeezing them out keeps each file on one line.
+const LIST_SCRIPT: &str = r#"for f in *.metadata; do
+  [ -e "$f" ] || continue
+  id=${f%.metadata}
+  echo "$id"
+  tr -d '\n' < "$f"; echo
+  [ -f "$id.content" ] && tr -d '\n' < "$id.content"; echo
+  echo "$(stat -c %s "$id.pdf" "$id.epub" 2>/dev/null | head -n 1)"
+done"#;
+
+/// Prints file `$f`, if it exists, as a line with its name and size
+/// followed by its bytes; see `files`.
+const FETCH_FILE: &str = r#"[ -f "$f" ] || continue; echo "$f $(wc -c < "$f")"; cat "$f""#;
+
+/// Splits the output of `FETCH_FILE` into names and contents.
+fn files(mut output: &[u8]) -> Result<Files> {
+    let mut files = Vec::new();
+    while let Some(end) = output.iter().position(|&b| b == b'\n') {
+        let header = String::from_utf8_lossy(&output[..end]).to_string();
+        let (name, size) = header.rsplit_once(' ').ok_or_else(|| anyhow!("Unexpected output from the tablet"))?;
+        let size: usize = size.trim().parse().context("Unexpected output from the tablet")?;
+        let body = output.get(end + 1..).and_then(|rest| rest.get(..size)).ok_or_else(|| anyhow!("{} arrived incomplete", name))?;
+        files.push((name.to_string(), body.to_vec()));
+        output = &output[end + 1 + size..];
+    }
+    // A header cut off before its newline
+    if !output.is_empty() {
+        return Err(anyhow!("Unexpected output from the tablet"));
+    }
+    Ok(files)
+}
+
+/// Splits the output of `LIST_SCRIPT` into records.
+fn records(output: &str) -> Result<Vec<xochitl::Record>> {
+    let lines: Vec<&str> = output.lines().collect();
+    let chunks = lines.chunks_exact(4);
+    if !chunks.remainder().is_empty() {
+        return Err(anyhow!("The document list from the tablet arrived incomplete"));
+    }
+    Ok(chunks
+        .map(|record| xochitl::Record {
+            id: record[0].to_string(),
+            metadata: record[1].to_string(),
+            content: Some(record[2].to_string()).filter(|c| !c.is_empty()),
+            size: record[3].trim().parse().ok(),
+        })
+        .collect())
+}
+
+/// The tablet's data directory, through the system `ssh` command so that
+/// `~/.ssh/config`, keys and agents all apply. Password prompts are turned
+/// off since they would land in the middle of the TUI.
+pub struct Ssh {
+    host: String,
+    path: String,
+    restart: bool,
+    templates: String,
+    splash: String,
+    /// Something was uploaded since the last restart of the tablet's UI.
+    dirty: AtomicBool,
+}
+
+impl Ssh {
+    pub fn new(host: &str, path: &str, restart: bool, templates: &str, splash: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            path: path.trim_end_matches('/').to_string(),
+            restart,
+            templates: templates.trim_end_matches('/').to_string(),
+            splash: splash.trim_end_matches('/').to_string(),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    fn command(&self, script: &str) -> Command {
+        let mut cmd = Command::new("ssh");
+        cmd.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10", &self.host])
+            .arg(format!("cd {} && {}", quote(&self.path), script))
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        cmd
+    }
+
+    /// Runs `script` in the data directory and returns its output.
+    async fn run(&self, script: &str) -> Result<Vec<u8>> {
+        let output = self.command(script).output().await.context("Could not run ssh")?;
+        self.check(&output)?;
+        Ok(output.stdout)
+    }
+
+    fn check(&self, output: &std::process::Output) -> Result<()> {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = format!("ssh {}: {}", self.host, stderr.trim());
+        match output.status.code() {
+            Some(0) => Ok(()),
+            // ssh's own failures, such as no route or refused keys
+            Some(255) => Err(Unreachable(message).into()),
+            _ => Err(anyhow!(message)),
+        }
+    }
+
+    /// Every document in the data directory, fetched in one go.
+    async fn records(&self) -> Result<Vec<xochitl::Record>> {
+        records(&String::from_utf8(self.run(LIST_SCRIPT).await?)?)
+    }
+
+    /// The files `item` is drawn from, fetched in one go.
+    async fn sources(&self, item: &Item) -> Result<xochitl::Sources> {
+        let id = quote(&item.id);
+        let script = format!("for f in {id}.content {id}.pdf {id}/*.rm {id}.highlights/*.json; do {}; done", FETCH_FILE, id = id);
+        let mut sources = xochitl::Sources::default();
+        for (name, bytes) in files(&self.run(&script).await?)? {
+            let page = |dir: &str, ext: &str| {
+                name.strip_prefix(&format!("{}{}/", item.id, dir)).and_then(|n| n.strip_suffix(ext)).map(str::to_string)
+            };
+            if name.ends_with(".content") {
+                sources.content = Some(String::from_utf8(bytes)?);
+            } else if name.ends_with(".pdf") {
+                sources.pdf = Some(bytes);
+            } else if let Some(page) = page("", ".rm") {
+                sources.pages.insert(page, bytes);
+            } else if let Some(page) = page(".highlights", ".json") {
+                sources.highlights.insert(page, String::from_utf8(bytes)?);
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Writes `bytes` to `name` in the data directory.
+    async fn write(&self, name: &str, bytes: &[u8]) -> Result<()> {
+        let mut child = self
+            .command(&format!("cat > {}", quote(name)))
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Could not run ssh")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(bytes).await?;
+        }
+        let output = child.wait_with_output().await?;
+        self.check(&output).with_context(|| format!("Could not write {}", name))
+    }
+}
+
+impl System for Ssh {
+    fn templates_dir(&self) -> &str {
+        &self.templates
+    }
+
+    fn splash_dir(&self) -> &str {
+        &self.splash
+    }
+
+    fn read<'a>(&'a self, paths: &'a [String]) -> BoxFuture<'a, Result<Files>> {
+        Box::pin(async move {
+            if paths.is_empty() {
+                return Ok(Vec::new());
+            }
+            let names: Vec<String> = paths.iter().map(|p| quote(p)).collect();
+            files(&self.run(&format!("for f in {}; do {}; done", names.join(" "), FETCH_FILE)).await?)
+        })
+    }
+
+    fn write<'a>(&'a self, path: &'a str, bytes: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            match Ssh::write(self, path, bytes).await {
+                // Newer firmware mounts the root filesystem read-only
+                Err(e) if format!("{:#}", e).contains("Read-only file system") => {
+                    return Err(e.context("The tablet's root filesystem is read-only; `mount -o remount,rw /` there allows changes until the next reboot"));
+                }
+                result => result?,
+            }
+            self.dirty.store(true, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, paths: &'a [String]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if paths.is_empty() {
+                return Ok(());
+            }
+            let names: Vec<String> = paths.iter().map(|p| quote(p)).collect();
+            self.run(&format!("rm -f -- {}", names.join(" "))).await?;
+            self.dirty.store(true, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+}
+
+impl Backend for Ssh {
+    fn list<'a>(&'a self, parent: &'a Option<String>) -> BoxFuture<'a, Result<Vec<Item>>> {
+        Box::pin(async move { Ok(xochitl::items(self.records().await?, parent)) })
+    }
+
+    fn library(&self) -> BoxFuture<'_, Result<Vec<Item>>> {
+        Box::pin(async move { Ok(xochitl::library(self.records().await?)) })
+    }
+
+    fn trash(&self) -> BoxFuture<'_, Result<Vec<Item>>> {
+        Box::pin(async move { Ok(xochitl::trash(self.records().await?)) })
+    }
+
+    fn download<'a>(&'a self, item: &'a Item) -> BoxFuture<'a, Result<Body>> {
+        Box::pin(async move { xochitl::render(item, self.sources(item).await?).await })
+    }
+
+    fn notes<'a>(&'a self, item: &'a Item) -> BoxFuture<'a, Result<Notes>> {
+        Box::pin(async move { xochitl::notes(item, self.sources(item).await?) })
+    }
+
+    fn upload<'a>(&'a self, parent: &'a Option<String>, path: &'a Path, file_name: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            for (name, bytes) in xochitl::new_document(parent, path, file_name)? {
+                self.write(&name, &bytes).await?;
+            }
+            self.dirty.store(true, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn can_edit(&self) -> bool {
+        true
+    }
+
+    fn edit<'a>(&'a self, item: &'a Item, edit: &'a Edit) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if let Edit::Purge = edit {
+                // Metadata first, so nothing half-deleted shows up
+                let ids: Vec<String> = xochitl::descendants(self.records().await?, &item.id).iter().map(|id| quote(id)).collect();
+                let metadata: Vec<String> = ids.iter().map(|id| format!("{}.metadata", id)).collect();
+                let rest: Vec<String> = ids.iter().map(|id| format!("{id} {id}.*", id = id)).collect();
+                self.run(&format!("rm -f -- {} && rm -rf -- {}", metadata.join(" "), rest.join(" "))).await?;
+                self.dirty.store(true, Ordering::Relaxed);
+                return Ok(());
+            }
+            let id = quote(&item.id);
+            let script = format!("for f in {id}.metadata {id}.content; do {}; done", FETCH_FILE, id = id);
+            let (mut metadata, mut content) = (None, None);
+            for (name, bytes) in files(&self.run(&script).await?)? {
+                let text = String::from_utf8(bytes)?;
+                if name.ends_with(".metadata") {
+                    metadata = Some(text);
+                } else {
+                    content = Some(text);
+                }
+            }
+            let metadata = metadata.ok_or_else(|| anyhow!("{}.metadata is missing", item.id))?;
+            for (name, bytes) in xochitl::apply(&item.id, &metadata, content.as_deref(), edit)? {
+                self.write(&name, &bytes).await?;
+            }
+            self.dirty.store(true, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn mkdir<'a>(&'a self, parent: &'a Option<String>, name: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let (id, files) = xochitl::new_folder(parent, name)?;
+            for (name, bytes) in files {
+                self.write(&name, &bytes).await?;
+            }
+            self.dirty.store(true, Ordering::Relaxed);
+            Ok(id)
+        })
+    }
+
+    fn settle(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            if self.restart && self.dirty.swap(false, Ordering::Relaxed) {
+                self.run("systemctl restart xochitl").await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn describe(&self) -> String {
+        format!("{}:{}", self.host, self.path)
+    }
+
+    fn system(&self) -> Option<&dyn System> {
+        Some(self)
+    }
+}
+
+/// `s` as a single-quoted shell word.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_fetched_files() {
+        let output = b"abc.content 2\n{}abc/page one.rm 5\nx\ny\n\nabc.pdf 0\n";
+        let files = files(output).unwrap();
+        assert_eq!(
+            files,
+            [
+                ("abc.content".to_string(), b"{}".to_vec()),
+                // Spaces in the name and newlines in the body are kept
+                ("abc/page one.rm".to_string(), b"x\ny\n\n".to_vec()),
+                ("abc.pdf".to_string(), Vec::new()),
+            ]
+        );
+        assert!(super::files(b"").unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let e = files(b"abc.content 2\n{}abc.pdf 10\n%PDF").unwrap_err();
+        assert_eq!(e.to_string(), "abc.pdf arrived incomplete");
+        assert!(files(b"abc.content 2\n{}abc.p").is_err());
+        assert!(files(b"abc.pdf\n").is_err());
+        assert!(files(format!("abc.pdf {}\n%PDF", usize::MAX).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn splits_listed_records() {
+        let output = "a\n{\"visibleName\":\"My notes\"}\n{}\n\nb\n{}\n\n2048\n";
+        let records = records(output).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].id.as_str(), records[0].metadata.as_str()), ("a", "{\"visibleName\":\"My notes\"}"));
+        assert_eq!((records[0].content.as_deref(), records[0].size), (Some("{}"), None));
+        assert_eq!((records[1].content.as_deref(), records[1].size), (None, Some(2048)));
+
+        // A listing cut off inside a record
+        assert!(super::records("a\n{}\n{}\n\nb\n{}\n").is_err());
+    }
+}
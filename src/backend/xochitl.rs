@@ -0,0 +1,336 @@
+use super::format::{Content, Highlights, Metadata, Tag, Timestamp};
+use super::{Body, Edit, Notes, PageNotes};
+use crate::{lines, render, Item};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// --- The xochitl data directory ---
+//
+// The tablet's UI (xochitl) keeps every document as `<id>.metadata` (name,
+// parent folder, type), `<id>.content` (file type, page count) and the
+// document itself, `<id>.pdf` or `<id>.epub`; notebooks only have pages in
+// `<id>/`. The SSH and local backends both read this layout.
+
+/// The files of one document as read from the data directory.
+pub struct Record {
+    pub id: String,
+    pub metadata: String,
+    pub content: Option<String>,
+    /// Size of the stored PDF or EPUB, if there is one.
+    pub size: Option<u64>,
+}
+
+/// The items of `records` directly inside folder `parent`, leaving out
+/// deleted and trashed ones. Records that cannot be parsed are skipped.
+pub fn items(records: Vec<Record>, parent: &Option<String>) -> Vec<Item> {
+    let parent = parent.as_deref().unwrap_or("");
+    let mut items: Vec<Item> = records.into_iter().filter_map(item).filter(|item| item.parent == parent).collect();
+    // The directory has no order of its own; folders first, then by name
+    items.sort_by(|a, b| b.is_folder().cmp(&a.is_folder()).then_with(|| a.visible_name.cmp(&b.visible_name)));
+    items
+}
+
+/// Every item of `records` outside the trash, in any folder.
+pub fn library(records: Vec<Record>) -> Vec<Item> {
+    let items: Vec<Item> = records.into_iter().filter_map(item).collect();
+    let parents: HashMap<&str, &str> = items.iter().map(|i| (i.id.as_str(), i.parent.as_str())).collect();
+    // Items inside a trashed folder keep that folder as their parent
+    let trashed = |item: &Item| {
+        let mut parent = item.parent.as_str();
+        for _ in 0..parents.len() {
+            match parent {
+                "" => return false,
+                "trash" => return true,
+                id => parent = parents.get(id).copied().unwrap_or(""),
+            }
+        }
+        false
+    };
+    items.iter().filter(|item| !trashed(item)).cloned().collect()
+}
+
+/// The items in the trash itself; what is inside a trashed folder is not
+/// listed separately.
+pub fn trash(records: Vec<Record>) -> Vec<Item> {
+    let mut items: Vec<Item> = records.into_iter().filter_map(item).filter(|item| item.parent == "trash").collect();
+    items.sort_by(|a, b| b.modified.cmp(&a.modified));
+    items
+}
+
+/// IDs of `id` and of everything inside it, for purging a trashed folder.
+pub fn descendants(records: Vec<Record>, id: &str) -> Vec<String> {
+    let items: Vec<Item> = records.into_iter().filter_map(item).collect();
+    let mut ids = vec![id.to_string()];
+    let mut i = 0;
+    while let Some(parent) = ids.get(i).cloned() {
+        let children: Vec<String> =
+            items.iter().filter(|item| item.parent == parent && !ids.contains(&item.id)).map(|item| item.id.clone()).collect();
+        ids.extend(children);
+        i += 1;
+    }
+    ids
+}
+
+/// Where this tool records the folder an item was trashed from, so it
+/// can go back there. The tablet itself does not keep it.
+const TRASHED_FROM: &str = "trashedFrom";
+
+/// The item `record` describes, unless it was deleted or cannot be parsed.
+fn item(record: Record) -> Option<Item> {
+    let meta = Metadata::parse(&record.metadata).ok()?;
+    if meta.deleted {
+        return None;
+    }
+    let content = record.content.and_then(|c| Content::parse(&c).ok()).unwrap_or_default();
+    let modified = meta.last_modified.and_then(Timestamp::to_datetime).map(|t| t.to_rfc3339());
+    let mut page_tags: Vec<String> = content.page_tags.iter().map(|t| t.name.clone()).collect();
+    page_tags.sort();
+    page_tags.dedup();
+    let origin = meta.other.get(TRASHED_FROM).and_then(|v| v.as_str()).map(str::to_string);
+    Some(Item {
+        id: record.id,
+        visible_name: meta.visible_name,
+        item_type: meta.item_type,
+        modified,
+        page_count: content.page_count(),
+        file_type: content.file_type.clone(),
+        size: record.size.map(Into::into),
+        parent: meta.parent,
+        pinned: meta.pinned,
+        tags: content.tags.into_iter().map(|t| t.name).collect(),
+        page_tags,
+        origin,
+    })
+}
+
+/// What the PDF of a document is made from.
+#[derive(Default)]
+pub struct Sources {
+    /// `<id>.content`.
+    pub content: Option<String>,
+    /// `<id>.pdf`: an imported PDF, or the tablet's conversion of an EPUB.
+    pub pdf: Option<Vec<u8>>,
+    /// `<id>/<page>.rm` by page ID.
+    pub pages: HashMap<String, Vec<u8>>,
+    /// `<id>.highlights/<page>.json` by page ID.
+    pub highlights: HashMap<String, String>,
+}
+
+impl Sources {
+    /// Page IDs in document order, each with the page of the stored PDF it
+    /// shows. Without a page list the `.rm` files go by name.
+    fn page_order(&self) -> Vec<(String, Option<usize>)> {
+        let content = self.content.as_deref().and_then(|c| Content::parse(c).ok()).unwrap_or_default();
+        let order: Vec<(String, Option<usize>)> =
+            content.page_sources().into_iter().map(|(id, source)| (id.to_string(), source)).collect();
+        if !order.is_empty() {
+            return order;
+        }
+        let mut ids: Vec<&String> = self.pages.keys().collect();
+        ids.sort();
+        ids.into_iter().enumerate().map(|(i, id)| (id.clone(), Some(i))).collect()
+    }
+}
+
+/// The PDF of `item`: the stored PDF as it is when nothing was drawn on
+/// it, otherwise the strokes of every page drawn over it, or on white
+/// pages for notebooks.
+pub async fn render(item: &Item, sources: Sources) -> Result<Body> {
+    let item = item.clone();
+    let bytes = tokio::task::spawn_blocking(move || render_pdf(&item, sources)).await??;
+    Ok(Body::whole(bytes))
+}
+
+fn render_pdf(item: &Item, mut sources: Sources) -> Result<Vec<u8>> {
+    if sources.pages.is_empty() {
+        match (sources.pdf.take(), item.file_type.as_deref()) {
+            (Some(pdf), _) => return Ok(pdf),
+            (None, Some("epub")) => {
+                return Err(anyhow!("\"{}\" is an EPUB the tablet has not converted yet; open it there once", item.visible_name))
+            }
+            (None, Some("pdf")) => return Err(anyhow!("\"{}\" has no stored PDF", item.visible_name)),
+            _ => {}
+        }
+    }
+    let mut order = sources.page_order();
+    if order.is_empty() {
+        // A new notebook still has a page
+        order.push((String::new(), None));
+    }
+
+    let mut pages = Vec::new();
+    for (i, (id, source)) in order.into_iter().enumerate() {
+        let strokes = match sources.pages.get(&id) {
+            Some(bytes) => Some(
+                lines::Page::parse(bytes).with_context(|| format!("Could not read page {} of \"{}\"", i + 1, item.visible_name))?,
+            ),
+            None => None,
+        };
+        let background = if sources.pdf.is_some() { source } else { None };
+        pages.push(render::PdfPage { strokes, background });
+    }
+    render::pdf(&pages, sources.pdf.as_deref())
+}
+
+/// The tags of `item` and the highlights and strokes of every page, from
+/// the page files and, for firmware 2, the `.highlights` files.
+pub fn notes(item: &Item, sources: Sources) -> Result<Notes> {
+    let content = sources.content.as_deref().and_then(|c| Content::parse(c).ok()).unwrap_or_default();
+    let tags = content.tags.into_iter().map(|t| t.name).collect();
+    let mut pages = Vec::new();
+    for (i, (id, source)) in sources.page_order().into_iter().enumerate() {
+        let context = || format!("Could not read page {} of \"{}\"", i + 1, item.visible_name);
+        let mut strokes = match sources.pages.get(&id) {
+            Some(bytes) => Some(lines::Page::parse(bytes).with_context(context)?),
+            None => None,
+        };
+        let mut highlights = strokes.as_mut().map(|page| std::mem::take(&mut page.highlights)).unwrap_or_default();
+        if let Some(text) = sources.highlights.get(&id) {
+            let ranges = Highlights::parse(text).with_context(context)?.highlights;
+            highlights.extend(ranges.into_iter().flatten().map(|range| {
+                let (left, top) = range.rects.first().map_or((0.0, 0.0), |r| (r.x, r.y));
+                lines::Highlight { text: range.text, color: lines::color(range.color, None), start: range.start, top, left }
+            }));
+        }
+        // Reading order: by offset in the text where every highlight has
+        // one, otherwise top to bottom
+        if highlights.iter().all(|h| h.start.is_some()) {
+            highlights.sort_by_key(|h| h.start);
+        } else {
+            highlights.sort_by(|a, b| a.top.total_cmp(&b.top).then(a.left.total_cmp(&b.left)));
+        }
+        pages.push(PageNotes {
+            number: i + 1,
+            source: source.filter(|_| sources.pdf.is_some()).map(|s| s + 1),
+            highlights,
+            strokes,
+        });
+    }
+    Ok(Notes { tags, pages })
+}
+
+/// Files to write into the data directory, or read from the system, as
+/// `(name, contents)` pairs.
+pub type Files = Vec<(String, Vec<u8>)>;
+
+/// A new document as the files to write: the document, its `.content` and
+/// its `.metadata`, in that order so the tablet never sees metadata without
+/// a document.
+pub fn new_document(parent: &Option<String>, path: &Path, file_name: &str) -> Result<Files> {
+    let file_type = match path.extension().map(|e| e.to_string_lossy().to_lowercase()).as_deref() {
+        Some("pdf") => "pdf",
+        Some("epub") => "epub",
+        _ => return Err(anyhow!("Only PDF and EPUB files can be uploaded")),
+    };
+    // The tablet names imported documents without the extension
+    let name = Path::new(file_name).file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let id = new_id(path);
+
+    let mut metadata = new_metadata(parent, "DocumentType", &name);
+    metadata.other.insert("lastOpened".into(), "0".into());
+    metadata.other.insert("lastOpenedPage".into(), 0.into());
+    let content = json!({
+        "extraMetadata": {},
+        "fileType": file_type,
+        "lastOpenedPage": 0,
+        "lineHeight": -1,
+        "margins": 100,
+        "pageCount": 0,
+        "textScale": 1,
+        "transform": {},
+    });
+
+    Ok(vec![
+        (format!("{}.{}", id, file_type), std::fs::read(path)?),
+        (format!("{}.content", id), serde_json::to_vec_pretty(&content)?),
+        (format!("{}.metadata", id), metadata.to_json()?),
+    ])
+}
+
+/// A new folder as its ID and the files to write.
+pub fn new_folder(parent: &Option<String>, name: &str) -> Result<(String, Files)> {
+    let id = new_id(Path::new(name));
+    let files = vec![
+        (format!("{}.content", id), Content::default().to_json()?),
+        (format!("{}.metadata", id), new_metadata(parent, "CollectionType", name).to_json()?),
+    ];
+    Ok((id, files))
+}
+
+/// Metadata for a new item, with the bookkeeping fields xochitl writes for
+/// its own.
+fn new_metadata(parent: &Option<String>, item_type: &str, name: &str) -> Metadata {
+    let mut other = serde_json::Map::new();
+    other.insert("metadatamodified".into(), false.into());
+    other.insert("modified".into(), false.into());
+    other.insert("synced".into(), false.into());
+    other.insert("version".into(), 0.into());
+    Metadata {
+        visible_name: name.into(),
+        item_type: item_type.into(),
+        parent: parent.clone().unwrap_or_default(),
+        last_modified: Some(Timestamp::now()),
+        deleted: false,
+        pinned: false,
+        other,
+    }
+}
+
+/// The files to write to apply `edit` to document `id`: its `.content`
+/// when tags change, then its `.metadata`, keeping every field the edit
+/// does not touch. The change is flagged the way the tablet flags its own,
+/// for cloud sync.
+pub fn apply(id: &str, metadata: &str, content: Option<&str>, edit: &Edit) -> Result<Files> {
+    let mut meta = Metadata::parse(metadata)?;
+    let mut files = Files::new();
+    match edit {
+        Edit::Rename(name) => meta.visible_name = name.clone(),
+        Edit::Move(parent) => {
+            meta.parent = parent.clone().unwrap_or_default();
+            meta.other.remove(TRASHED_FROM);
+        }
+        Edit::Trash => {
+            let from = std::mem::replace(&mut meta.parent, "trash".into());
+            meta.other.insert(TRASHED_FROM.into(), from.into());
+        }
+        Edit::Pin(pinned) => meta.pinned = *pinned,
+        Edit::Tags { add, remove } => {
+            let mut content = content.map(Content::parse).transpose()?.unwrap_or_default();
+            content.tags.retain(|t| !remove.contains(&t.name));
+            for name in add {
+                if !content.tags.iter().any(|t| &t.name == name) {
+                    let timestamp = Some(Timestamp::now().0);
+                    content.tags.push(Tag { name: name.clone(), timestamp, other: Default::default() });
+                }
+            }
+            files.push((format!("{}.content", id), content.to_json()?));
+        }
+        Edit::Purge => return Err(anyhow!("{} is deleted, not rewritten", id)),
+    }
+    meta.last_modified = Some(Timestamp::now());
+    meta.other.insert("metadatamodified".into(), true.into());
+    files.push((format!("{}.metadata", id), meta.to_json()?));
+    Ok(files)
+}
+
+/// A random-looking version 4 UUID, as the tablet uses for ids.
+fn new_id(path: &Path) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = Sha256::new();
+    hasher.update(Utc::now().timestamp_nanos_opt().unwrap_or_default().to_le_bytes());
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hasher.update(path.to_string_lossy().as_bytes());
+    let mut bytes = hasher.finalize()[..16].to_vec();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
@@ -0,0 +1,248 @@
+use crate::{device, notes::NotesFormat};
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: remarkable [--config <FILE>] [--profile <NAME>] [--dry-run] [COMMAND]
+
+Commands:
+  (none)    Start the TUI
+  config    Print the effective settings and where they were loaded from
+  watch     Upload new files from the [[watch]] folders until interrupted
+  sync [NAME] [--yes]
+            Show what syncing the [[sync]] pairs (or just NAME) would do,
+            then run it after asking; --yes skips the question
+  render <FILE.rm>...
+            Draw reMarkable page files (v3, v5 or v6) as SVG, written next
+            to each one as FILE.svg
+  notes <PATH> [DIR] [--format markdown|json|obsidian]
+            Write the highlights and handwriting of a tablet document, or
+            of every document in a folder, to DIR (default: notes.directory)
+            in the format of notes.format unless given; only changed files
+            are rewritten (needs an ssh or local profile)
+  mkdir <PATH>
+            Create a tablet folder such as /Work/Notes, along with any
+            missing folders above it (needs an ssh or local profile)
+  templates [list]
+            List the page templates on the tablet, marking custom ones
+  templates add <FILE>... --category <NAME> [--name <NAME>] [--landscape]
+            Install .png and .svg files as templates, one per file name,
+            in an existing category such as Lines or Grids
+  templates remove <NAME> [--force]
+            Uninstall a custom template; --force also removes stock ones
+  templates backup | restore [BACKUP]
+            Copy every template to the state directory, or put back a
+            backup (default: the latest; `original` is the set found
+            before the first change)
+  splash [list] | set <SCREEN> <FILE.png> | restore [SCREEN]
+            List, replace or restore the splash screens, such as
+            suspended or poweroff; the original is kept until restored
+            (templates and splash need an ssh profile)
+  verify [DIR]
+            Check downloads against the SHA256SUMS files in DIR (default:
+            the download directory) and everything below it
+
+Options:
+  -c, --config <FILE>  Read settings from FILE instead of the XDG config file
+  -p, --profile <NAME> Reach the tablet through [profiles.NAME] instead of
+                       the default
+  -n, --dry-run        Only show what transfers would do: the TUI previews
+                       downloads and uploads, `watch` lists pending files
+                       once, `sync` prints its plan, `mkdir` says
+                       what it would create, `notes` what it would
+                       write and `templates` and `splash` what they
+                       would change
+  -h, --help           Print this help
+";
+
+pub enum Command {
+    Tui,
+    Config,
+    Watch,
+    Sync { name: Option<String>, yes: bool },
+    Verify { dir: Option<PathBuf> },
+    Mkdir { path: Option<String> },
+    Render { files: Vec<PathBuf> },
+    Notes { path: Option<String>, dir: Option<PathBuf>, format: Option<NotesFormat> },
+    /// `templates` or `splash`.
+    Device(device::Request),
+    Help,
+}
+
+pub struct Cli {
+    pub config: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub dry_run: bool,
+    pub command: Command,
+}
+
+impl Cli {
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut config = None;
+        let mut profile = None;
+        let mut dry_run = false;
+        let mut command = None;
+        let mut device_args = Vec::new(); // Words after `templates` or `splash`
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                // Everything but help and the global options belongs to
+                // `templates` or `splash`, even words that name other commands
+                _ if !device_args.is_empty() && !is_global(&arg) && arg != "-h" && arg != "--help" => device_args.push(arg),
+                "-h" | "--help" => command = Some(Command::Help),
+                "-c" | "--config" => {
+                    let value = args.next().ok_or_else(|| anyhow!("`{}` needs a file path", arg))?;
+                    config = Some(PathBuf::from(value));
+                }
+                _ if arg.starts_with("--config=") => {
+                    config = Some(PathBuf::from(&arg["--config=".len()..]));
+                }
+                "-p" | "--profile" => {
+                    profile = Some(args.next().ok_or_else(|| anyhow!("`{}` needs a profile name", arg))?);
+                }
+                _ if arg.starts_with("--profile=") => {
+                    profile = Some(arg["--profile=".len()..].to_string());
+                }
+                "config" if command.is_none() => command = Some(Command::Config),
+                "watch" if command.is_none() => command = Some(Command::Watch),
+                "-n" | "--dry-run" => dry_run = true,
+                "sync" if command.is_none() => command = Some(Command::Sync { name: None, yes: false }),
+                "verify" if command.is_none() => command = Some(Command::Verify { dir: None }),
+                "mkdir" if command.is_none() => command = Some(Command::Mkdir { path: None }),
+                "render" if command.is_none() => command = Some(Command::Render { files: Vec::new() }),
+                "notes" if command.is_none() => command = Some(Command::Notes { path: None, dir: None, format: None }),
+                "templates" | "splash" if command.is_none() => device_args.push(arg),
+                "--format" => match &mut command {
+                    Some(Command::Notes { format, .. }) => {
+                        let value = args.next().ok_or_else(|| anyhow!("`{}` needs markdown, json or obsidian", arg))?;
+                        *format = Some(NotesFormat::parse(&value)?);
+                    }
+                    _ => return Err(anyhow!("`{}` only applies to `notes`", arg)),
+                },
+                "--yes" | "-y" => match &mut command {
+                    Some(Command::Sync { yes, .. }) => *yes = true,
+                    _ => return Err(anyhow!("`{}` only applies to `sync`", arg)),
+                },
+                _ if !arg.starts_with('-') && matches!(command, Some(Command::Sync { name: None, .. })) => {
+                    if let Some(Command::Sync { name, .. }) = &mut command {
+                        *name = Some(arg);
+                    }
+                }
+                _ if !arg.starts_with('-') && matches!(command, Some(Command::Verify { dir: None })) => {
+                    command = Some(Command::Verify { dir: Some(PathBuf::from(arg)) });
+                }
+                _ if !arg.starts_with('-') && matches!(command, Some(Command::Mkdir { path: None })) => {
+                    command = Some(Command::Mkdir { path: Some(arg) });
+                }
+                _ if !arg.starts_with('-') && matches!(command, Some(Command::Render { .. })) => {
+                    if let Some(Command::Render { files }) = &mut command {
+                        files.push(PathBuf::from(arg));
+                    }
+                }
+                _ if !arg.starts_with('-') && matches!(command, Some(Command::Notes { dir: None, .. })) => {
+                    if let Some(Command::Notes { path, dir, .. }) = &mut command {
+                        match path {
+                            None => *path = Some(arg),
+                            Some(_) => *dir = Some(PathBuf::from(arg)),
+                        }
+                    }
+                }
+                _ => return Err(anyhow!("Unexpected argument `{}`\n\n{}", arg, USAGE)),
+            }
+        }
+
+        if let Some((name, args)) = device_args.split_first().filter(|_| command.is_none()) {
+            command = Some(Command::Device(device::Request::parse(name, args)?));
+        }
+
+        if let Some(Command::Mkdir { path: None }) = command {
+            return Err(anyhow!("`mkdir` needs a tablet folder path, e.g. /Work/Notes"));
+        }
+        if let Some(Command::Notes { path: None, .. }) = command {
+            return Err(anyhow!("`notes` needs a tablet document or folder, e.g. /Work/Paper"));
+        }
+        if matches!(&command, Some(Command::Render { files }) if files.is_empty()) {
+            return Err(anyhow!("`render` needs at least one .rm file"));
+        }
+
+        Ok(Self {
+            config,
+            profile,
+            dry_run,
+            command: command.unwrap_or(Command::Tui),
+        })
+    }
+}
+
+/// Options that apply to every command, wherever they are given.
+fn is_global(arg: &str) -> bool {
+    matches!(arg, "-c" | "--config" | "-p" | "--profile" | "-n" | "--dry-run")
+        || arg.starts_with("--config=")
+        || arg.starts_with("--profile=")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{Request, SplashRequest, TemplateRequest};
+    use std::path::Path;
+
+    fn parse(args: &[&str]) -> Result<Cli> {
+        Cli::parse_from(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn device_words_that_name_commands() {
+        let cli = parse(&["templates", "remove", "notes"]).unwrap();
+        assert!(matches!(cli.command, Command::Device(Request::Templates(TemplateRequest::Remove { name, force: false })) if name == "notes"));
+
+        let cli = parse(&["templates", "add", "--name", "sync", "x.png", "--category", "Lines"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Device(Request::Templates(TemplateRequest::Add { name: Some(name), files, .. })) if name == "sync" && files == [PathBuf::from("x.png")]
+        ));
+
+        let cli = parse(&["splash", "set", "suspended", "watch"]).unwrap();
+        assert!(matches!(cli.command, Command::Device(Request::Splash(SplashRequest::Set { screen, file })) if screen == "suspended" && file == Path::new("watch")));
+
+        for word in ["config", "watch", "sync", "verify", "mkdir", "render"] {
+            let cli = parse(&["templates", "remove", word, "--force"]).unwrap();
+            assert!(matches!(cli.command, Command::Device(Request::Templates(TemplateRequest::Remove { name, force: true })) if name == word));
+        }
+    }
+
+    #[test]
+    fn global_options_around_device_commands() {
+        let cli = parse(&["templates", "-n", "remove", "Grid", "--profile", "wifi", "-c", "x.toml"]).unwrap();
+        assert!(cli.dry_run);
+        assert_eq!(cli.profile.as_deref(), Some("wifi"));
+        assert_eq!(cli.config, Some(PathBuf::from("x.toml")));
+        assert!(matches!(cli.command, Command::Device(Request::Templates(TemplateRequest::Remove { name, .. })) if name == "Grid"));
+
+        assert!(matches!(parse(&["templates", "-h"]).unwrap().command, Command::Help));
+        assert!(matches!(parse(&["splash", "set", "--help"]).unwrap().command, Command::Help));
+        assert!(matches!(parse(&["templates"]).unwrap().command, Command::Device(Request::Templates(TemplateRequest::List))));
+    }
+
+    #[test]
+    fn device_usage_errors() {
+        assert!(parse(&["templates", "remove"]).is_err());
+        assert!(parse(&["templates", "add", "x.png"]).is_err());
+        assert!(parse(&["splash", "set", "nope", "x.png"]).is_err());
+        assert!(parse(&["templates", "bogus"]).is_err());
+    }
+
+    #[test]
+    fn other_commands() {
+        assert!(matches!(parse(&["sync", "papers", "--yes"]).unwrap().command, Command::Sync { name: Some(n), yes: true } if n == "papers"));
+        assert!(matches!(parse(&["notes", "/Work", "out", "--format", "json"]).unwrap().command, Command::Notes { path: Some(p), dir: Some(_), format: Some(NotesFormat::Json) } if p == "/Work"));
+        assert!(matches!(parse(&[]).unwrap().command, Command::Tui));
+        assert!(parse(&["mkdir"]).is_err());
+        assert!(parse(&["watch", "extra"]).is_err());
+    }
+}
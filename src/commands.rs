@@ -0,0 +1,207 @@
+use crate::{config::Config, device, integrity, ledger, lines, notes, render, sync, watch};
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+// --- Subcommands ---
+//
+// What `remarkable <command>` does without the TUI. Each prints as it goes;
+// those that carry on past a failing file return whether all went well, so
+// the exit status can say.
+
+/// `remarkable watch`: uploads from the watch folders without the TUI.
+pub async fn run_watch(config: &Config, dry_run: bool) -> Result<()> {
+    if config.watch.is_empty() {
+        return Err(anyhow!("No [[watch]] folders configured"));
+    }
+    if dry_run {
+        let ledger = ledger::Ledger::open_default()?;
+        for entry in &config.watch {
+            let preview = watch::preview(entry, &ledger)?;
+            println!("{} ({})", preview, entry.directory);
+            for line in preview.lines() {
+                println!("  {}", line);
+            }
+        }
+        return Ok(());
+    }
+    for entry in &config.watch {
+        println!("Watching {} -> {}", entry.directory, entry.tablet_folder);
+    }
+    let mut events = crate::spawn_watchers(config)?;
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        };
+        let Some(event) = event else { return Ok(()) };
+        let line = match event {
+            watch::WatchEvent::Uploaded { local, tablet_folder } => format!("uploaded {} to {}/", local.display(), tablet_folder),
+            watch::WatchEvent::Failed { local, error } => format!("failed {}: {}", local.display(), error),
+            watch::WatchEvent::Skipped { local, reason } => format!("skipped {}: {}", local.display(), reason),
+            watch::WatchEvent::Offline { queued } => format!("tablet unreachable; {} file(s) queued", queued),
+            watch::WatchEvent::Online => "tablet reachable again".to_string(),
+        };
+        println!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), line);
+    }
+}
+
+/// Creates tablet folder `path` and any missing folders above it.
+pub async fn run_mkdir(config: &Config, path: &str, dry_run: bool) -> Result<()> {
+    let backend = config.profile()?.1.open();
+    if !backend.can_edit() {
+        return Err(backend.read_only());
+    }
+    let shown = format!("/{}", path.trim_matches('/'));
+    let made = crate::make_folders(backend.as_ref(), Vec::new(), path, !dry_run).await;
+    backend.settle().await?;
+    match made? {
+        0 => println!("{} already exists", shown),
+        n if dry_run => println!("Would create {} ({} new folder(s))", shown, n),
+        n => println!("Created {} ({} new folder(s))", shown, n),
+    }
+    Ok(())
+}
+
+/// `remarkable templates` and `remarkable splash`.
+pub async fn run_device(config: &Config, request: device::Request, dry_run: bool) -> Result<()> {
+    let (profile, settings) = config.profile()?;
+    let outcome = device::run(settings.open().as_ref(), &profile, request, dry_run).await?;
+    println!("{}", outcome.summary);
+    for line in &outcome.lines {
+        println!("  {}", line);
+    }
+    Ok(())
+}
+
+/// `remarkable sync`: prints each pair's plan, then runs it once confirmed.
+/// Returns whether every step went through.
+pub async fn run_sync(config: &Config, name: Option<&str>, dry_run: bool, yes: bool) -> Result<bool> {
+    let backend = config.profile()?.1.open();
+    let mut ok = true;
+    for pair in sync::select(&config.sync, name)? {
+        let plan = sync::plan(backend.as_ref(), pair, config.downloads.ascii_filenames).await?;
+        println!("{}", plan.summary());
+        for step in plan.visible() {
+            println!("  {}", step);
+        }
+        if dry_run {
+            continue;
+        }
+        // Plans that only record files already in step need no go-ahead
+        if !yes && plan.steps.iter().any(sync::Step::transfers) && !ask("Run this sync? [y/N] ")? {
+            println!("Skipped {}.", pair.name());
+            continue;
+        }
+        let report = sync::run(backend.as_ref(), plan).await?;
+        println!("Synced {}: {}", pair.name(), report);
+        for error in &report.errors {
+            eprintln!("  {}", error);
+        }
+        ok &= report.errors.is_empty();
+    }
+    Ok(ok)
+}
+
+/// Writes each `.rm` file in `files` as an SVG beside it. False if any
+/// could not be drawn.
+pub fn run_render(files: &[PathBuf]) -> bool {
+    let mut ok = true;
+    for file in files {
+        let target = file.with_extension("svg");
+        let result = std::fs::read(file)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| lines::Page::parse(&bytes))
+            .and_then(|page| Ok(std::fs::write(&target, render::svg(&page))?));
+        match result {
+            Ok(()) => println!("{} -> {}", file.display(), target.display()),
+            Err(e) => {
+                ok = false;
+                eprintln!("{}: {:#}", file.display(), e);
+            }
+        }
+    }
+    ok
+}
+
+/// `remarkable notes`: writes the notes of the document or folder at
+/// tablet path `path`. Returns whether every document could be read.
+pub async fn run_notes(config: &Config, path: &str, dir: Option<PathBuf>, format: notes::NotesFormat, dry_run: bool) -> Result<bool> {
+    let dir = match dir.or_else(|| crate::notes_directory(config).map(|d| PathBuf::from(crate::expand_path(&d)))) {
+        Some(dir) => dir,
+        None => return Err(anyhow!("No notes directory; pass one or set notes.directory")),
+    };
+    let backend = config.profile()?.1.open();
+
+    let path = path.trim_matches('/');
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let trail = crate::resolve_trail(backend.as_ref(), Vec::new(), parent).await?;
+    let children = backend.list(&trail.last().map(|(id, _)| id.clone())).await?;
+    let item = children
+        .iter()
+        .find(|c| c.visible_name == name)
+        .or_else(|| children.iter().find(|c| c.visible_name.eq_ignore_ascii_case(name)))
+        .cloned()
+        .ok_or_else(|| anyhow!("Nothing named '{}' in /{}", name, parent))?;
+    let folder_path: String = trail.iter().map(|(_, name)| format!("/{}", name)).collect();
+    let tablet_path = format!("{}/{}", folder_path, item.visible_name);
+    let folders = if item.is_folder() { vec![item.visible_name.clone()] } else { Vec::new() };
+    let documents = notes::documents(backend.as_ref(), item, tablet_path, folders).await?;
+
+    let report = notes::export(backend.as_ref(), documents, &dir, format, config.downloads.ascii_filenames, dry_run).await;
+    for file in &report.written {
+        println!("{}{}", if dry_run { "Would write " } else { "" }, file.display());
+    }
+    for error in &report.errors {
+        eprintln!("{}", error);
+    }
+    println!("{}: {}", if dry_run { "Would export" } else { "Exported" }, report);
+    Ok(report.errors.is_empty())
+}
+
+/// `remarkable verify`: re-checks downloads against their manifests.
+/// Returns whether every listed file is intact.
+pub fn run_verify(config: &Config, dir: Option<PathBuf>) -> Result<bool> {
+    let dir = match (dir, &config.downloads.directory) {
+        (Some(dir), _) => dir,
+        (None, Some(dir)) => PathBuf::from(crate::expand_path(dir)),
+        (None, None) => std::env::current_dir()?,
+    };
+    let mut manifests = Vec::new();
+    integrity::find_manifests(&dir, &mut manifests)?;
+    if manifests.is_empty() {
+        return Err(anyhow!("No {} found in {} or below", integrity::MANIFEST, dir.display()));
+    }
+
+    let (mut good, mut bad) = (0, 0);
+    for manifest in manifests {
+        for (path, verdict) in integrity::verify(&manifest)? {
+            let problem = match verdict {
+                integrity::Verdict::Ok => None,
+                integrity::Verdict::Missing => Some("MISSING".to_string()),
+                integrity::Verdict::Mismatch => Some("CHANGED (checksum differs)".to_string()),
+                integrity::Verdict::Damaged(why) => Some(format!("DAMAGED ({})", why)),
+            };
+            match problem {
+                None => good += 1,
+                Some(problem) => {
+                    bad += 1;
+                    println!("{}: {}", path.display(), problem);
+                }
+            }
+        }
+    }
+    println!("{} file(s) intact, {} with problems", good, bad);
+    Ok(bad == 0)
+}
+
+fn ask(question: &str) -> io::Result<bool> {
+    print!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,441 @@
+use crate::backend::Profile;
+use crate::conflict::{ConflictPolicy, DuplicatePolicy};
+use crate::hooks::HookConfig;
+use crate::keymap::{Action, Keymap};
+use crate::naming::Template;
+use crate::notes::NotesConfig;
+use crate::sync::SyncConfig;
+use crate::viewer::ViewerConfig;
+use crate::watch::WatchConfig;
+use anyhow::{anyhow, Context, Result};
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const DEFAULT_ENDPOINT: &str = "http://10.11.99.1";
+
+// --- Schema ---
+//
+// Every section and key is optional; anything left out falls back to the
+// compiled-in default. Unknown keys are rejected so typos don't go unnoticed.
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub device: DeviceConfig,
+    pub downloads: DownloadConfig,
+    pub uploads: UploadConfig,
+    pub notes: NotesConfig,
+    pub viewer: ViewerConfig,
+    pub ui: UiConfig,
+
+    /// Commands run after each downloaded or uploaded file.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<HookConfig>,
+
+    /// Local folders whose new files are uploaded automatically.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub watch: Vec<WatchConfig>,
+
+    /// Named ways of reaching a tablet; `device.profile` picks one.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Local folders kept in step with tablet folders by `sync`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sync: Vec<SyncConfig>,
+
+    /// Per-action key overrides. An action listed here loses its default
+    /// bindings; actions left out keep theirs.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<Action, Vec<String>>,
+
+    /// File the settings were read from, if any.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// Base URL of the tablet's USB web interface, used when no profile
+    /// is chosen.
+    pub endpoint: String,
+    /// Entry of `[profiles]` to use instead of `endpoint`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self { endpoint: DEFAULT_ENDPOINT.into(), profile: None }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadConfig {
+    /// Pre-filled into the download prompt. `~` is expanded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    /// What to do when a downloaded file already exists locally.
+    pub on_conflict: ConflictPolicy,
+    /// Replace every non-ASCII character in file names, for filesystems or
+    /// tools that cannot cope with Unicode.
+    pub ascii_filenames: bool,
+    /// Where each document lands relative to the download directory.
+    pub name_template: Template,
+    /// Put every file of a folder download directly in the target
+    /// directory, folding tablet folders into the file name.
+    pub flatten: bool,
+    /// Record a SHA-256 of every downloaded file in `SHA256SUMS` in the
+    /// download directory, for `remarkable verify`.
+    pub checksums: bool,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            on_conflict: ConflictPolicy::default(),
+            ascii_filenames: false,
+            name_template: Template::default(),
+            flatten: false,
+            checksums: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UploadConfig {
+    /// What to do when the destination already has a document of that name.
+    pub on_duplicate: DuplicatePolicy,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// How long the event loop waits for input before redrawing.
+    pub tick_rate_ms: u64,
+    /// Capture the mouse for clicking and scrolling. When off, the terminal
+    /// keeps its own text selection.
+    pub mouse: bool,
+    /// Start with the local file pane open beside the tablet list.
+    pub dual_pane: bool,
+    pub icons: IconConfig,
+    pub colors: ColorConfig,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate_ms: 100,
+            mouse: true,
+            dual_pane: false,
+            icons: IconConfig::default(),
+            colors: ColorConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconConfig {
+    pub folder: String,
+    pub document: String,
+    /// After the names of starred items.
+    pub star: String,
+}
+
+impl Default for IconConfig {
+    fn default() -> Self {
+        Self {
+            folder: "📁".into(),
+            document: "📄".into(),
+            star: "★".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    pub text: ThemeColor,
+    pub highlight_bg: ThemeColor,
+    pub marked_fg: ThemeColor,
+    /// Border of the focused pane in the dual-pane layout.
+    pub focus_border: ThemeColor,
+    pub input_fg: ThemeColor,
+    pub input_bg: ThemeColor,
+    pub modal_bg: ThemeColor,
+    pub star_fg: ThemeColor,
+    /// Tag chips after item names.
+    pub tag_fg: ThemeColor,
+    pub tag_bg: ThemeColor,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self {
+            text: ThemeColor(Color::White),
+            highlight_bg: ThemeColor(Color::DarkGray),
+            marked_fg: ThemeColor(Color::Yellow),
+            focus_border: ThemeColor(Color::Cyan),
+            input_fg: ThemeColor(Color::White),
+            input_bg: ThemeColor(Color::Blue),
+            modal_bg: ThemeColor(Color::Black),
+            star_fg: ThemeColor(Color::Yellow),
+            tag_fg: ThemeColor(Color::Black),
+            tag_bg: ThemeColor(Color::Cyan),
+        }
+    }
+}
+
+/// A colour written either by name (`"darkgray"`, `"lightblue"`), as an
+/// indexed colour (`"42"`) or as hex (`"#ff8800"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThemeColor(pub Color);
+
+impl<'de> Deserialize<'de> for ThemeColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Color::from_str(&raw)
+            .map(ThemeColor)
+            .map_err(|_| serde::de::Error::custom(format!("unknown colour `{}`", raw)))
+    }
+}
+
+impl Serialize for ThemeColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl fmt::Display for ThemeColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            other => write!(f, "{}", other.to_string().to_lowercase()),
+        }
+    }
+}
+
+// --- Loading ---
+
+/// `$XDG_CONFIG_HOME/remarkable/config.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("remarkable").join("config.toml"))
+}
+
+impl Config {
+    /// Loads `override_path` if given (it must exist), otherwise the XDG
+    /// config file if present, otherwise the defaults.
+    pub fn load(override_path: Option<&Path>) -> Result<Self> {
+        let path = match override_path {
+            Some(p) => p.to_path_buf(),
+            None => match default_path() {
+                Some(p) if p.exists() => p,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read config file {}", path.display()))?;
+        let mut config = Self::parse(&text)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.source = Some(path);
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let endpoint = &self.device.endpoint;
+        if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) {
+            return Err(anyhow!(
+                "`device.endpoint` must start with http:// or https:// (got `{}`)",
+                endpoint
+            ));
+        }
+        if !(10..=1000).contains(&self.ui.tick_rate_ms) {
+            return Err(anyhow!(
+                "`ui.tick_rate_ms` must be between 10 and 1000 (got {})",
+                self.ui.tick_rate_ms
+            ));
+        }
+        if let Some(dir) = &self.downloads.directory {
+            if dir.trim().is_empty() {
+                return Err(anyhow!("`downloads.directory` cannot be empty; remove the key instead"));
+            }
+        }
+        if let Some(dir) = &self.notes.directory {
+            if dir.trim().is_empty() {
+                return Err(anyhow!("`notes.directory` cannot be empty; remove the key instead"));
+            }
+        }
+        if let Some(i) = self.hooks.iter().position(|hook| hook.command.trim().is_empty()) {
+            return Err(anyhow!("`hooks[{}].command` cannot be empty", i));
+        }
+        if let Some(i) = self.watch.iter().position(|w| w.directory.trim().is_empty()) {
+            return Err(anyhow!("`watch[{}].directory` cannot be empty", i));
+        }
+        for (i, pair) in self.sync.iter().enumerate() {
+            if pair.directory.trim().is_empty() {
+                return Err(anyhow!("`sync[{}].directory` cannot be empty", i));
+            }
+            if pair.tablet_folder.is_none() && pair.guid.is_none() {
+                return Err(anyhow!("`sync[{}]` needs `tablet_folder` or `guid`", i));
+            }
+        }
+        for (name, profile) in &self.profiles {
+            profile.validate(name)?;
+        }
+        self.profile()?;
+        Keymap::new(&self.keys)?;
+        Ok(())
+    }
+
+    /// The chosen profile and its name; without one, the USB web
+    /// interface at `device.endpoint`.
+    pub fn profile(&self) -> Result<(String, Profile)> {
+        match &self.device.profile {
+            Some(name) => match self.profiles.get(name) {
+                Some(profile) => Ok((name.clone(), profile.clone())),
+                None => Err(anyhow!(
+                    "No profile named `{}`; known profiles: {}",
+                    name,
+                    self.profile_names().join(", ")
+                )),
+            },
+            None => Ok(("default".into(), Profile::Http { endpoint: self.device.endpoint.clone() })),
+        }
+    }
+
+    /// Switches to profile `name` (e.g. from `--profile`), if given.
+    pub fn with_profile(mut self, name: Option<String>) -> Result<Self> {
+        if name.is_some() {
+            self.device.profile = name;
+            self.profile()?;
+        }
+        Ok(self)
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+
+    /// Renders the effective settings as TOML, prefixed with where they came from.
+    pub fn describe(&self) -> Result<String> {
+        let origin = match &self.source {
+            Some(path) => format!("# Loaded from {}\n", path.display()),
+            None => match default_path() {
+                Some(path) => format!("# No config file at {}; using defaults\n", path.display()),
+                None => "# No config file; using defaults\n".to_string(),
+            },
+        };
+        let mut effective = self.clone();
+        effective.keys = Keymap::new(&self.keys)?.to_table();
+        Ok(format!("{}\n{}", origin, toml::to_string_pretty(&effective)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        format!("{:#}", Config::parse(text).unwrap_err())
+    }
+
+    #[test]
+    fn unknown_keys_are_refused() {
+        assert!(error("[ui]\ntick_rate = 50\n").contains("unknown field `tick_rate`"));
+        assert!(error("[colours]\ntext = \"red\"\n").contains("unknown field `colours`"));
+        assert!(error("[ui.colors]\nbackground = \"red\"\n").contains("unknown field `background`"));
+    }
+
+    #[test]
+    fn bad_values_name_their_key() {
+        assert_eq!(error("[ui]\ntick_rate_ms = 5\n"), "`ui.tick_rate_ms` must be between 10 and 1000 (got 5)");
+        assert_eq!(
+            error("[device]\nendpoint = \"10.11.99.1\"\n"),
+            "`device.endpoint` must start with http:// or https:// (got `10.11.99.1`)"
+        );
+        assert_eq!(error("[downloads]\ndirectory = \" \"\n"), "`downloads.directory` cannot be empty; remove the key instead");
+        assert_eq!(error("[[sync]]\ndirectory = \"~/Notes\"\n"), "`sync[0]` needs `tablet_folder` or `guid`");
+        assert!(error("[device]\nprofile = \"work\"\n").starts_with("No profile named `work`"));
+        let colour = error("[ui.colors]\nhighlight_bg = \"nope\"\n");
+        assert!(colour.contains("unknown colour `nope`") && colour.contains("highlight_bg"), "{}", colour);
+        let kind = error("[ui]\nmouse = \"yes\"\n");
+        assert!(kind.contains("mouse"), "{}", kind);
+    }
+
+    #[test]
+    fn missing_keys_take_the_defaults() {
+        let config = Config::parse("[ui]\nmouse = false\n").unwrap();
+        assert!(!config.ui.mouse);
+        assert_eq!(config.ui.tick_rate_ms, 100);
+        assert_eq!(config.ui.colors.highlight_bg, ThemeColor(Color::DarkGray));
+        assert_eq!(config.device.endpoint, DEFAULT_ENDPOINT);
+        assert!(config.downloads.checksums);
+        assert!(config.keys.is_empty() && config.watch.is_empty());
+        assert!(matches!(config.profile().unwrap().1, Profile::Http { endpoint } if endpoint == DEFAULT_ENDPOINT));
+    }
+
+    #[test]
+    fn colours_read_back_as_written() {
+        for raw in ["darkgray", "42", "#ff8800"] {
+            let config = Config::parse(&format!("[ui.colors]\ntext = \"{}\"\n", raw)).unwrap();
+            assert_eq!(config.ui.colors.text.to_string(), raw);
+        }
+    }
+
+    #[test]
+    fn loads_the_given_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[ui]\ntick_rate_ms = 250\n").unwrap();
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.ui.tick_rate_ms, 250);
+        assert_eq!(config.source.as_deref(), Some(path.as_path()));
+
+        // A given file has to exist, and errors say which file is wrong
+        let missing = dir.path().join("missing.toml");
+        let e = format!("{:#}", Config::load(Some(&missing)).unwrap_err());
+        assert!(e.starts_with(&format!("Could not read config file {}", missing.display())), "{}", e);
+        std::fs::write(&path, "[ui]\ntick_rate_ms = 5\n").unwrap();
+        let e = format!("{:#}", Config::load(Some(&path)).unwrap_err());
+        assert!(e.starts_with(&format!("Invalid config file {}: `ui.tick_rate_ms`", path.display())), "{}", e);
+    }
+
+    #[test]
+    fn describe_prints_settings_that_load_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[ui]\ndual_pane = true\n\n[keys]\nquit = [\"<C-q>\"]\n").unwrap();
+        let text = Config::load(Some(&path)).unwrap().describe().unwrap();
+        assert!(text.starts_with(&format!("# Loaded from {}\n", path.display())), "{}", text);
+
+        // Every setting is spelled out, keys included
+        let again = Config::parse(&text).unwrap();
+        assert!(again.ui.dual_pane);
+        assert_eq!(again.device.endpoint, DEFAULT_ENDPOINT);
+        assert_eq!(again.keys[&Action::Quit], ["<C-q>"]);
+        assert_eq!(again.keys[&Action::Top], ["gg", "<Home>"]);
+        assert_eq!(again.describe().unwrap().lines().skip(1).collect::<Vec<_>>(), text.lines().skip(1).collect::<Vec<_>>());
+    }
+}
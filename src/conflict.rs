@@ -0,0 +1,212 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::sync::oneshot;
+
+// --- Download conflicts ---
+
+/// What to do when a download would land on a file that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Ask for every conflicting file.
+    #[default]
+    Prompt,
+    Overwrite,
+    Skip,
+    /// Keep both, giving the new file a numeric suffix.
+    Rename,
+    /// Overwrite only if the tablet copy was modified after the local one.
+    Newer,
+}
+
+/// What to do when an upload would add a second document with a name that
+/// already exists in the destination folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Ask for every duplicate. Watch folders cannot ask, so they skip.
+    #[default]
+    Prompt,
+    Skip,
+    /// Upload regardless, leaving two documents with the same name.
+    Anyway,
+    /// Upload under the name with a numeric suffix.
+    Rename,
+}
+
+/// An answer to a single conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Overwrite,
+    Skip,
+    Rename,
+    Newer,
+    /// Upload next to the existing document under the same name.
+    Anyway,
+    /// Stop the whole job.
+    Abort,
+}
+
+impl From<ConflictPolicy> for Option<Choice> {
+    fn from(policy: ConflictPolicy) -> Self {
+        match policy {
+            ConflictPolicy::Prompt => None,
+            ConflictPolicy::Overwrite => Some(Choice::Overwrite),
+            ConflictPolicy::Skip => Some(Choice::Skip),
+            ConflictPolicy::Rename => Some(Choice::Rename),
+            ConflictPolicy::Newer => Some(Choice::Newer),
+        }
+    }
+}
+
+impl From<DuplicatePolicy> for Option<Choice> {
+    fn from(policy: DuplicatePolicy) -> Self {
+        match policy {
+            DuplicatePolicy::Prompt => None,
+            DuplicatePolicy::Skip => Some(Choice::Skip),
+            DuplicatePolicy::Anyway => Some(Choice::Anyway),
+            DuplicatePolicy::Rename => Some(Choice::Rename),
+        }
+    }
+}
+
+/// Which transfer a conflict question is about.
+pub enum Subject {
+    /// A download onto an existing local file. Holds whether the tablet copy
+    /// is newer, if both times are known.
+    Download { tablet_newer: Option<bool> },
+    /// An upload next to a document of the same name, and what else the two
+    /// have in common.
+    Upload { duplicate: String },
+}
+
+/// A question sent from a transfer job to the UI. The job waits until
+/// `answer` is called (or the prompt is dropped, which aborts the job).
+pub struct ConflictPrompt {
+    /// The local file in question.
+    pub path: PathBuf,
+    pub subject: Subject,
+    reply: oneshot::Sender<(Choice, bool)>,
+}
+
+impl ConflictPrompt {
+    pub fn new(path: PathBuf, subject: Subject) -> (Self, oneshot::Receiver<(Choice, bool)>) {
+        let (reply, rx) = oneshot::channel();
+        (Self { path, subject, reply }, rx)
+    }
+
+    /// Whether `choice` is one of this question's answers.
+    pub fn offers(&self, choice: Choice) -> bool {
+        match self.subject {
+            Subject::Download { .. } => choice != Choice::Anyway,
+            Subject::Upload { .. } => matches!(choice, Choice::Skip | Choice::Anyway | Choice::Rename | Choice::Abort),
+        }
+    }
+
+    /// `all` makes the choice stick for the rest of the job.
+    pub fn answer(self, choice: Choice, all: bool) {
+        let _ = self.reply.send((choice, all));
+    }
+}
+
+/// Compares the tablet's modification time with the local file's.
+pub fn tablet_is_newer(tablet: Option<DateTime<Utc>>, local: &Path) -> Option<bool> {
+    let local: DateTime<Utc> = std::fs::metadata(local).ok()?.modified().ok()?.into();
+    Some(tablet? > local)
+}
+
+/// The first free `name (n).ext` next to `path`.
+pub fn numbered_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .expect("unbounded range always yields a free name")
+}
+
+/// What `choice` would do to the existing `target`, for dry runs. `None`
+/// means the question would be asked.
+pub fn describe(choice: Option<Choice>, target: &Path, tablet_newer: Option<bool>) -> String {
+    match choice {
+        None => "exists; you would be asked".into(),
+        Some(Choice::Overwrite) | Some(Choice::Anyway) => "exists; would be overwritten".into(),
+        Some(Choice::Skip) | Some(Choice::Abort) => "exists; would be skipped".into(),
+        Some(Choice::Rename) => format!("exists; would be saved as {}", numbered_path(target).display()),
+        Some(Choice::Newer) => match tablet_newer {
+            Some(true) => "exists; would be overwritten (tablet copy is newer)".into(),
+            _ => "exists; would be skipped (local copy is not older)".into(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// `name` in `dir`, last modified at `time`.
+    fn file_at(dir: &Path, name: &str, time: DateTime<Utc>) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, b"%PDF-1.4").unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(time.into()).unwrap();
+        path
+    }
+
+    fn day(month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, month, day, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn compares_modification_times() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = file_at(dir.path(), "a.pdf", day(3, 1));
+        assert_eq!(tablet_is_newer(Some(day(3, 2)), &local), Some(true));
+        assert_eq!(tablet_is_newer(Some(day(2, 28)), &local), Some(false));
+        assert_eq!(tablet_is_newer(Some(day(3, 1)), &local), Some(false));
+        assert_eq!(tablet_is_newer(None, &local), None);
+        assert_eq!(tablet_is_newer(Some(day(3, 2)), &dir.path().join("missing.pdf")), None);
+    }
+
+    #[test]
+    fn renames_to_the_first_free_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = file_at(dir.path(), "Paper.pdf", day(3, 1));
+        assert_eq!(numbered_path(&target), dir.path().join("Paper (1).pdf"));
+        file_at(dir.path(), "Paper (1).pdf", day(3, 1));
+        file_at(dir.path(), "Paper (3).pdf", day(3, 1));
+        assert_eq!(numbered_path(&target), dir.path().join("Paper (2).pdf"));
+        assert_eq!(numbered_path(&dir.path().join("README")), dir.path().join("README (1)"));
+    }
+
+    #[test]
+    fn describes_each_choice() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = file_at(dir.path(), "a.pdf", day(3, 1));
+        let newer = tablet_is_newer(Some(day(3, 2)), &target);
+        let older = tablet_is_newer(Some(day(2, 1)), &target);
+        let cases = [
+            (None, None, "exists; you would be asked".to_string()),
+            (Some(Choice::Overwrite), None, "exists; would be overwritten".to_string()),
+            (Some(Choice::Skip), None, "exists; would be skipped".to_string()),
+            (Some(Choice::Rename), None, format!("exists; would be saved as {}", dir.path().join("a (1).pdf").display())),
+            (Some(Choice::Newer), newer, "exists; would be overwritten (tablet copy is newer)".to_string()),
+            (Some(Choice::Newer), older, "exists; would be skipped (local copy is not older)".to_string()),
+            (Some(Choice::Newer), None, "exists; would be skipped (local copy is not older)".to_string()),
+        ];
+        for (choice, tablet_newer, expected) in cases {
+            assert_eq!(describe(choice, &target, tablet_newer), expected, "{:?}", choice);
+        }
+    }
+
+    #[test]
+    fn uploads_offer_their_own_answers() {
+        let (download, _) = ConflictPrompt::new(PathBuf::from("a.pdf"), Subject::Download { tablet_newer: None });
+        let (upload, rx) = ConflictPrompt::new(PathBuf::from("a.pdf"), Subject::Upload { duplicate: String::new() });
+        assert!(download.offers(Choice::Newer) && !download.offers(Choice::Anyway));
+        assert!(upload.offers(Choice::Anyway) && !upload.offers(Choice::Overwrite) && !upload.offers(Choice::Newer));
+        upload.answer(Choice::Rename, true);
+        assert_eq!(rx.blocking_recv().unwrap(), (Choice::Rename, true));
+    }
+}
@@ -0,0 +1,754 @@
+use crate::{
+    backend::{Backend, Files, System},
+    ledger,
+};
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+// --- Templates and splash screens ---
+//
+// The page templates offered for new notebooks and the pictures shown while
+// the tablet sleeps, starts or shuts down live among its system files rather
+// than its documents, so only SSH reaches them. Before the first change the
+// installed templates are copied to the state directory as the `original`
+// backup: templates missing from it count as custom, and the stock ones are
+// only removed when forced. A replaced splash screen keeps its original
+// there until it is restored.
+
+pub const TEMPLATES_USAGE: &str =
+    "templates [list | add FILE... --category NAME [--name NAME] [--landscape] | remove NAME [--force] | backup | restore [BACKUP]]";
+
+pub const SPLASH_USAGE: &str = "splash [list | set SCREEN FILE.png | restore [SCREEN]]";
+
+/// Splash screens that can be replaced, with when the tablet shows them.
+const SCREENS: &[(&str, &str)] = &[
+    ("suspended", "while asleep"),
+    ("poweroff", "when switched off"),
+    ("rebooting", "while restarting"),
+    ("starting", "while starting up"),
+    ("batteryempty", "when the battery has run out"),
+    ("overheating", "when too hot to run"),
+];
+
+/// Size of the tablet's screen in portrait, which splash screens must match.
+const SCREEN_SIZE: (u32, u32) = (1404, 1872);
+
+/// Icon of the blank template, for templates added here.
+const DEFAULT_ICON: &str = "\u{e9fe}";
+
+const TEMPLATES_JSON: &str = "templates.json";
+
+/// The backup taken before the first change.
+const ORIGINAL: &str = "original";
+
+/// `templates.json`: every template the tablet offers.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct TemplateList {
+    templates: Vec<Template>,
+    #[serde(flatten)]
+    other: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Template {
+    name: String,
+    /// Name of the `.png` and `.svg` files, without the extension.
+    filename: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    categories: Vec<String>,
+    /// `iconCode`, `landscape` and whatever newer firmware adds.
+    #[serde(flatten)]
+    other: Map<String, Value>,
+}
+
+impl Template {
+    fn landscape(&self) -> bool {
+        match self.other.get("landscape") {
+            Some(Value::Bool(landscape)) => *landscape,
+            Some(Value::String(landscape)) => landscape == "true",
+            _ => false,
+        }
+    }
+}
+
+/// What a request did, or would do on a dry run.
+pub struct Outcome {
+    pub summary: String,
+    /// One line per template, screen or file.
+    pub lines: Vec<String>,
+}
+
+/// A `templates` or `splash` command, from the command line or the palette.
+pub enum Request {
+    Templates(TemplateRequest),
+    Splash(SplashRequest),
+}
+
+impl Request {
+    /// `args` after the command `name`.
+    pub fn parse(name: &str, args: &[String]) -> Result<Self> {
+        match name {
+            "templates" => Ok(Self::Templates(TemplateRequest::parse(args)?)),
+            "splash" => Ok(Self::Splash(SplashRequest::parse(args)?)),
+            _ => Err(anyhow!("Unknown command `{}`", name)),
+        }
+    }
+
+    /// Whether this only shows what is installed.
+    pub fn is_list(&self) -> bool {
+        matches!(self, Self::Templates(TemplateRequest::List) | Self::Splash(SplashRequest::List))
+    }
+}
+
+/// Runs `request` through profile `profile`, then restarts the tablet's UI
+/// if anything changed so it shows.
+pub async fn run(backend: &dyn Backend, profile: &str, request: Request, dry_run: bool) -> Result<Outcome> {
+    let system = backend
+        .system()
+        .ok_or_else(|| anyhow!("{} cannot reach templates or splash screens; use an ssh profile", backend.describe()))?;
+    let outcome = match request {
+        Request::Templates(request) => templates(system, profile, request, dry_run).await,
+        Request::Splash(request) => splash(system, profile, request, dry_run).await,
+    };
+    backend.settle().await?;
+    outcome
+}
+
+pub enum TemplateRequest {
+    List,
+    /// Templates from `.png` and `.svg` files, one per file name.
+    Add { files: Vec<PathBuf>, name: Option<String>, categories: Vec<String>, landscape: bool },
+    /// A custom template, or a stock one when forced.
+    Remove { name: String, force: bool },
+    Backup,
+    /// From this backup, or the latest one.
+    Restore(Option<String>),
+}
+
+impl TemplateRequest {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let usage = || anyhow!("Usage: {}", TEMPLATES_USAGE);
+        let Some((verb, rest)) = args.split_first() else { return Ok(Self::List) };
+        let mut rest = rest.iter();
+        let request = match verb.as_str() {
+            "list" => Self::List,
+            "add" => {
+                let (mut files, mut name, mut categories, mut landscape) = (Vec::new(), None, Vec::new(), false);
+                while let Some(arg) = rest.next() {
+                    match arg.as_str() {
+                        "--category" => {
+                            let value = rest.next().ok_or_else(|| anyhow!("`--category` needs a category, e.g. Lines"))?;
+                            categories.extend(value.split(',').map(str::trim).filter(|c| !c.is_empty()).map(str::to_string));
+                        }
+                        "--name" => name = Some(rest.next().ok_or_else(|| anyhow!("`--name` needs a template name"))?.clone()),
+                        "--landscape" => landscape = true,
+                        _ if arg.starts_with('-') => return Err(usage()),
+                        _ => files.push(PathBuf::from(crate::expand_path(arg))),
+                    }
+                }
+                if files.is_empty() {
+                    return Err(anyhow!("`templates add` needs at least one .png or .svg file"));
+                }
+                if categories.is_empty() {
+                    return Err(anyhow!("`templates add` needs a `--category`, e.g. Lines or Grids"));
+                }
+                return Ok(Self::Add { files, name, categories, landscape });
+            }
+            "remove" => {
+                let (mut name, mut force) = (None, false);
+                for arg in rest.by_ref() {
+                    match arg.as_str() {
+                        "--force" => force = true,
+                        _ if name.is_none() && !arg.starts_with('-') => name = Some(arg.clone()),
+                        _ => return Err(usage()),
+                    }
+                }
+                let name = name.ok_or_else(|| anyhow!("`templates remove` needs a template name"))?;
+                return Ok(Self::Remove { name, force });
+            }
+            "backup" => Self::Backup,
+            "restore" => Self::Restore(rest.next().cloned()),
+            _ => return Err(usage()),
+        };
+        match rest.next() {
+            Some(_) => Err(usage()),
+            None => Ok(request),
+        }
+    }
+}
+
+pub enum SplashRequest {
+    List,
+    Set { screen: String, file: PathBuf },
+    /// This screen, or every replaced one.
+    Restore(Option<String>),
+}
+
+impl SplashRequest {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let request = match args {
+            [] => Self::List,
+            [verb] if verb == "list" => Self::List,
+            [verb, screen, file] if verb == "set" => {
+                Self::Set { screen: screen_name(screen)?, file: PathBuf::from(crate::expand_path(file)) }
+            }
+            [verb] if verb == "restore" => Self::Restore(None),
+            [verb, screen] if verb == "restore" => Self::Restore(Some(screen_name(screen)?)),
+            _ => return Err(anyhow!("Usage: {}", SPLASH_USAGE)),
+        };
+        Ok(request)
+    }
+}
+
+/// `screen` as one of `SCREENS`, with or without `.png`.
+fn screen_name(screen: &str) -> Result<String> {
+    let name = screen.strip_suffix(".png").unwrap_or(screen).to_lowercase();
+    match SCREENS.iter().find(|(s, _)| *s == name) {
+        Some(_) => Ok(name),
+        None => Err(anyhow!(
+            "Unknown splash screen `{}`; use one of {}",
+            screen,
+            SCREENS.iter().map(|(s, _)| *s).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// Splits a palette line into words; double quotes keep spaces inside one.
+pub fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            _ if c.is_whitespace() && !quoted => words.extend(word.take()),
+            _ => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+// --- Templates ---
+
+async fn templates(system: &dyn System, profile: &str, request: TemplateRequest, dry_run: bool) -> Result<Outcome> {
+    let backups = state_dir("templates", profile)?;
+    match request {
+        TemplateRequest::List => list(system, &backups).await,
+        TemplateRequest::Add { files, name, categories, landscape } => {
+            add(system, &backups, files, name, categories, landscape, dry_run).await
+        }
+        TemplateRequest::Remove { name, force } => remove(system, &backups, &name, force, dry_run).await,
+        TemplateRequest::Backup => {
+            original(system, &backups, dry_run).await?;
+            let dir = backups.join(Local::now().format("%Y-%m-%d_%H%M%S").to_string());
+            let (list, files) = snapshot(system).await?;
+            if !dry_run {
+                save(&dir, &list, &files)?;
+            }
+            let verb = if dry_run { "Would back up" } else { "Backed up" };
+            Ok(Outcome {
+                summary: format!("{} {} templates ({} files) to {}", verb, list.templates.len(), files.len(), dir.display()),
+                lines: Vec::new(),
+            })
+        }
+        TemplateRequest::Restore(name) => restore(system, &backups, name, dry_run).await,
+    }
+}
+
+async fn list(system: &dyn System, backups: &Path) -> Result<Outcome> {
+    let installed = installed(system).await?;
+    let stock = load(&backups.join(ORIGINAL)).ok().map(|(list, _)| filenames(&list));
+    let custom = |t: &Template| stock.as_ref().is_some_and(|stock| !stock.contains(&t.filename));
+    let width = installed.templates.iter().map(|t| t.name.chars().count()).max().unwrap_or(0);
+    let mut lines: Vec<String> = installed
+        .templates
+        .iter()
+        .map(|t| {
+            let mut notes = vec![t.categories.join(", ")];
+            notes.extend(t.landscape().then(|| "landscape".to_string()));
+            notes.extend(custom(t).then(|| "custom".to_string()));
+            format!("{:width$}  {}", t.name, notes.join(" · "), width = width)
+        })
+        .collect();
+    let saved = backup_names(backups);
+    if !saved.is_empty() {
+        lines.push(format!("Backups in {}: {}", backups.display(), saved.join(", ")));
+    }
+    let count = installed.templates.iter().filter(|t| custom(t)).count();
+    Ok(Outcome {
+        summary: format!("{} templates, {} custom, in {}", installed.templates.len(), count, system.templates_dir()),
+        lines,
+    })
+}
+
+async fn add(
+    system: &dyn System,
+    backups: &Path,
+    files: Vec<PathBuf>,
+    name: Option<String>,
+    categories: Vec<String>,
+    landscape: bool,
+    dry_run: bool,
+) -> Result<Outcome> {
+    let mut installed = installed(system).await?;
+    // The tablet only shows the categories it already has
+    let known: BTreeSet<&String> = installed.templates.iter().flat_map(|t| &t.categories).collect();
+    let categories = categories
+        .iter()
+        .map(|wanted| {
+            known.iter().find(|c| c.eq_ignore_ascii_case(wanted)).map(|c| c.to_string()).ok_or_else(|| {
+                anyhow!("Unknown category `{}`; the tablet has {}", wanted, known.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", "))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut groups: BTreeMap<String, Vec<(String, Vec<u8>)>> = BTreeMap::new();
+    for file in &files {
+        let (Some(stem), Some(ext)) = (file.file_stem(), file.extension()) else {
+            return Err(anyhow!("{} is not a .png or .svg file", file.display()));
+        };
+        let ext = ext.to_string_lossy().to_lowercase();
+        if ext != "png" && ext != "svg" {
+            return Err(anyhow!("{} is not a .png or .svg file", file.display()));
+        }
+        let bytes = std::fs::read(file).with_context(|| format!("Could not read {}", file.display()))?;
+        if ext == "png" {
+            png_size(&bytes).ok_or_else(|| anyhow!("{} is not a PNG image", file.display()))?;
+        }
+        groups.entry(stem.to_string_lossy().to_string()).or_default().push((ext, bytes));
+    }
+    if name.is_some() && groups.len() > 1 {
+        return Err(anyhow!("`--name` needs the files of a single template, not {}", groups.len()));
+    }
+
+    original(system, backups, dry_run).await?;
+    let mut lines = Vec::new();
+    let mut writes = Vec::new();
+    for (filename, files) in groups {
+        let name = name.clone().unwrap_or_else(|| filename.clone());
+        if let Some(other) = installed.templates.iter().find(|t| t.name == name && t.filename != filename) {
+            return Err(anyhow!("A template named `{}` already exists (files {})", name, other.filename));
+        }
+        let wide = landscape || files.iter().any(|(_, bytes)| png_size(bytes).is_some_and(|(w, h)| w > h));
+        let mut other = Map::new();
+        other.insert("iconCode".into(), DEFAULT_ICON.into());
+        if wide {
+            other.insert("landscape".into(), true.into());
+        }
+        let template = Template { name: name.clone(), filename: filename.clone(), categories: categories.clone(), other };
+        match installed.templates.iter_mut().find(|t| t.filename == filename) {
+            Some(existing) => {
+                lines.push(format!("{} (replacing the template of the same files)", name));
+                *existing = template;
+            }
+            None => {
+                lines.push(name);
+                installed.templates.push(template);
+            }
+        }
+        writes.extend(files.into_iter().map(|(ext, bytes)| (format!("{}.{}", filename, ext), bytes)));
+    }
+    if !dry_run {
+        // Files first, so the list never names a template that is not there
+        for (file, bytes) in &writes {
+            system.write(&template_path(system, file), bytes).await?;
+        }
+        write_list(system, &installed).await?;
+    }
+    let verb = if dry_run { "Would add" } else { "Added" };
+    Ok(Outcome { summary: format!("{} {} template(s) to {}", verb, lines.len(), categories.join(", ")), lines })
+}
+
+async fn remove(system: &dyn System, backups: &Path, name: &str, force: bool, dry_run: bool) -> Result<Outcome> {
+    let mut installed = installed(system).await?;
+    let found = installed
+        .templates
+        .iter()
+        .position(|t| t.name == name)
+        .or_else(|| installed.templates.iter().position(|t| t.name.eq_ignore_ascii_case(name) || t.filename.eq_ignore_ascii_case(name)))
+        .ok_or_else(|| anyhow!("No template named `{}`", name))?;
+    let stock = filenames(&original(system, backups, dry_run).await?);
+    let template = installed.templates.remove(found);
+    if stock.contains(&template.filename) && !force {
+        return Err(anyhow!("`{}` came with the tablet; --force removes it anyway, and a restore of `original` brings it back", template.name));
+    }
+    // Files still used by another entry stay
+    let files: Vec<String> = match filenames(&installed).contains(&template.filename) {
+        true => Vec::new(),
+        false => ["png", "svg"].iter().map(|ext| template_path(system, &format!("{}.{}", template.filename, ext))).collect(),
+    };
+    if !dry_run {
+        write_list(system, &installed).await?;
+        system.remove(&files).await?;
+    }
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    let what = if files.is_empty() { "" } else { " and its files" };
+    Ok(Outcome { summary: format!("{} template `{}`{}", verb, template.name, what), lines: Vec::new() })
+}
+
+async fn restore(system: &dyn System, backups: &Path, name: Option<String>, dry_run: bool) -> Result<Outcome> {
+    let dir = match name {
+        Some(name) if Path::new(&crate::expand_path(&name)).join(TEMPLATES_JSON).is_file() => PathBuf::from(crate::expand_path(&name)),
+        Some(name) => backups.join(name),
+        None => {
+            let latest = backup_names(backups).into_iter().rfind(|n| n != ORIGINAL);
+            backups.join(latest.ok_or_else(|| anyhow!("No template backups yet; `templates backup` makes one"))?)
+        }
+    };
+    let (list, files) = load(&dir)?;
+    original(system, backups, dry_run).await?;
+    let installed = installed(system).await?;
+    let kept = filenames(&list);
+    let orphans: Vec<String> = filenames(&installed)
+        .difference(&kept)
+        .flat_map(|f| ["png", "svg"].map(|ext| template_path(system, &format!("{}.{}", f, ext))))
+        .collect();
+    if !dry_run {
+        for (file, bytes) in &files {
+            system.write(&template_path(system, file), bytes).await?;
+        }
+        write_list(system, &list).await?;
+        system.remove(&orphans).await?;
+    }
+    let verb = if dry_run { "Would restore" } else { "Restored" };
+    let mut lines: Vec<String> = list.templates.iter().map(|t| t.name.clone()).collect();
+    lines.extend(filenames(&installed).difference(&kept).map(|f| format!("{} (removed)", f)));
+    Ok(Outcome { summary: format!("{} {} templates from {}", verb, list.templates.len(), dir.display()), lines })
+}
+
+/// `templates.json` as it is on the tablet.
+async fn installed(system: &dyn System) -> Result<TemplateList> {
+    let path = template_path(system, TEMPLATES_JSON);
+    let (_, bytes) = system.read(std::slice::from_ref(&path)).await?.pop().ok_or_else(|| anyhow!("{} is missing", path))?;
+    serde_json::from_slice(&bytes).with_context(|| format!("Invalid {}", path))
+}
+
+async fn write_list(system: &dyn System, list: &TemplateList) -> Result<()> {
+    system.write(&template_path(system, TEMPLATES_JSON), &serde_json::to_vec_pretty(list)?).await
+}
+
+/// The installed templates along with every file they use, by file name.
+async fn snapshot(system: &dyn System) -> Result<(TemplateList, Files)> {
+    let list = installed(system).await?;
+    let paths: Vec<String> = filenames(&list)
+        .iter()
+        .flat_map(|f| ["png", "svg"].map(|ext| template_path(system, &format!("{}.{}", f, ext))))
+        .collect();
+    let prefix = format!("{}/", system.templates_dir());
+    let files = system
+        .read(&paths)
+        .await?
+        .into_iter()
+        .map(|(path, bytes)| (path.strip_prefix(&prefix).unwrap_or(&path).to_string(), bytes))
+        .collect();
+    Ok((list, files))
+}
+
+/// The templates of the `original` backup, taking it first if there is
+/// none yet. A dry run only looks at what is installed.
+async fn original(system: &dyn System, backups: &Path, dry_run: bool) -> Result<TemplateList> {
+    let dir = backups.join(ORIGINAL);
+    if dir.join(TEMPLATES_JSON).is_file() {
+        return Ok(load(&dir)?.0);
+    }
+    if dry_run {
+        return installed(system).await;
+    }
+    let (list, files) = snapshot(system).await?;
+    save(&dir, &list, &files)?;
+    Ok(list)
+}
+
+fn save(dir: &Path, list: &TemplateList, files: &Files) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    for (file, bytes) in files {
+        std::fs::write(dir.join(file), bytes).with_context(|| format!("Could not write {}", dir.join(file).display()))?;
+    }
+    // Written last, so a backup cut short is never taken for a whole one
+    std::fs::write(dir.join(TEMPLATES_JSON), serde_json::to_vec_pretty(list)?)
+        .with_context(|| format!("Could not write {}", dir.join(TEMPLATES_JSON).display()))
+}
+
+fn load(dir: &Path) -> Result<(TemplateList, Files)> {
+    let json = dir.join(TEMPLATES_JSON);
+    let text = std::fs::read(&json).with_context(|| format!("No template backup at {}", dir.display()))?;
+    let list: TemplateList = serde_json::from_slice(&text).with_context(|| format!("Invalid {}", json.display()))?;
+    let mut files = Vec::new();
+    for file in filenames(&list).iter().flat_map(|f| ["png", "svg"].map(|ext| format!("{}.{}", f, ext))) {
+        match std::fs::read(dir.join(&file)) {
+            Ok(bytes) => files.push((file, bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("Could not read {}", dir.join(&file).display())),
+        }
+    }
+    Ok((list, files))
+}
+
+/// Backups of one profile, oldest first with `original` ahead of them.
+fn backup_names(backups: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(backups)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().join(TEMPLATES_JSON).is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort_by_key(|name| (name != ORIGINAL, name.clone()));
+    names
+}
+
+fn filenames(list: &TemplateList) -> BTreeSet<String> {
+    list.templates.iter().map(|t| t.filename.clone()).collect()
+}
+
+fn template_path(system: &dyn System, file: &str) -> String {
+    format!("{}/{}", system.templates_dir(), file)
+}
+
+// --- Splash screens ---
+
+async fn splash(system: &dyn System, profile: &str, request: SplashRequest, dry_run: bool) -> Result<Outcome> {
+    let saved = state_dir("splash", profile)?;
+    let path = |screen: &str| format!("{}/{}.png", system.splash_dir(), screen);
+    match request {
+        SplashRequest::List => {
+            let lines = SCREENS
+                .iter()
+                .map(|(screen, when)| {
+                    let replaced = if saved.join(format!("{}.png", screen)).is_file() { " · replaced" } else { "" };
+                    format!("{:12}  {}{}", screen, when, replaced)
+                })
+                .collect();
+            Ok(Outcome { summary: format!("Splash screens in {}", system.splash_dir()), lines })
+        }
+        SplashRequest::Set { screen, file } => {
+            let bytes = std::fs::read(&file).with_context(|| format!("Could not read {}", file.display()))?;
+            match png_size(&bytes) {
+                Some(size) if size == SCREEN_SIZE => {}
+                Some((w, h)) => {
+                    return Err(anyhow!("{} is {}x{}; splash screens are {}x{} pixels", file.display(), w, h, SCREEN_SIZE.0, SCREEN_SIZE.1))
+                }
+                None => return Err(anyhow!("{} is not a PNG image", file.display())),
+            }
+            let backup = saved.join(format!("{}.png", screen));
+            // Only the first replacement keeps what was there; later ones
+            // would save a custom picture as the original
+            if !backup.is_file() && !dry_run {
+                let (_, original) =
+                    system.read(&[path(&screen)]).await?.pop().ok_or_else(|| anyhow!("{} is missing on the tablet", path(&screen)))?;
+                std::fs::create_dir_all(&saved).with_context(|| format!("Could not create {}", saved.display()))?;
+                std::fs::write(&backup, original).with_context(|| format!("Could not write {}", backup.display()))?;
+            }
+            if !dry_run {
+                system.write(&path(&screen), &bytes).await?;
+            }
+            let verb = if dry_run { "Would replace" } else { "Replaced" };
+            Ok(Outcome { summary: format!("{} the {} screen with {}", verb, screen, file.display()), lines: Vec::new() })
+        }
+        SplashRequest::Restore(screen) => {
+            let screens: Vec<&str> = SCREENS
+                .iter()
+                .map(|(s, _)| *s)
+                .filter(|s| screen.as_deref().is_none_or(|wanted| wanted == *s))
+                .filter(|s| saved.join(format!("{}.png", s)).is_file())
+                .collect();
+            if screens.is_empty() {
+                return Err(match screen {
+                    Some(screen) => anyhow!("The {} screen was not replaced from here", screen),
+                    None => anyhow!("No replaced splash screens to restore"),
+                });
+            }
+            for screen in &screens {
+                let backup = saved.join(format!("{}.png", screen));
+                if !dry_run {
+                    let bytes = std::fs::read(&backup).with_context(|| format!("Could not read {}", backup.display()))?;
+                    system.write(&path(screen), &bytes).await?;
+                    std::fs::remove_file(&backup).with_context(|| format!("Could not remove {}", backup.display()))?;
+                }
+            }
+            let verb = if dry_run { "Would restore" } else { "Restored" };
+            Ok(Outcome {
+                summary: format!("{} {} splash screen(s)", verb, screens.len()),
+                lines: screens.iter().map(|s| s.to_string()).collect(),
+            })
+        }
+    }
+}
+
+/// `$XDG_STATE_HOME/remarkable/<kind>/<profile>`.
+fn state_dir(kind: &str, profile: &str) -> Result<PathBuf> {
+    let dir = ledger::state_dir().context("Cannot find a state directory; set HOME or XDG_STATE_HOME")?;
+    Ok(dir.join(kind).join(profile))
+}
+
+/// Width and height from the header of a PNG image.
+fn png_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.len() < 24 || !bytes.starts_with(b"\x89PNG\r\n\x1a\n") || &bytes[12..16] != b"IHDR" {
+        return None;
+    }
+    let number = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+    Some((number(16), number(20)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::BoxFuture;
+    use std::sync::Mutex;
+
+    /// A tablet's system files, in memory.
+    #[derive(Default)]
+    struct Fake(Mutex<BTreeMap<String, Vec<u8>>>);
+
+    impl System for Fake {
+        fn templates_dir(&self) -> &str {
+            "/templates"
+        }
+
+        fn splash_dir(&self) -> &str {
+            "/splash"
+        }
+
+        fn read<'a>(&'a self, paths: &'a [String]) -> BoxFuture<'a, Result<Files>> {
+            let files = self.0.lock().unwrap();
+            let found = paths.iter().filter_map(|p| files.get(p).map(|bytes| (p.clone(), bytes.clone()))).collect();
+            Box::pin(async move { Ok(found) })
+        }
+
+        fn write<'a>(&'a self, path: &'a str, bytes: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+            self.0.lock().unwrap().insert(path.to_string(), bytes.to_vec());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn remove<'a>(&'a self, paths: &'a [String]) -> BoxFuture<'a, Result<()>> {
+            let mut files = self.0.lock().unwrap();
+            for path in paths {
+                files.remove(path);
+            }
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    impl Fake {
+        /// A tablet with the templates `(name, filename)`, all of them lines.
+        fn with(templates: &[(&str, &str)]) -> Self {
+            let fake = Self::default();
+            let list: Vec<Value> = templates
+                .iter()
+                .map(|(name, filename)| serde_json::json!({ "name": name, "filename": filename, "iconCode": "\u{e9fe}", "categories": ["Lines"] }))
+                .collect();
+            fake.put(TEMPLATES_JSON, &serde_json::to_vec(&serde_json::json!({ "templates": list })).unwrap());
+            for (_, filename) in templates {
+                fake.put(&format!("{}.png", filename), filename.as_bytes());
+            }
+            fake
+        }
+
+        fn put(&self, file: &str, bytes: &[u8]) {
+            self.0.lock().unwrap().insert(format!("/templates/{}", file), bytes.to_vec());
+        }
+
+        fn get(&self, file: &str) -> Option<Vec<u8>> {
+            self.0.lock().unwrap().get(&format!("/templates/{}", file)).cloned()
+        }
+
+        fn names(&self) -> Vec<String> {
+            let list: TemplateList = serde_json::from_slice(&self.get(TEMPLATES_JSON).unwrap()).unwrap();
+            list.templates.into_iter().map(|t| t.name).collect()
+        }
+    }
+
+    /// The header of a PNG image, which is all that is checked.
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes
+    }
+
+    #[tokio::test]
+    async fn stock_templates_are_only_removed_when_forced() {
+        let backups = tempfile::tempdir().unwrap();
+        let system = Fake::with(&[("Blank", "Blank"), ("Lined", "P Lines medium")]);
+        let e = remove(&system, backups.path(), "lined", false, false).await.err().unwrap();
+        assert!(e.to_string().starts_with("`Lined` came with the tablet"), "{}", e);
+        assert_eq!(system.names(), ["Blank", "Lined"]);
+
+        remove(&system, backups.path(), "Lined", true, false).await.unwrap();
+        assert_eq!(system.names(), ["Blank"]);
+        assert_eq!(system.get("P Lines medium.png"), None);
+    }
+
+    #[tokio::test]
+    async fn the_original_backup_is_taken_before_the_first_change() {
+        let backups = tempfile::tempdir().unwrap();
+        let files = tempfile::tempdir().unwrap();
+        let system = Fake::with(&[("Blank", "Blank")]);
+        let grid = files.path().join("Grid.png");
+        std::fs::write(&grid, png(1404, 1872)).unwrap();
+
+        add(&system, backups.path(), vec![grid], None, vec!["lines".into()], false, false).await.unwrap();
+        assert_eq!(system.names(), ["Blank", "Grid"]);
+        assert_eq!(system.get("Grid.png"), Some(png(1404, 1872)));
+        let (original, saved) = load(&backups.path().join(ORIGINAL)).unwrap();
+        assert_eq!(original.templates.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["Blank"]);
+        assert_eq!(saved, [("Blank.png".to_string(), b"Blank".to_vec())]);
+
+        // Custom templates go without --force, and never into the original
+        remove(&system, backups.path(), "Grid", false, false).await.unwrap();
+        assert_eq!(system.names(), ["Blank"]);
+        assert_eq!(load(&backups.path().join(ORIGINAL)).unwrap().0.templates.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn restores_put_back_listed_files_and_delete_the_rest() {
+        let backups = tempfile::tempdir().unwrap();
+        let system = Fake::with(&[("Blank", "Blank"), ("Grid", "Grid")]);
+        let dir = backups.path().join("2024-05-01_120000");
+        let list = TemplateList {
+            templates: vec![Template { name: "Blank".into(), filename: "Blank".into(), categories: vec!["Lines".into()], other: Map::new() }],
+            other: Map::new(),
+        };
+        save(&dir, &list, &vec![("Blank.png".into(), b"saved".to_vec())]).unwrap();
+        system.put("Blank.png", b"changed since");
+        system.put("Grid.svg", b"<svg/>");
+
+        let outcome = restore(&system, backups.path(), None, false).await.unwrap();
+        assert_eq!(outcome.lines, ["Blank", "Grid (removed)"]);
+        assert_eq!(system.names(), ["Blank"]);
+        assert_eq!(system.get("Blank.png"), Some(b"saved".to_vec()));
+        assert_eq!(system.get("Grid.png"), None);
+        assert_eq!(system.get("Grid.svg"), None);
+    }
+
+    #[tokio::test]
+    async fn dry_runs_write_nothing() {
+        let backups = tempfile::tempdir().unwrap();
+        let files = tempfile::tempdir().unwrap();
+        let system = Fake::with(&[("Blank", "Blank"), ("Grid", "Grid")]);
+        let before = system.0.lock().unwrap().clone();
+        let dots = files.path().join("Dots.png");
+        std::fs::write(&dots, png(1872, 1404)).unwrap();
+        save(&backups.path().join("2024-05-01_120000"), &TemplateList { templates: Vec::new(), other: Map::new() }, &Vec::new()).unwrap();
+
+        let added = add(&system, backups.path(), vec![dots], None, vec!["Lines".into()], false, true).await.unwrap();
+        assert_eq!(added.summary, "Would add 1 template(s) to Lines");
+        let removed = remove(&system, backups.path(), "Grid", true, true).await.unwrap();
+        assert_eq!(removed.summary, "Would remove template `Grid` and its files");
+        let restored = restore(&system, backups.path(), None, true).await.unwrap();
+        assert_eq!(restored.lines, ["Blank (removed)", "Grid (removed)"]);
+
+        assert_eq!(*system.0.lock().unwrap(), before);
+        assert!(!backups.path().join(ORIGINAL).exists());
+    }
+}
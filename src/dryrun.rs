@@ -0,0 +1,88 @@
+use std::fmt;
+
+// --- Dry runs ---
+//
+// With dry run on, transfers stop short of the network and the disk: they
+// collect what they would have done into a `Preview` instead.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Download,
+    Upload,
+}
+
+/// One file a transfer would write.
+#[derive(Debug, Clone)]
+pub struct Planned {
+    pub source: String,
+    pub target: String,
+    /// Size of the source, when known. Tablet sizes are of the stored
+    /// document, so a PDF export may come out larger.
+    pub size: Option<u64>,
+    /// What is in the way at `target`, and what would happen to it.
+    pub conflict: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub direction: Direction,
+    pub files: Vec<Planned>,
+}
+
+impl Preview {
+    pub fn new(direction: Direction) -> Self {
+        Self { direction, files: Vec::new() }
+    }
+
+    pub fn conflicts(&self) -> usize {
+        self.files.iter().filter(|f| f.conflict.is_some()).count()
+    }
+
+    /// One line per file, under the summary.
+    pub fn lines(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .map(|f| {
+                let mut line = format!("{} -> {}", f.source, f.target);
+                if let Some(size) = f.size {
+                    line.push_str(&format!(" ({})", human_size(size)));
+                }
+                if let Some(conflict) = &f.conflict {
+                    line.push_str(&format!(" [{}]", conflict));
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Preview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = match self.direction {
+            Direction::Download => "download",
+            Direction::Upload => "upload",
+        };
+        let total: u64 = self.files.iter().filter_map(|f| f.size).sum();
+        let unknown = self.files.iter().filter(|f| f.size.is_none()).count();
+        write!(f, "Dry run: {} file(s) to {}, about {}", self.files.len(), verb, human_size(total))?;
+        if unknown > 0 {
+            write!(f, " plus {} of unknown size", unknown)?;
+        }
+        write!(f, ", {} conflict(s)", self.conflicts())
+    }
+}
+
+/// `1536` -> `1.5 KB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
@@ -0,0 +1,138 @@
+use crate::{ledger::Ledger, Item};
+use anyhow::Result;
+use std::path::Path;
+
+// --- Duplicate uploads ---
+//
+// The tablet happily keeps several documents with the same name, so an
+// upload is checked against the destination folder's listing first.
+
+/// A document in the destination folder that an upload would duplicate.
+pub struct Duplicate {
+    pub name: String,
+    pub same_size: bool,
+    /// The ledger has this exact content going to this folder before.
+    pub same_content: bool,
+}
+
+impl Duplicate {
+    /// What the two have in common, for the question and the log.
+    pub fn describe(&self) -> String {
+        let mut why = format!("\"{}\" already exists", self.name);
+        if self.same_content {
+            why.push_str("; the same file was uploaded here before");
+        } else if self.same_size {
+            why.push_str(" with the same size");
+        }
+        why
+    }
+}
+
+/// The tablet shows uploads under their file name, with or without the
+/// extension depending on the firmware, so both count as a match.
+fn same_name(item: &Item, file_name: &str, stem: &str) -> bool {
+    !item.is_folder() && (item.visible_name == file_name || item.visible_name == stem)
+}
+
+/// Looks for a document in `listing` (the contents of `tablet_folder`) that
+/// uploading `path` would duplicate.
+pub fn find(listing: &[Item], path: &Path, ledger: Option<&Ledger>, tablet_folder: &str) -> Result<Option<Duplicate>> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let Some(existing) = listing.iter().find(|item| same_name(item, &file_name, &stem)) else {
+        return Ok(None);
+    };
+
+    let size = std::fs::metadata(path)?.len();
+    let same_content = match ledger {
+        Some(ledger) => ledger.contains(&crate::ledger::sha256_file(path)?, tablet_folder),
+        None => false,
+    };
+    Ok(Some(Duplicate {
+        name: existing.visible_name.clone(),
+        same_size: existing.size_bytes() == Some(size),
+        same_content,
+    }))
+}
+
+/// `file_name` with the first ` (n)` suffix no document in `listing` has.
+pub fn suffixed(listing: &[Item], file_name: &str) -> String {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (2..)
+        .map(|n| (format!("{} ({}){}", stem, n, ext), format!("{} ({})", stem, n)))
+        .find(|(name, bare)| !listing.iter().any(|item| same_name(item, name, bare)))
+        .map(|(name, _)| name)
+        .expect("unbounded range always yields a free name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::UploadRecord;
+
+    fn item(name: &str, kind: &str, size: u64) -> Item {
+        serde_json::from_value(serde_json::json!({
+            "ID": name, "VissibleName": name, "Type": kind, "sizeInBytes": size.to_string(),
+        }))
+        .unwrap()
+    }
+
+    fn listing() -> Vec<Item> {
+        vec![item("Scan", "DocumentType", 5), item("Notes.pdf", "DocumentType", 99), item("Folder", "CollectionType", 0)]
+    }
+
+    #[test]
+    fn same_content_in_the_same_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let scan = dir.path().join("Scan.pdf");
+        std::fs::write(&scan, b"%PDF-").unwrap();
+        let mut ledger = Ledger::open(dir.path().join("uploads.jsonl")).unwrap();
+        let record = |folder: &str| UploadRecord {
+            sha256: crate::ledger::sha256_file(&scan).unwrap(),
+            size: 5,
+            local_path: scan.clone(),
+            tablet_folder: folder.into(),
+            uploaded_at: chrono::Utc::now(),
+        };
+        ledger.record(record("/Elsewhere")).unwrap();
+
+        let found = find(&listing(), &scan, Some(&ledger), "/Scans").unwrap().unwrap();
+        assert!(found.same_size && !found.same_content);
+        assert_eq!(found.describe(), "\"Scan\" already exists with the same size");
+
+        ledger.record(record("/Scans")).unwrap();
+        let found = find(&listing(), &scan, Some(&ledger), "/Scans").unwrap().unwrap();
+        assert!(found.same_content);
+        assert_eq!(found.describe(), "\"Scan\" already exists; the same file was uploaded here before");
+    }
+
+    #[test]
+    fn same_name_different_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let notes = dir.path().join("Notes.pdf");
+        std::fs::write(&notes, b"%PDF-1.4 other").unwrap();
+        let found = find(&listing(), &notes, None, "").unwrap().unwrap();
+        assert_eq!(found.name, "Notes.pdf");
+        assert!(!found.same_size && !found.same_content);
+        assert_eq!(found.describe(), "\"Notes.pdf\" already exists");
+
+        // Folders do not count, and other names are free
+        for name in ["Folder.pdf", "Other.pdf"] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"%PDF-").unwrap();
+            assert!(find(&listing(), &path, None, "").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn suffixes_skip_taken_names() {
+        let mut listing = listing();
+        assert_eq!(suffixed(&listing, "Scan.pdf"), "Scan (2).pdf");
+        listing.push(item("Scan (2)", "DocumentType", 5));
+        listing.push(item("Scan (3).pdf", "DocumentType", 5));
+        assert_eq!(suffixed(&listing, "Scan.pdf"), "Scan (4).pdf");
+        assert_eq!(suffixed(&listing, "Folder.pdf"), "Folder (2).pdf");
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+// --- Local file names ---
+
+/// Longest file name most filesystems accept, in bytes.
+const MAX_NAME_BYTES: usize = 255;
+
+/// Device names Windows refuses as file names, with or without an extension.
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_illegal(c: char, ascii_only: bool) -> bool {
+    matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+        || c.is_control()
+        || (ascii_only && !(c.is_ascii_graphic() || c == ' '))
+}
+
+/// Turns a tablet name into something every common filesystem accepts,
+/// keeping it as readable as possible. Only characters that are illegal
+/// somewhere are replaced, and long names are shortened. With `ascii_only`
+/// the name is transliterated first, so "Réunion – Q3" becomes "Reunion - Q3".
+pub fn sanitize(name: &str, ascii_only: bool) -> String {
+    let name = if ascii_only { deunicode::deunicode(name) } else { name.to_string() };
+    let replaced: String = name.chars().map(|c| if is_illegal(c, ascii_only) { '_' } else { c }).collect();
+    let mut safe = clean_ends(&replaced);
+
+    let stem = safe.split('.').next().unwrap_or_default();
+    if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem.trim_end())) {
+        safe.insert(stem.len(), '_');
+    }
+    if safe.is_empty() || safe.chars().all(|c| c == '.') {
+        safe = "_".into();
+    }
+    with_extension(&safe, "")
+}
+
+/// Windows drops trailing dots and spaces, and leading spaces are easy to miss.
+fn clean_ends(name: &str) -> String {
+    name.trim_start_matches(' ').trim_end_matches(['.', ' ']).to_string()
+}
+
+/// Cuts `name` to fit in `max` bytes without splitting a character.
+fn truncate(name: &str, max: usize) -> &str {
+    let end = (0..=max.min(name.len())).rev().find(|&i| name.is_char_boundary(i)).unwrap_or(0);
+    &name[..end]
+}
+
+/// Joins `stem` and `ext`, shortening the stem so the whole name fits.
+fn with_extension(stem: &str, ext: &str) -> String {
+    let (stem, ext) = match (ext.is_empty(), stem.rsplit_once('.')) {
+        // Keep an extension that is already part of the name
+        (true, Some((base, existing))) if !base.is_empty() && existing.len() < 16 => (base, &stem[base.len()..]),
+        _ => (stem, ext),
+    };
+    let short = clean_ends(truncate(stem, MAX_NAME_BYTES - ext.len()));
+    format!("{}{}", if short.is_empty() { "_" } else { &short }, ext)
+}
+
+/// Keeps sibling names apart once sanitizing has made them equal, e.g.
+/// `a/b` and `a:b`. Names are compared case-insensitively since macOS and
+/// Windows filesystems usually are.
+#[derive(Default)]
+pub struct Siblings {
+    taken: HashSet<PathBuf>,
+}
+
+impl Siblings {
+    /// Returns `path`, or `name (2).ext`, `name (3).ext`, ... next to it if
+    /// already used.
+    pub fn claim(&mut self, path: PathBuf) -> PathBuf {
+        let key = |p: &Path| PathBuf::from(p.to_string_lossy().to_lowercase());
+        if self.taken.insert(key(&path)) {
+            return path;
+        }
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem.to_string(), format!(".{}", ext)),
+            _ => (name.clone(), String::new()),
+        };
+        (2..)
+            .map(|n| {
+                // Shorten the stem rather than cut off the number
+                let number = format!(" ({})", n);
+                let short = clean_ends(truncate(&stem, MAX_NAME_BYTES.saturating_sub(ext.len() + number.len())));
+                path.with_file_name(format!("{}{}{}", short, number, ext))
+            })
+            .find(|candidate| self.taken.insert(key(candidate)))
+            .expect("unbounded range always yields a free name")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_names() {
+        let cases = [
+            ("Notes", "Notes"),
+            ("a/b:c*d?", "a_b_c_d_"),
+            ("tab\there", "tab_here"),
+            ("Réunion – Q3", "Réunion – Q3"),
+            // Reserved on Windows, with or without an extension
+            ("CON", "CON_"),
+            ("con.txt", "con_.txt"),
+            ("LPT1 ", "LPT1_"),
+            ("CONSOLE", "CONSOLE"),
+            // Windows drops trailing dots and spaces
+            ("Draft...", "Draft"),
+            ("  Draft . ", "Draft"),
+            ("...", "_"),
+            ("", "_"),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(sanitize(name, false), expected, "{:?}", name);
+        }
+        assert_eq!(sanitize("Réunion – Q3", true), "Reunion - Q3");
+        assert_eq!(sanitize("日本", true), "Ri Ben");
+    }
+
+    #[test]
+    fn long_names_fit_in_255_bytes() {
+        let cases = [
+            ("a".repeat(300), "a".repeat(255)),
+            ("a".repeat(300) + ".pdf", "a".repeat(251) + ".pdf"),
+            // Never half a character
+            ("é".repeat(200), "é".repeat(127)),
+            ("é".repeat(200) + ".pdf", "é".repeat(125) + ".pdf"),
+            // A cut that ends in a space loses it too
+            ("a".repeat(254) + " b", "a".repeat(254)),
+            // Anything this long is not an extension
+            ("a.".to_string() + &"b".repeat(300), "a.".to_string() + &"b".repeat(253)),
+        ];
+        for (name, expected) in cases {
+            let safe = sanitize(&name, false);
+            assert!(safe.len() <= MAX_NAME_BYTES);
+            assert_eq!(safe, expected, "{} bytes", name.len());
+        }
+    }
+
+    #[test]
+    fn siblings_are_numbered() {
+        let mut names = Siblings::default();
+        let claim = |names: &mut Siblings, path: &str| names.claim(PathBuf::from(path)).display().to_string();
+        assert_eq!(claim(&mut names, "x/Paper.pdf"), "x/Paper.pdf");
+        assert_eq!(claim(&mut names, "x/Paper.pdf"), "x/Paper (2).pdf");
+        // Case is ignored, as on macOS and Windows
+        assert_eq!(claim(&mut names, "x/paper.PDF"), "x/paper (3).PDF");
+        assert_eq!(claim(&mut names, "y/Paper.pdf"), "y/Paper.pdf");
+        assert_eq!(claim(&mut names, "x/Notes"), "x/Notes");
+        assert_eq!(claim(&mut names, "x/Notes"), "x/Notes (2)");
+        assert_eq!(claim(&mut names, "x/.hidden"), "x/.hidden");
+        assert_eq!(claim(&mut names, "x/.hidden"), "x/.hidden (2)");
+
+        // The number still fits next to a name at the limit
+        let long = format!("x/{}.pdf", "a".repeat(251));
+        assert_eq!(claim(&mut names, &long), long);
+        assert_eq!(claim(&mut names, &long), format!("x/{} (2).pdf", "a".repeat(247)));
+    }
+}
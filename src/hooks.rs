@@ -0,0 +1,136 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, process::Stdio};
+use tokio::process::Command;
+
+// --- Post-transfer hooks ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    Download,
+    Upload,
+    /// Both downloads and uploads.
+    Any,
+}
+
+/// A `[[hooks]]` entry: a shell command run after each transferred file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    #[serde(default = "HookConfig::default_on")]
+    pub on: HookEvent,
+    /// Run with `sh -c`; the transfer is described in `RM_*` variables.
+    pub command: String,
+}
+
+impl HookConfig {
+    fn default_on() -> HookEvent {
+        HookEvent::Any
+    }
+
+    fn matches(&self, event: HookEvent) -> bool {
+        self.on == HookEvent::Any || self.on == event
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Skipped,
+    Failed,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Skipped => "skipped",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+/// One transferred (or attempted) file.
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub event: HookEvent,
+    pub status: Status,
+    pub local_path: PathBuf,
+    /// `/Folder/Document`; folders are separated by `/`.
+    pub tablet_path: String,
+    /// Empty for uploads, whose ID the web interface does not report.
+    pub guid: String,
+    pub format: String,
+}
+
+impl Transfer {
+    fn event_name(&self) -> &'static str {
+        match self.event {
+            HookEvent::Upload => "upload",
+            _ => "download",
+        }
+    }
+
+    fn env(&self) -> [(&'static str, String); 6] {
+        [
+            ("RM_EVENT", self.event_name().to_string()),
+            ("RM_STATUS", self.status.as_str().to_string()),
+            ("RM_LOCAL_PATH", self.local_path.to_string_lossy().to_string()),
+            ("RM_TABLET_PATH", self.tablet_path.clone()),
+            ("RM_GUID", self.guid.clone()),
+            ("RM_FORMAT", self.format.clone()),
+        ]
+    }
+}
+
+/// The outcome of one hook, for the log view.
+#[derive(Debug, Clone)]
+pub struct HookRun {
+    pub started: DateTime<Local>,
+    pub command: String,
+    pub transfer: String,
+    /// Exit code, or an error if the command could not be run at all.
+    pub result: Result<i32, String>,
+    /// Combined stdout and stderr.
+    pub output: String,
+}
+
+impl HookRun {
+    pub fn succeeded(&self) -> bool {
+        self.result == Ok(0)
+    }
+}
+
+/// The hooks that apply to `event`.
+pub fn matching(hooks: &[HookConfig], event: HookEvent) -> Vec<HookConfig> {
+    hooks.iter().filter(|hook| hook.matches(event)).cloned().collect()
+}
+
+pub async fn run(hook: HookConfig, transfer: Transfer) -> HookRun {
+    let started = Local::now();
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(&hook.command)
+        .envs(transfer.env())
+        .stdin(Stdio::null())
+        .output()
+        .await;
+
+    let (result, output) = match output {
+        Ok(out) => {
+            let mut text = String::from_utf8_lossy(&out.stdout).to_string();
+            text.push_str(&String::from_utf8_lossy(&out.stderr));
+            // Killed by a signal counts as a failure without a code
+            (Ok(out.status.code().unwrap_or(-1)), text)
+        }
+        Err(e) => (Err(e.to_string()), String::new()),
+    };
+    HookRun {
+        started,
+        command: hook.command,
+        transfer: format!("{} {} {}", transfer.event_name(), transfer.status.as_str(), transfer.tablet_path),
+        result,
+        output,
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    collections::BTreeMap,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// --- Download integrity ---
+//
+// Downloads land in a temporary file that only replaces the target once it
+// has the announced length and looks like a whole PDF. Each one is then
+// listed in a `SHA256SUMS` file in the download directory, in the format
+// `sha256sum -c` reads, so `remarkable verify` (or any other tool) can check
+// a backup later.
+
+pub const MANIFEST: &str = "SHA256SUMS";
+
+/// Where a download is written until it is complete: `.name.pdf.part`
+/// next to the target, hidden so it is not mistaken for a finished file.
+pub fn temp_path(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    target.with_file_name(format!(".{}.part", name))
+}
+
+/// Checks that `path` has a PDF header and ends with a cross-reference
+/// pointer and end-of-file marker, which a cut-off transfer lacks.
+pub fn check_pdf(path: &Path) -> Result<()> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+
+    // Readers accept junk before the header within the first kilobyte
+    let mut head = vec![0; len.min(1024) as usize];
+    file.read_exact(&mut head)?;
+    if !contains(&head, b"%PDF-") {
+        return Err(anyhow!("not a PDF (no %PDF header)"));
+    }
+
+    let tail_len = len.min(2048);
+    let mut tail = vec![0; tail_len as usize];
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    file.read_exact(&mut tail)?;
+    if !contains(&tail, b"startxref") {
+        return Err(anyhow!("PDF is truncated (no startxref near the end)"));
+    }
+    if !contains(&tail, b"%%EOF") {
+        return Err(anyhow!("PDF is truncated (no %%EOF marker)"));
+    }
+    Ok(())
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Held while a manifest is rewritten, so downloads finishing together do
+/// not drop each other's lines.
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// Lists `file` in the manifest in `root`, replacing any earlier line for it.
+pub fn record(root: &Path, file: &Path, sha256: &str) -> Result<()> {
+    let relative = file.strip_prefix(root).unwrap_or(file).display().to_string();
+    rewrite(&root.join(MANIFEST), |entries| {
+        entries.insert(relative, sha256.to_string());
+    })
+}
+
+/// Drops `file` from the manifest of whichever folder above it lists it,
+/// e.g. after a sync deleted it.
+pub fn forget(file: &Path) -> Result<()> {
+    for root in file.ancestors().skip(1) {
+        let manifest = root.join(MANIFEST);
+        let relative = file.strip_prefix(root).unwrap_or(file).display().to_string();
+        if manifest.is_file() && read(&manifest)?.contains_key(&relative) {
+            return rewrite(&manifest, |entries| {
+                entries.remove(&relative);
+            });
+        }
+    }
+    Ok(())
+}
+
+/// `<hex>  <path>` lines by path. `sha256sum` marks binary mode with `*`
+/// instead of the second space; later lines for a path win.
+fn parse(text: &str) -> BTreeMap<String, String> {
+    let mut entries = BTreeMap::new();
+    for line in text.lines() {
+        if let Some((hash, name)) = line.split_once(' ') {
+            let name = name.strip_prefix([' ', '*']).unwrap_or(name);
+            entries.insert(name.to_string(), hash.to_lowercase());
+        }
+    }
+    entries
+}
+
+fn read(manifest: &Path) -> Result<BTreeMap<String, String>> {
+    match std::fs::read_to_string(manifest) {
+        Ok(text) => Ok(parse(&text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("Could not read {}", manifest.display())),
+    }
+}
+
+/// Applies `change` to the manifest and writes it back whole, through a
+/// temporary file so a crash never leaves half a manifest.
+fn rewrite(manifest: &Path, change: impl FnOnce(&mut BTreeMap<String, String>)) -> Result<()> {
+    let _writing = WRITING.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = read(manifest)?;
+    change(&mut entries);
+    let tmp = manifest.with_file_name(format!(".{}.tmp", MANIFEST));
+    let mut text = String::new();
+    for (name, hash) in &entries {
+        text.push_str(&format!("{}  {}\n", hash, name));
+    }
+    std::fs::write(&tmp, text).with_context(|| format!("Could not write {}", manifest.display()))?;
+    std::fs::rename(&tmp, manifest).with_context(|| format!("Could not write {}", manifest.display()))?;
+    Ok(())
+}
+
+/// The outcome for one file listed in a manifest.
+pub enum Verdict {
+    Ok,
+    Missing,
+    Mismatch,
+    /// The checksum matches but the file does not look like a whole PDF.
+    Damaged(String),
+}
+
+/// Checks every file listed in `manifest`, in path order.
+pub fn verify(manifest: &Path) -> Result<Vec<(PathBuf, Verdict)>> {
+    let text = std::fs::read_to_string(manifest).with_context(|| format!("Could not read {}", manifest.display()))?;
+    let root = manifest.parent().unwrap_or(Path::new("."));
+
+    let mut results = Vec::new();
+    for (name, hash) in parse(&text) {
+        let path = root.join(name);
+        let verdict = if !path.exists() {
+            Verdict::Missing
+        } else if crate::ledger::sha256_file(&path)? != hash {
+            Verdict::Mismatch
+        } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf")) {
+            match check_pdf(&path) {
+                Ok(()) => Verdict::Ok,
+                Err(e) => Verdict::Damaged(e.to_string()),
+            }
+        } else {
+            Verdict::Ok
+        };
+        results.push((path, verdict));
+    }
+    Ok(results)
+}
+
+/// Every manifest in `dir` or below it.
+pub fn find_manifests(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let manifest = dir.join(MANIFEST);
+    if manifest.is_file() {
+        out.push(manifest);
+    }
+    let mut subdirs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Could not read {}", dir.display()))?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    subdirs.sort();
+    for sub in subdirs {
+        find_manifests(&sub, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PDF: &[u8] = b"%PDF-1.4\n1 0 obj << >> endobj\nstartxref\n9\n%%EOF\n";
+
+    fn write(dir: &Path, name: &str, bytes: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        check_pdf(&write(dir.path(), "a.pdf", bytes)).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn whole_pdfs_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        check_pdf(&write(dir.path(), "a.pdf", PDF)).unwrap();
+        // Junk before the header is fine
+        check_pdf(&write(dir.path(), "b.pdf", &[b"\xef\xbb\xbf".as_slice(), PDF].concat())).unwrap();
+    }
+
+    #[test]
+    fn cut_off_pdfs_fail() {
+        assert_eq!(error(b""), "not a PDF (no %PDF header)");
+        assert_eq!(error(b"<html>"), "not a PDF (no %PDF header)");
+        assert_eq!(error(&PDF[..20]), "PDF is truncated (no startxref near the end)");
+        assert_eq!(error(&PDF[..PDF.len() - 4]), "PDF is truncated (no %%EOF marker)");
+    }
+
+    #[test]
+    fn record_keeps_one_line_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        record(root, &root.join("b.pdf"), "22").unwrap();
+        record(root, &root.join("Work/a.pdf"), "11").unwrap();
+        record(root, &root.join("b.pdf"), "33").unwrap();
+        assert_eq!(std::fs::read_to_string(root.join(MANIFEST)).unwrap(), "11  Work/a.pdf\n33  b.pdf\n");
+        assert!(!root.join(format!(".{}.tmp", MANIFEST)).exists());
+
+        forget(&root.join("Work/a.pdf")).unwrap();
+        forget(&root.join("unlisted.pdf")).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join(MANIFEST)).unwrap(), "33  b.pdf\n");
+    }
+
+    #[test]
+    fn verify_reports_each_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let sum = |path: &Path| crate::ledger::sha256_file(path).unwrap();
+        let good = write(root, "good.pdf", PDF);
+        record(root, &good, &sum(&good)).unwrap();
+        let changed = write(root, "Work/changed.pdf", PDF);
+        record(root, &changed, &sum(&changed)).unwrap();
+        write(root, "Work/changed.pdf", &PDF[1..]);
+        let cut = write(root, "cut.pdf", &PDF[..20]);
+        record(root, &cut, &sum(&cut)).unwrap();
+        record(root, &root.join("missing.pdf"), "00").unwrap();
+        // Upper-case hashes and binary-mode markers are read as well
+        let notes = write(root, "notes.txt", b"notes");
+        let line = format!("{}  good.pdf\n{} *notes.txt\n", sum(&good), sum(&notes).to_uppercase());
+        let manifest = root.join(MANIFEST);
+        let text = std::fs::read_to_string(&manifest).unwrap() + &line;
+        std::fs::write(&manifest, text).unwrap();
+
+        let verdicts: Vec<(String, &str)> = verify(&manifest)
+            .unwrap()
+            .iter()
+            .map(|(path, verdict)| {
+                let name = path.strip_prefix(root).unwrap().display().to_string();
+                let verdict = match verdict {
+                    Verdict::Ok => "ok",
+                    Verdict::Missing => "missing",
+                    Verdict::Mismatch => "mismatch",
+                    Verdict::Damaged(_) => "damaged",
+                };
+                (name, verdict)
+            })
+            .collect();
+        let expected = [("Work/changed.pdf", "mismatch"), ("cut.pdf", "damaged"), ("good.pdf", "ok"), ("missing.pdf", "missing"), ("notes.txt", "ok")];
+        assert_eq!(verdicts, expected.map(|(n, v)| (n.to_string(), v)));
+    }
+}
@@ -0,0 +1,680 @@
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+// --- Actions ---
+
+/// Where an action applies. Bindings in different contexts never clash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Browse,
+    Prompt,
+    Palette,
+    Conflict,
+}
+
+impl Context {
+    pub const ALL: &'static [Context] = &[Context::Browse, Context::Prompt, Context::Palette, Context::Conflict];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Context::Browse => "Browsing",
+            Context::Prompt => "Prompts & palette",
+            Context::Palette => "Command palette",
+            Context::Conflict => "File conflict",
+        }
+    }
+
+    /// Whether actions of `other` are live in this context. The palette and
+    /// the conflict question are prompts too, so confirm/cancel work there.
+    fn accepts(self, other: Context) -> bool {
+        self == other || (matches!(self, Context::Palette | Context::Conflict) && other == Context::Prompt)
+    }
+
+    fn overlaps(self, other: Context) -> bool {
+        self.accepts(other) || other.accepts(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Down,
+    Up,
+    Top,
+    Bottom,
+    Open,
+    Back,
+    Download,
+    Upload,
+    View,
+    Rename,
+    Move,
+    Delete,
+    Mkdir,
+    Star,
+    EditTags,
+    Refresh,
+    SortName,
+    SortModified,
+    SortType,
+    Mark,
+    Filter,
+    Favourites,
+    Tagged,
+    #[serde(rename = "trash")]
+    ShowTrash,
+    Restore,
+    Purge,
+    TogglePanes,
+    SwitchPane,
+    Copy,
+    DryRun,
+    Log,
+    Help,
+    Palette,
+    Confirm,
+    Cancel,
+    Complete,
+    HistoryPrev,
+    HistoryNext,
+    ConflictOverwrite,
+    ConflictSkip,
+    ConflictRename,
+    ConflictNewer,
+    UploadAnyway,
+    ApplyToAll,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::Down,
+        Action::Up,
+        Action::Top,
+        Action::Bottom,
+        Action::Open,
+        Action::Back,
+        Action::Download,
+        Action::Upload,
+        Action::View,
+        Action::Rename,
+        Action::Move,
+        Action::Delete,
+        Action::Mkdir,
+        Action::Star,
+        Action::EditTags,
+        Action::Refresh,
+        Action::SortName,
+        Action::SortModified,
+        Action::SortType,
+        Action::Mark,
+        Action::Filter,
+        Action::Favourites,
+        Action::Tagged,
+        Action::ShowTrash,
+        Action::Restore,
+        Action::Purge,
+        Action::TogglePanes,
+        Action::SwitchPane,
+        Action::Copy,
+        Action::DryRun,
+        Action::Log,
+        Action::Help,
+        Action::Palette,
+        Action::Confirm,
+        Action::Cancel,
+        Action::Complete,
+        Action::HistoryPrev,
+        Action::HistoryNext,
+        Action::ConflictOverwrite,
+        Action::ConflictSkip,
+        Action::ConflictRename,
+        Action::ConflictNewer,
+        Action::UploadAnyway,
+        Action::ApplyToAll,
+    ];
+
+    pub fn context(self) -> Context {
+        match self {
+            Action::Confirm | Action::Cancel => Context::Prompt,
+            Action::Complete | Action::HistoryPrev | Action::HistoryNext => Context::Palette,
+            Action::ConflictOverwrite
+            | Action::ConflictSkip
+            | Action::ConflictRename
+            | Action::ConflictNewer
+            | Action::UploadAnyway
+            | Action::ApplyToAll => Context::Conflict,
+            _ => Context::Browse,
+        }
+    }
+
+    /// The name used in the `[keys]` config table.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::Open => "open",
+            Action::Back => "back",
+            Action::Download => "download",
+            Action::Upload => "upload",
+            Action::View => "view",
+            Action::Rename => "rename",
+            Action::Move => "move",
+            Action::Delete => "delete",
+            Action::Mkdir => "mkdir",
+            Action::Star => "star",
+            Action::EditTags => "edit_tags",
+            Action::Refresh => "refresh",
+            Action::SortName => "sort_name",
+            Action::SortModified => "sort_modified",
+            Action::SortType => "sort_type",
+            Action::Mark => "mark",
+            Action::Filter => "filter",
+            Action::Favourites => "favourites",
+            Action::Tagged => "tagged",
+            Action::ShowTrash => "trash",
+            Action::Restore => "restore",
+            Action::Purge => "purge",
+            Action::TogglePanes => "toggle_panes",
+            Action::SwitchPane => "switch_pane",
+            Action::Copy => "copy",
+            Action::DryRun => "dry_run",
+            Action::Log => "log",
+            Action::Help => "help",
+            Action::Palette => "palette",
+            Action::Confirm => "confirm",
+            Action::Cancel => "cancel",
+            Action::Complete => "complete",
+            Action::HistoryPrev => "history_prev",
+            Action::HistoryNext => "history_next",
+            Action::ConflictOverwrite => "conflict_overwrite",
+            Action::ConflictSkip => "conflict_skip",
+            Action::ConflictRename => "conflict_rename",
+            Action::ConflictNewer => "conflict_newer",
+            Action::UploadAnyway => "upload_anyway",
+            Action::ApplyToAll => "apply_to_all",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Down => "Move selection down",
+            Action::Up => "Move selection up",
+            Action::Top => "Jump to first item",
+            Action::Bottom => "Jump to last item",
+            Action::Open => "Open folder",
+            Action::Back => "Go back a directory",
+            Action::Download => "Download selection",
+            Action::Upload => "Upload a local file",
+            Action::View => "Open the document in a local viewer",
+            Action::Rename => "Rename the selection in place",
+            Action::Move => "Move the selection: pick it up, then drop it in another folder",
+            Action::Delete => "Move the selection to the tablet's trash",
+            Action::Mkdir => "Create a folder in the current folder",
+            Action::Star => "Star or unstar the selection as a favourite",
+            Action::EditTags => "Edit the tags of the selection",
+            Action::Refresh => "Refresh file list",
+            Action::SortName => "Sort by name",
+            Action::SortModified => "Sort by last modified, newest first",
+            Action::SortType => "Sort folders first, then by name",
+            Action::Mark => "Mark or unmark the selection for copying",
+            Action::Filter => "Filter the focused list by name, or by tag with #tag",
+            Action::Favourites => "Show the starred documents and folders of the whole library",
+            Action::Tagged => "Show everything with a tag, or with the given tag, across the library",
+            Action::ShowTrash => "Show the tablet's trash",
+            Action::Restore => "Restore the selection from the trash to the folder it was deleted from",
+            Action::Purge => "Delete the selection from the trash for good, after confirming",
+            Action::TogglePanes => "Toggle the local file pane",
+            Action::SwitchPane => "Switch focus between panes",
+            Action::Copy => "Copy marked items to the other pane",
+            Action::DryRun => "Toggle dry run: preview transfers without making them",
+            Action::Log => "Show transfers and hook output",
+            Action::Help => "Toggle help overlay",
+            Action::Palette => "Open command palette",
+            Action::Confirm => "Confirm",
+            Action::Cancel => "Cancel",
+            Action::Complete => "Complete the highlighted command",
+            Action::HistoryPrev => "Previous command from history",
+            Action::HistoryNext => "Next command from history",
+            Action::ConflictOverwrite => "Overwrite the existing file",
+            Action::ConflictSkip => "Keep the existing file",
+            Action::ConflictRename => "Keep both; add a numeric suffix",
+            Action::ConflictNewer => "Overwrite only if the tablet copy is newer",
+            Action::UploadAnyway => "Upload even though a document with that name exists",
+            Action::ApplyToAll => "Toggle: use this answer for the rest of the job",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Down => &["j", "<Down>"],
+            Action::Up => &["k", "<Up>"],
+            Action::Top => &["gg", "<Home>"],
+            Action::Bottom => &["G", "<End>"],
+            Action::Open => &["l", "<Enter>", "<Right>"],
+            Action::Back => &["h", "<Backspace>", "<Left>"],
+            Action::Download => &["d"],
+            Action::Upload => &["u"],
+            Action::View => &["o"],
+            Action::Rename => &["R"],
+            Action::Move => &["m"],
+            Action::Delete => &["x", "<Delete>"],
+            Action::Mkdir => &["N"],
+            Action::Star => &["*"],
+            Action::EditTags => &["T"],
+            Action::Refresh => &["r"],
+            Action::SortName | Action::SortModified | Action::SortType => &[],
+            Action::Mark => &["<Space>"],
+            Action::Filter => &["/"],
+            Action::Favourites => &["F"],
+            Action::Tagged => &["#"],
+            Action::ShowTrash => &["X"],
+            Action::Restore => &["U"],
+            Action::Purge => &["P"],
+            Action::TogglePanes => &["<F2>"],
+            Action::SwitchPane => &["<Tab>"],
+            Action::Copy => &["c", "<F5>"],
+            Action::DryRun => &["D"],
+            Action::Log => &["L"],
+            Action::Help => &["?"],
+            Action::Palette => &[":"],
+            Action::Confirm => &["<Enter>"],
+            Action::Cancel => &["<Esc>"],
+            Action::Complete => &["<Tab>"],
+            Action::HistoryPrev => &["<Up>", "<C-p>"],
+            Action::HistoryNext => &["<Down>", "<C-n>"],
+            Action::ConflictOverwrite => &["o"],
+            Action::ConflictSkip => &["s"],
+            Action::ConflictRename => &["r"],
+            Action::ConflictNewer => &["n"],
+            Action::UploadAnyway => &["u"],
+            Action::ApplyToAll => &["a"],
+        }
+    }
+}
+
+// --- Keys ---
+
+/// A single key press with its modifiers. Shift is folded into the
+/// character itself, so `G` and `<S-g>` are the same chord.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code: event.code, modifiers }
+    }
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Space", KeyCode::Char(' ')),
+    ("lt", KeyCode::Char('<')),
+];
+
+impl KeyChord {
+    fn char(c: char) -> Self {
+        Self { code: KeyCode::Char(c), modifiers: KeyModifiers::NONE }
+    }
+
+    pub fn code(&self) -> KeyCode {
+        self.code
+    }
+
+    /// The character typed, if this is a plain (unmodified) character key.
+    pub fn as_char(&self) -> Option<char> {
+        match self.code {
+            KeyCode::Char(c) if self.modifiers.is_empty() => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Parses the inside of a `<...>` group, e.g. `C-d`, `A-Enter`, `F5`.
+    fn parse_named(spec: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        while rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+            match rest.as_bytes()[0].to_ascii_uppercase() {
+                b'C' => modifiers |= KeyModifiers::CONTROL,
+                b'A' | b'M' => modifiers |= KeyModifiers::ALT,
+                b'S' => modifiers |= KeyModifiers::SHIFT,
+                _ => return None,
+            }
+            rest = &rest[2..];
+        }
+
+        let code = if let Some((_, code)) = NAMED_KEYS.iter().find(|(name, _)| name.eq_ignore_ascii_case(rest)) {
+            *code
+        } else if let Some(n) = rest.strip_prefix(['F', 'f']).and_then(|n| n.parse::<u8>().ok()) {
+            KeyCode::F(n)
+        } else {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        };
+
+        let mut chord = Self { code, modifiers };
+        if let KeyCode::Char(c) = chord.code {
+            if chord.modifiers.contains(KeyModifiers::SHIFT) {
+                chord.modifiers.remove(KeyModifiers::SHIFT);
+                chord.code = KeyCode::Char(c.to_ascii_uppercase());
+            }
+        }
+        Some(chord)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char('<') => "lt".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            code => NAMED_KEYS
+                .iter()
+                .find(|(_, named)| *named == code)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_else(|| format!("{:?}", code)),
+        };
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "C-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "A-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "S-")?;
+        }
+        write!(f, "{}", name)
+    }
+}
+
+/// A sequence of chords such as `gg` or `<C-w>j`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySequence(Vec<KeyChord>);
+
+impl KeySequence {
+    /// Vim-style notation: plain characters stand for themselves and named
+    /// keys go in angle brackets (`<Enter>`, `<C-d>`, `<lt>` for `<`). A bare
+    /// key name such as `Enter` is accepted as a shorthand for `<Enter>`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        if spec.chars().count() > 1 && !spec.contains('<') {
+            if let Some(chord) = KeyChord::parse_named(spec) {
+                return Some(Self(vec![chord]));
+            }
+        }
+
+        let mut chords = Vec::new();
+        let mut rest = spec;
+        while let Some(c) = rest.chars().next() {
+            if c == '<' {
+                let end = rest.find('>')?;
+                chords.push(KeyChord::parse_named(&rest[1..end])?);
+                rest = &rest[end + 1..];
+            } else {
+                chords.push(KeyChord::char(c));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+
+        if chords.is_empty() {
+            None
+        } else {
+            Some(Self(chords))
+        }
+    }
+}
+
+impl KeySequence {
+    /// The sequence in config-file notation, which `parse` reads back.
+    pub fn notation(&self) -> String {
+        self.0
+            .iter()
+            .map(|chord| match chord.code {
+                KeyCode::Char(c) if chord.modifiers.is_empty() && c != ' ' && c != '<' => c.to_string(),
+                _ => format!("<{}>", chord),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let [chord] = self.0.as_slice() {
+            match chord.code {
+                KeyCode::Char('<') if chord.modifiers.is_empty() => write!(f, "<"),
+                _ => write!(f, "{}", chord),
+            }
+        } else {
+            write!(f, "{}", self.notation())
+        }
+    }
+}
+
+// --- Keymap ---
+
+pub enum Resolution {
+    Action(Action),
+    /// The keys so far are the start of a longer binding.
+    Pending,
+    Unbound,
+}
+
+pub struct Keymap {
+    bindings: Vec<(KeySequence, Action)>,
+}
+
+impl Keymap {
+    /// Builds the default keymap, replacing the bindings of any action that
+    /// appears in `overrides` (the `[keys]` config table).
+    pub fn new(overrides: &BTreeMap<Action, Vec<String>>) -> Result<Self> {
+        let mut bindings: Vec<(KeySequence, Action)> = Vec::new();
+
+        for &action in Action::ALL {
+            let specs: Vec<&str> = match overrides.get(&action) {
+                Some(keys) => keys.iter().map(String::as_str).collect(),
+                None => action.default_keys().to_vec(),
+            };
+
+            for spec in specs {
+                let seq = KeySequence::parse(spec)
+                    .ok_or_else(|| anyhow!("`keys.{}`: cannot parse key `{}`", action.name(), spec))?;
+
+                let clash = bindings.iter().find(|(other, other_action)| {
+                    other_action.context().overlaps(action.context())
+                        && (other.0.starts_with(&seq.0) || seq.0.starts_with(&other.0))
+                });
+                if let Some((other, other_action)) = clash {
+                    // Blame whichever side the user actually wrote
+                    let (culprit, key, victim, victim_key) = if overrides.contains_key(&action) {
+                        (action, seq.notation(), *other_action, other.notation())
+                    } else {
+                        (*other_action, other.notation(), action, seq.notation())
+                    };
+                    return Err(anyhow!(
+                        "`keys.{}`: `{}` conflicts with `{}` bound to `{}`",
+                        culprit.name(),
+                        key,
+                        victim_key,
+                        victim.name()
+                    ));
+                }
+
+                bindings.push((seq, action));
+            }
+        }
+
+        Ok(Self { bindings })
+    }
+
+    /// Looks `keys` up among the bindings live in `context`.
+    pub fn resolve(&self, context: Context, keys: &[KeyChord]) -> Resolution {
+        let mut pending = false;
+        for (seq, action) in &self.bindings {
+            if !context.accepts(action.context()) || !seq.0.starts_with(keys) {
+                continue;
+            }
+            if seq.0.len() == keys.len() {
+                return Resolution::Action(*action);
+            }
+            pending = true;
+        }
+        if pending {
+            Resolution::Pending
+        } else {
+            Resolution::Unbound
+        }
+    }
+
+    pub fn keys_for(&self, action: Action) -> impl Iterator<Item = &KeySequence> {
+        self.bindings.iter().filter(move |(_, a)| *a == action).map(|(seq, _)| seq)
+    }
+
+    /// The first binding of an action, as shown in the help bar.
+    pub fn primary(&self, action: Action) -> Option<String> {
+        self.keys_for(action).next().map(|seq| seq.to_string())
+    }
+
+    /// All bindings of an action joined for display, e.g. `j, Down`.
+    pub fn describe(&self, action: Action) -> String {
+        let keys: Vec<String> = self.keys_for(action).map(|seq| seq.to_string()).collect();
+        if keys.is_empty() {
+            "(unbound)".into()
+        } else {
+            keys.join(", ")
+        }
+    }
+
+    /// The effective bindings in the same notation the config file uses.
+    pub fn to_table(&self) -> BTreeMap<Action, Vec<String>> {
+        Action::ALL
+            .iter()
+            .map(|&action| (action, self.keys_for(action).map(KeySequence::notation).collect()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn chords(spec: &str) -> Vec<KeyChord> {
+        KeySequence::parse(spec).unwrap().0
+    }
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord { code, modifiers }
+    }
+
+    fn action(keymap: &Keymap, context: Context, spec: &str) -> Option<Action> {
+        match keymap.resolve(context, &chords(spec)) {
+            Resolution::Action(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_the_notation() {
+        assert_eq!(chords("gg"), [KeyChord::char('g'), KeyChord::char('g')]);
+        assert_eq!(chords("<C-x>"), [chord(KeyCode::Char('x'), KeyModifiers::CONTROL)]);
+        assert_eq!(chords("<Space>"), [KeyChord::char(' ')]);
+        assert_eq!(chords("<lt>"), [KeyChord::char('<')]);
+        assert_eq!(chords("<S-g>"), [KeyChord::char('G')]);
+        assert_eq!(chords("<C-w>j"), [chord(KeyCode::Char('w'), KeyModifiers::CONTROL), KeyChord::char('j')]);
+        assert_eq!(chords("<a-enter>"), [chord(KeyCode::Enter, KeyModifiers::ALT)]);
+        // Bare key names need no brackets
+        assert_eq!(chords("f5"), [chord(KeyCode::F(5), KeyModifiers::NONE)]);
+        assert_eq!(chords("Enter"), [chord(KeyCode::Enter, KeyModifiers::NONE)]);
+        for bad in ["", "<C-x", "<Nope>", "<Q-x>"] {
+            assert!(KeySequence::parse(bad).is_none(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn notation_reads_back() {
+        for spec in ["gg", "<C-x>", "<Space>", "<lt>", "<F5>", "<C-w>j", "G"] {
+            let seq = KeySequence::parse(spec).unwrap();
+            assert_eq!(seq.notation(), spec);
+            assert_eq!(KeySequence::parse(&seq.notation()), Some(seq));
+        }
+        assert_eq!(KeySequence::parse("<lt>").unwrap().to_string(), "<");
+        assert_eq!(KeySequence::parse("<C-x>").unwrap().to_string(), "C-x");
+    }
+
+    #[test]
+    fn sequences_wait_for_their_last_key() {
+        let keymap = Keymap::new(&BTreeMap::new()).unwrap();
+        assert!(matches!(keymap.resolve(Context::Browse, &chords("g")), Resolution::Pending));
+        assert_eq!(action(&keymap, Context::Browse, "gg"), Some(Action::Top));
+        assert!(matches!(keymap.resolve(Context::Browse, &chords("z")), Resolution::Unbound));
+        // The same key means different things in different contexts
+        assert_eq!(action(&keymap, Context::Browse, "o"), Some(Action::View));
+        assert_eq!(action(&keymap, Context::Conflict, "o"), Some(Action::ConflictOverwrite));
+        assert_eq!(action(&keymap, Context::Conflict, "<Esc>"), Some(Action::Cancel));
+        assert_eq!(action(&keymap, Context::Browse, "<Esc>"), None);
+    }
+
+    #[test]
+    fn overrides_replace_the_defaults() {
+        let overrides = BTreeMap::from([(Action::Top, vec!["<C-x>".to_string()]), (Action::Copy, vec!["f6".to_string()])]);
+        let keymap = Keymap::new(&overrides).unwrap();
+        assert_eq!(action(&keymap, Context::Browse, "<C-x>"), Some(Action::Top));
+        assert!(matches!(keymap.resolve(Context::Browse, &chords("g")), Resolution::Unbound));
+        assert_eq!(keymap.describe(Action::Copy), "F6");
+        assert_eq!(keymap.to_table()[&Action::Copy], ["<F6>"]);
+    }
+
+    #[test]
+    fn prefix_clashes_are_refused_at_load() {
+        let error = |text: &str| Config::parse(text).unwrap_err().to_string();
+        // `g` would hide `gg`
+        assert_eq!(error("[keys]\nbottom = [\"g\"]\n"), "`keys.bottom`: `g` conflicts with `gg` bound to `top`");
+        assert_eq!(error("[keys]\nquit = [\"jj\"]\n"), "`keys.quit`: `jj` conflicts with `j` bound to `down`");
+        // A prompt key is live in the palette too
+        assert_eq!(error("[keys]\ncomplete = [\"<Enter>\"]\n"), "`keys.complete`: `<Enter>` conflicts with `<Enter>` bound to `confirm`");
+        assert!(error("[keys]\nquit = [\"<Nope>\"]\n").contains("cannot parse key `<Nope>`"));
+        // Contexts that never meet may share keys
+        Config::parse("[keys]\nconflict_skip = [\"q\"]\n").unwrap();
+    }
+
+    #[test]
+    fn every_action_is_named_as_in_the_config() {
+        for &action in Action::ALL {
+            let config = Config::parse(&format!("[keys]\n{} = []\n", action.name())).unwrap();
+            assert!(config.keys.contains_key(&action), "{}", action.name());
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+// --- Upload ledger ---
+//
+// One JSON object per line, appended after every successful upload, so
+// several watchers (or a daemon and the TUI) never rewrite each other's
+// entries.
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UploadRecord {
+    pub sha256: String,
+    pub size: u64,
+    pub local_path: PathBuf,
+    /// Tablet folder as `/A/B`, empty for the root.
+    pub tablet_folder: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+pub struct Ledger {
+    path: PathBuf,
+    records: Vec<UploadRecord>,
+}
+
+/// `$XDG_STATE_HOME/remarkable`, falling back to `~/.local/state`.
+pub fn state_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("state"),
+    };
+    Some(base.join("remarkable"))
+}
+
+impl Ledger {
+    /// Opens the ledger in the state directory; a missing file is empty.
+    pub fn open_default() -> Result<Self> {
+        let dir = state_dir().context("Cannot find a state directory; set HOME or XDG_STATE_HOME")?;
+        Self::open(dir.join("uploads.jsonl"))
+    }
+
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let records = match std::fs::read_to_string(&path) {
+            // A line cut short by a crash is skipped rather than fatal
+            Ok(text) => text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("Could not read {}", path.display())),
+        };
+        Ok(Self { path, records })
+    }
+
+    /// Whether this content was already uploaded into `tablet_folder`.
+    pub fn contains(&self, sha256: &str, tablet_folder: &str) -> bool {
+        self.records.iter().any(|r| r.sha256 == sha256 && r.tablet_folder == tablet_folder)
+    }
+
+    pub fn record(&mut self, record: UploadRecord) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)
+            .with_context(|| format!("Could not write {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        self.records.push(record);
+        Ok(())
+    }
+}
+
+/// Hex SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(sha256: &str, tablet_folder: &str) -> UploadRecord {
+        UploadRecord {
+            sha256: sha256.into(),
+            size: 3,
+            local_path: PathBuf::from("/scans/a.pdf"),
+            tablet_folder: tablet_folder.into(),
+            uploaded_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn records_survive_a_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("uploads.jsonl");
+        let mut ledger = Ledger::open(path.clone()).unwrap();
+        assert!(!ledger.contains("abc", "/Scans"));
+        ledger.record(record("abc", "/Scans")).unwrap();
+        ledger.record(record("def", "")).unwrap();
+
+        let ledger = Ledger::open(path).unwrap();
+        assert!(ledger.contains("abc", "/Scans"));
+        assert!(ledger.contains("def", ""));
+        assert!(!ledger.contains("abc", ""));
+    }
+
+    #[test]
+    fn a_torn_line_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("uploads.jsonl");
+        let mut ledger = Ledger::open(path.clone()).unwrap();
+        ledger.record(record("abc", "/Scans")).unwrap();
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"sha256\": \"de").unwrap();
+
+        let ledger = Ledger::open(path).unwrap();
+        assert!(ledger.contains("abc", "/Scans"));
+        assert_eq!(ledger.records.len(), 1);
+    }
+
+    #[test]
+    fn hashes_file_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(sha256_file(&path).unwrap(), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+}
@@ -0,0 +1,720 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+
+// --- reMarkable lines files ---
+//
+// Every notebook page, and every annotated page of a PDF or EPUB, keeps its
+// strokes in `<id>/<page>.rm`. Firmware 2 writes version 5 (and older ones
+// version 3): a flat list of layers, each a list of strokes. Firmware 3
+// writes version 6: a sequence of tagged blocks describing a scene tree, of
+// which the layers, strokes and text highlights are read here and typed
+// text is skipped.
+
+/// Size of the tablet's screen in pixels, the unit of every coordinate.
+pub const WIDTH: f32 = 1404.0;
+pub const HEIGHT: f32 = 1872.0;
+
+const HEADER_V3: &[u8] = b"reMarkable .lines file, version=3";
+const HEADER_V5: &[u8] = b"reMarkable .lines file, version=5";
+const HEADER_V6: &[u8] = b"reMarkable .lines file, version=6";
+const HEADER_LEN: usize = 43;
+
+#[derive(Default)]
+pub struct Page {
+    pub layers: Vec<Layer>,
+    /// Text marked with the highlighter, which version 6 snaps to the text
+    /// of the PDF or EPUB underneath.
+    pub highlights: Vec<Highlight>,
+    /// Version 6 measures x from the middle of the page, not its left edge.
+    pub centered: bool,
+}
+
+pub struct Layer {
+    pub name: Option<String>,
+    pub visible: bool,
+    pub strokes: Vec<Stroke>,
+}
+
+pub struct Stroke {
+    pub pen: Pen,
+    pub color: Rgb,
+    /// The thin, medium or thick setting the stroke was drawn with.
+    pub thickness: f32,
+    pub points: Vec<Point>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+    /// Width of the line at this point, in pixels.
+    pub width: f32,
+    /// 0 to 1.
+    pub pressure: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Highlight {
+    pub text: String,
+    pub color: Rgb,
+    /// Offset of the text in the page's text, where the tablet recorded it.
+    pub start: Option<u32>,
+    /// Top left corner of the first marked line.
+    pub top: f32,
+    pub left: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pen {
+    Paintbrush,
+    Pencil,
+    Ballpoint,
+    Marker,
+    Fineliner,
+    Highlighter,
+    Eraser,
+    MechanicalPencil,
+    /// The selection eraser; its strokes only outline what was erased.
+    EraseArea,
+    Calligraphy,
+    Shader,
+    Unknown(u32),
+}
+
+impl Pen {
+    fn from_id(id: u32) -> Self {
+        match id {
+            0 | 12 => Self::Paintbrush,
+            1 | 14 => Self::Pencil,
+            2 | 15 => Self::Ballpoint,
+            3 | 16 => Self::Marker,
+            4 | 17 => Self::Fineliner,
+            5 | 18 => Self::Highlighter,
+            6 => Self::Eraser,
+            7 | 13 => Self::MechanicalPencil,
+            8 => Self::EraseArea,
+            21 => Self::Calligraphy,
+            23 => Self::Shader,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+/// The colour a stroke or highlight was drawn in. Version 6 highlighters
+/// may carry their own colour instead.
+pub fn color(id: u32, argb: Option<u32>) -> Rgb {
+    if let Some(argb) = argb {
+        return Rgb((argb >> 16) as u8, (argb >> 8) as u8, argb as u8);
+    }
+    match id {
+        1 => Rgb(144, 144, 144),
+        2 => Rgb(255, 255, 255),
+        3 => Rgb(251, 247, 25),
+        4 => Rgb(0, 255, 0),
+        5 => Rgb(255, 192, 203),
+        6 => Rgb(78, 105, 201),
+        7 => Rgb(179, 62, 57),
+        8 => Rgb(125, 125, 125),
+        9 => Rgb(251, 247, 25),
+        10 => Rgb(161, 216, 125),
+        11 => Rgb(139, 208, 229),
+        12 => Rgb(183, 130, 205),
+        13 => Rgb(247, 232, 81),
+        _ => Rgb(0, 0, 0),
+    }
+}
+
+impl Page {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let header = bytes.get(..HEADER_LEN).ok_or_else(|| anyhow!("Not a reMarkable lines file"))?;
+        let body = Reader::new(&bytes[HEADER_LEN..]);
+        if header.starts_with(HEADER_V6) {
+            parse_v6(body)
+        } else if header.starts_with(HEADER_V5) {
+            parse_v3(body, true)
+        } else if header.starts_with(HEADER_V3) {
+            parse_v3(body, false)
+        } else {
+            Err(anyhow!("Not a reMarkable lines file, or a version this tool cannot read"))
+        }
+    }
+
+    /// Whether nothing visible was drawn.
+    pub fn is_blank(&self) -> bool {
+        self.layers.iter().all(|l| !l.visible || l.strokes.is_empty())
+    }
+
+    /// Lowest point drawn, to size pages that were scrolled past the screen.
+    pub fn bottom(&self) -> f32 {
+        self.layers
+            .iter()
+            .flat_map(|l| &l.strokes)
+            .flat_map(|s| &s.points)
+            .map(|p| p.y + p.width)
+            .fold(0.0, f32::max)
+    }
+}
+
+// --- Versions 3 and 5 ---
+
+fn parse_v3(mut r: Reader, v5: bool) -> Result<Page> {
+    let mut layers = Vec::new();
+    for _ in 0..r.u32()? {
+        let mut strokes = Vec::new();
+        for _ in 0..r.u32()? {
+            let pen = r.u32()?;
+            let color_id = r.u32()?;
+            r.u32()?;
+            let thickness = r.f32()?;
+            if v5 {
+                r.u32()?;
+            }
+            let mut points = Vec::new();
+            for _ in 0..r.u32()? {
+                let (x, y, _speed, _tilt, width, pressure) = (r.f32()?, r.f32()?, r.f32()?, r.f32()?, r.f32()?, r.f32()?);
+                points.push(Point { x, y, width, pressure });
+            }
+            strokes.push(Stroke { pen: Pen::from_id(pen), color: color(color_id, None), thickness, points });
+        }
+        layers.push(Layer { name: None, visible: true, strokes });
+    }
+    Ok(Page { layers, highlights: Vec::new(), centered: false })
+}
+
+// --- Version 6 ---
+
+/// A scene item's ID: the author and a per-author counter.
+type CrdtId = (u8, u64);
+
+const ROOT: CrdtId = (0, 1);
+
+const TREE_NODE: u8 = 0x01;
+const NODE_INFO: u8 = 0x02;
+const GLYPH_ITEM: u8 = 0x03;
+const GROUP_ITEM: u8 = 0x04;
+const LINE_ITEM: u8 = 0x05;
+const TOMBSTONE: u8 = 0x08;
+
+// Value kinds in the low nibble of a tag
+const BYTE1: u8 = 0x1;
+const BYTE4: u8 = 0x4;
+const BYTE8: u8 = 0x8;
+const LENGTH4: u8 = 0xC;
+const ID: u8 = 0xF;
+
+#[derive(Default)]
+struct Scene {
+    /// Which group each group or layer sits in.
+    parents: HashMap<CrdtId, CrdtId>,
+    labels: HashMap<CrdtId, String>,
+    hidden: HashSet<CrdtId>,
+    /// Layers (groups directly under the root) in the order they appear.
+    layers: Vec<CrdtId>,
+    /// Strokes with their item ID and the group they were drawn in.
+    strokes: Vec<(CrdtId, CrdtId, Stroke)>,
+    highlights: Vec<(CrdtId, Highlight)>,
+    deleted: HashSet<CrdtId>,
+}
+
+fn parse_v6(mut r: Reader) -> Result<Page> {
+    let mut scene = Scene::default();
+    while !r.is_empty() {
+        let len = r.u32()? as usize;
+        let (_, _min_version, version, kind) = (r.u8()?, r.u8()?, r.u8()?, r.u8()?);
+        let block = Reader::new(r.take(len).context("Truncated block")?);
+        // A block this tool misreads is left out rather than failing the page
+        let _ = read_block(&mut scene, block, kind, version);
+    }
+
+    let layer_of = |mut group: CrdtId| {
+        let mut hops = 0;
+        while let Some(&parent) = scene.parents.get(&group) {
+            if parent == ROOT || hops > 64 {
+                break;
+            }
+            group = parent;
+            hops += 1;
+        }
+        group
+    };
+    let mut order = scene.layers.clone();
+    let mut by_layer: HashMap<CrdtId, Vec<Stroke>> = HashMap::new();
+    for (id, group, stroke) in scene.strokes {
+        if scene.deleted.contains(&id) {
+            continue;
+        }
+        let layer = layer_of(group);
+        if !order.contains(&layer) {
+            order.push(layer);
+        }
+        by_layer.entry(layer).or_default().push(stroke);
+    }
+    let layers = order
+        .into_iter()
+        .map(|id| Layer {
+            name: scene.labels.get(&id).cloned(),
+            visible: !scene.hidden.contains(&id),
+            strokes: by_layer.remove(&id).unwrap_or_default(),
+        })
+        .collect();
+    let highlights = scene.highlights.into_iter().filter(|(id, _)| !scene.deleted.contains(id)).map(|(_, h)| h).collect();
+    Ok(Page { layers, highlights, centered: true })
+}
+
+fn read_block(scene: &mut Scene, mut r: Reader, kind: u8, version: u8) -> Result<()> {
+    match kind {
+        TREE_NODE => {
+            let node = r.id(1)?;
+            let _ = r.id(2)?;
+            let _is_update = r.bool(3)?;
+            let mut parent = r.subblock(4)?;
+            scene.parents.insert(node, parent.id(1)?);
+        }
+        NODE_INFO => {
+            let node = r.id(1)?;
+            let mut label = r.subblock(2)?;
+            let _ = label.id(1)?;
+            scene.labels.insert(node, label.string(2)?);
+            let mut visible = r.subblock(3)?;
+            let _ = visible.id(1)?;
+            if !visible.bool(2)? {
+                scene.hidden.insert(node);
+            }
+        }
+        GROUP_ITEM => {
+            let parent = r.id(1)?;
+            let item = r.id(2)?;
+            let (_left, _right) = (r.id(3)?, r.id(4)?);
+            let deleted = r.int(5)? > 0;
+            if deleted || !r.has_tag(6, LENGTH4) {
+                scene.deleted.insert(item);
+                return Ok(());
+            }
+            let mut value = r.subblock(6)?;
+            let _item_type = value.u8()?;
+            let node = value.id(2)?;
+            if parent == ROOT && !scene.layers.contains(&node) {
+                scene.layers.push(node);
+            }
+            scene.parents.entry(node).or_insert(parent);
+        }
+        LINE_ITEM => {
+            let parent = r.id(1)?;
+            let item = r.id(2)?;
+            let (_left, _right) = (r.id(3)?, r.id(4)?);
+            let deleted = r.int(5)? > 0;
+            if deleted || !r.has_tag(6, LENGTH4) {
+                scene.deleted.insert(item);
+                return Ok(());
+            }
+            let mut value = r.subblock(6)?;
+            let _item_type = value.u8()?;
+            scene.strokes.push((item, parent, read_line(&mut value, version)?));
+        }
+        GLYPH_ITEM => {
+            let _parent = r.id(1)?;
+            let item = r.id(2)?;
+            let (_left, _right) = (r.id(3)?, r.id(4)?);
+            let deleted = r.int(5)? > 0;
+            if deleted || !r.has_tag(6, LENGTH4) {
+                scene.deleted.insert(item);
+                return Ok(());
+            }
+            let mut value = r.subblock(6)?;
+            let _item_type = value.u8()?;
+            scene.highlights.push((item, read_glyph(&mut value)?));
+        }
+        TOMBSTONE => {
+            let _parent = r.id(1)?;
+            scene.deleted.insert(r.id(2)?);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn read_line(r: &mut Reader, version: u8) -> Result<Stroke> {
+    let pen = r.int(1)?;
+    let color_id = r.int(2)?;
+    let thickness = r.double(3)? as f32;
+    let _starting_length = r.float(4)?;
+    let mut data = r.subblock(5)?;
+    let mut points = Vec::new();
+    while !data.is_empty() {
+        let point = if version >= 2 {
+            let (x, y) = (data.f32()?, data.f32()?);
+            let (_speed, width, _direction, pressure) = (data.u16()?, data.u16()?, data.u8()?, data.u8()?);
+            Point { x, y, width: width as f32 / 4.0, pressure: pressure as f32 / 255.0 }
+        } else {
+            let (x, y, _speed, _direction, width, pressure) =
+                (data.f32()?, data.f32()?, data.f32()?, data.f32()?, data.f32()?, data.f32()?);
+            Point { x, y, width, pressure }
+        };
+        points.push(point);
+    }
+    let _timestamp = r.id(6)?;
+    if r.has_tag(7, ID) {
+        let _move_id = r.id(7)?;
+    }
+    let argb = if r.has_tag(8, BYTE4) { Some(r.int(8)?) } else { None };
+    Ok(Stroke { pen: Pen::from_id(pen), color: color(color_id, argb), thickness, points })
+}
+
+fn read_glyph(r: &mut Reader) -> Result<Highlight> {
+    let start = if r.has_tag(2, BYTE4) { Some(r.int(2)?) } else { None };
+    let _length = r.int(3)?;
+    let color_id = r.int(4)?;
+    let text = r.string(5)?;
+    let mut rects = r.subblock(6)?;
+    let (mut left, mut top) = (0.0, 0.0);
+    // Each marked line is a rectangle of x, y, width and height
+    if rects.varuint()? > 0 {
+        (left, top) = (rects.f64()? as f32, rects.f64()? as f32);
+    }
+    Ok(Highlight { text, color: color(color_id, None), start, top, left })
+}
+
+/// Little-endian reads over a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.bytes.len() {
+            return Err(anyhow!("Unexpected end of lines file"));
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn varuint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow!("Malformed number in lines file"))
+    }
+
+    /// Whether the next value is field `index` of kind `kind`.
+    fn has_tag(&self, index: u64, kind: u8) -> bool {
+        let mut peek = Reader::new(self.bytes);
+        peek.varuint().is_ok_and(|tag| tag == (index << 4 | kind as u64))
+    }
+
+    fn tag(&mut self, index: u64, kind: u8) -> Result<()> {
+        let tag = self.varuint()?;
+        if tag != (index << 4 | kind as u64) {
+            return Err(anyhow!("Expected field {} in lines file, found tag {:#x}", index, tag));
+        }
+        Ok(())
+    }
+
+    fn id(&mut self, index: u64) -> Result<CrdtId> {
+        self.tag(index, ID)?;
+        Ok((self.u8()?, self.varuint()?))
+    }
+
+    fn bool(&mut self, index: u64) -> Result<bool> {
+        self.tag(index, BYTE1)?;
+        Ok(self.u8()? != 0)
+    }
+
+    fn int(&mut self, index: u64) -> Result<u32> {
+        self.tag(index, BYTE4)?;
+        self.u32()
+    }
+
+    fn float(&mut self, index: u64) -> Result<f32> {
+        self.tag(index, BYTE4)?;
+        self.f32()
+    }
+
+    fn double(&mut self, index: u64) -> Result<f64> {
+        self.tag(index, BYTE8)?;
+        self.f64()
+    }
+
+    fn subblock(&mut self, index: u64) -> Result<Reader<'a>> {
+        self.tag(index, LENGTH4)?;
+        let len = self.u32()? as usize;
+        Ok(Reader::new(self.take(len)?))
+    }
+
+    fn string(&mut self, index: u64) -> Result<String> {
+        let mut block = self.subblock(index)?;
+        let len = block.varuint()? as usize;
+        let _is_ascii = block.u8()?;
+        Ok(String::from_utf8_lossy(block.take(len)?).into_owned())
+    }
+}
+
+/// Small hand-built pages, shared with the rendering tests.
+#[cfg(test)]
+pub mod fixtures {
+    fn header(version: u8) -> Vec<u8> {
+        let mut header = format!("reMarkable .lines file, version={}", version).into_bytes();
+        header.resize(super::HEADER_LEN, b' ');
+        header
+    }
+
+    fn push_u32s(out: &mut Vec<u8>, values: &[u32]) {
+        for v in values {
+            out.extend(v.to_le_bytes());
+        }
+    }
+
+    /// Pen, colour, thickness and points.
+    type Fixture<'a> = (u32, u32, f32, &'a [(f32, f32)]);
+
+    /// Version 3 or 5: a ballpoint and a highlighter stroke in one layer,
+    /// a fineliner in a second.
+    pub fn v3(v5: bool) -> Vec<u8> {
+        let mut out = header(if v5 { 5 } else { 3 });
+        let strokes: [&[Fixture]; 2] = [
+            &[(15, 0, 2.0, &[(100.0, 300.0), (110.0, 310.0), (120.0, 305.0)]), (18, 3, 2.0, &[(100.0, 600.0), (500.0, 600.0)])],
+            &[(17, 6, 3.0, &[(200.0, 800.0), (1200.0, 800.0)])],
+        ];
+        push_u32s(&mut out, &[strokes.len() as u32]);
+        for layer in strokes {
+            push_u32s(&mut out, &[layer.len() as u32]);
+            for &(pen, color, thickness, points) in layer {
+                push_u32s(&mut out, &[pen, color, 0]);
+                out.extend(thickness.to_le_bytes());
+                if v5 {
+                    push_u32s(&mut out, &[0]);
+                }
+                push_u32s(&mut out, &[points.len() as u32]);
+                for &(x, y) in points {
+                    for value in [x, y, 0.0, 0.0, 4.0, 0.5] {
+                        out.extend(value.to_le_bytes());
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn varuint(mut n: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                out.push(byte);
+                return out;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    fn tag(index: u64, kind: u8) -> Vec<u8> {
+        varuint(index << 4 | kind as u64)
+    }
+
+    fn id(index: u64, (author, n): (u8, u64)) -> Vec<u8> {
+        [tag(index, super::ID), vec![author], varuint(n)].concat()
+    }
+
+    fn int(index: u64, value: u32) -> Vec<u8> {
+        [tag(index, super::BYTE4), value.to_le_bytes().to_vec()].concat()
+    }
+
+    fn boolean(index: u64, value: bool) -> Vec<u8> {
+        [tag(index, super::BYTE1), vec![value as u8]].concat()
+    }
+
+    fn sub(index: u64, data: &[u8]) -> Vec<u8> {
+        [tag(index, super::LENGTH4), (data.len() as u32).to_le_bytes().to_vec(), data.to_vec()].concat()
+    }
+
+    fn string(index: u64, text: &str) -> Vec<u8> {
+        sub(index, &[varuint(text.len() as u64), vec![1], text.as_bytes().to_vec()].concat())
+    }
+
+    fn block(kind: u8, version: u8, data: &[u8]) -> Vec<u8> {
+        [(data.len() as u32).to_le_bytes().to_vec(), vec![0, 1, version, kind], data.to_vec()].concat()
+    }
+
+    /// The start of an item block: parent, item, neighbours, deleted flag.
+    fn item(parent: (u8, u64), item: (u8, u64), deleted: bool) -> Vec<u8> {
+        [id(1, parent), id(2, item), id(3, (0, 0)), id(4, (0, 0)), int(5, deleted as u32)].concat()
+    }
+
+    fn line(parent: (u8, u64), stroke: (u8, u64), pen: u32, color: u32, points: &[(f32, f32)], argb: Option<u32>) -> Vec<u8> {
+        let mut data = Vec::new();
+        for &(x, y) in points {
+            data.extend(x.to_le_bytes());
+            data.extend(y.to_le_bytes());
+            data.extend(4u16.to_le_bytes());
+            data.extend(12u16.to_le_bytes()); // Width 3
+            data.extend([0, 255]);
+        }
+        let mut value = [vec![3], int(1, pen), int(2, color), tag(3, super::BYTE8), 2.0f64.to_le_bytes().to_vec()].concat();
+        value.extend([int(4, 0), sub(5, &data), id(6, (0, 1))].concat());
+        if let Some(argb) = argb {
+            value.extend(int(8, argb));
+        }
+        block(super::LINE_ITEM, 2, &[item(parent, stroke, false), sub(6, &value)].concat())
+    }
+
+    fn glyph(highlight: (u8, u64), start: u32, text: &str) -> Vec<u8> {
+        let mut rects = varuint(1);
+        for value in [-300.0f64, 400.0, 300.0, 40.0] {
+            rects.extend(value.to_le_bytes());
+        }
+        let value = [vec![1], int(2, start), int(3, text.len() as u32), int(4, 3), string(5, text), sub(6, &rects)].concat();
+        block(super::GLYPH_ITEM, 1, &[item((0, 11), highlight, false), sub(6, &value)].concat())
+    }
+
+    /// Version 6: layer "Layer 1" with a ballpoint stroke, a green
+    /// highlighter and a highlight; a hidden layer with one stroke; a
+    /// stroke deleted in place and a highlight removed by a tombstone.
+    pub fn v6() -> Vec<u8> {
+        let mut out = header(6);
+        out.extend(block(0x09, 1, &[0])); // Author IDs, not read
+        for (node, label, visible) in [((0, 11), "Layer 1", true), ((0, 12), "Hidden", false)] {
+            out.extend(block(super::TREE_NODE, 1, &[id(1, node), id(2, (0, 0)), boolean(3, true), sub(4, &id(1, super::ROOT))].concat()));
+            let label = sub(2, &[id(1, (0, 0)), string(2, label)].concat());
+            let visible = sub(3, &[id(1, (0, 0)), boolean(2, visible)].concat());
+            out.extend(block(super::NODE_INFO, 1, &[id(1, node), label, visible].concat()));
+            let group = sub(6, &[vec![2], id(2, node)].concat());
+            out.extend(block(super::GROUP_ITEM, 1, &[item(super::ROOT, (0, node.1 + 100), false), group].concat()));
+        }
+        out.extend(line((0, 11), (1, 20), 15, 0, &[(-500.0, 300.0), (-400.0, 350.0)], None));
+        out.extend(line((0, 11), (1, 21), 18, 9, &[(-600.0, 700.0), (0.0, 700.0)], Some(0xff00ff00)));
+        out.extend(block(super::LINE_ITEM, 2, &item((0, 11), (1, 22), true)));
+        out.extend(line((0, 12), (1, 23), 17, 6, &[(-600.0, 1000.0), (600.0, 1000.0)], None));
+        out.extend(glyph((1, 30), 20, "A highlighted passage"));
+        out.extend(glyph((1, 31), 200, "erased highlight"));
+        out.extend(block(super::TOMBSTONE, 1, &[id(1, (0, 11)), id(2, (1, 31))].concat()));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pens(layer: &Layer) -> Vec<Pen> {
+        layer.strokes.iter().map(|s| s.pen).collect()
+    }
+
+    #[test]
+    fn reads_version_5() {
+        let page = Page::parse(&fixtures::v3(true)).unwrap();
+        assert!(!page.centered);
+        assert_eq!(page.layers.len(), 2);
+        assert!(page.layers.iter().all(|l| l.visible && l.name.is_none()));
+        assert_eq!(pens(&page.layers[0]), [Pen::Ballpoint, Pen::Highlighter]);
+        assert_eq!(pens(&page.layers[1]), [Pen::Fineliner]);
+        let ballpoint = &page.layers[0].strokes[0];
+        assert_eq!(ballpoint.color, Rgb(0, 0, 0));
+        assert_eq!(ballpoint.thickness, 2.0);
+        assert_eq!(ballpoint.points.len(), 3);
+        assert_eq!((ballpoint.points[1].x, ballpoint.points[1].y, ballpoint.points[1].width), (110.0, 310.0, 4.0));
+        assert_eq!(page.layers[0].strokes[1].color, Rgb(251, 247, 25));
+        assert_eq!(page.layers[1].strokes[0].color, Rgb(78, 105, 201));
+        assert!(page.highlights.is_empty());
+        assert!(!page.is_blank());
+    }
+
+    #[test]
+    fn reads_version_3() {
+        let page = Page::parse(&fixtures::v3(false)).unwrap();
+        assert_eq!(pens(&page.layers[0]), [Pen::Ballpoint, Pen::Highlighter]);
+        assert_eq!(page.layers[1].strokes[0].points.len(), 2);
+    }
+
+    #[test]
+    fn reads_version_6() {
+        let page = Page::parse(&fixtures::v6()).unwrap();
+        assert!(page.centered);
+        let names: Vec<Option<&str>> = page.layers.iter().map(|l| l.name.as_deref()).collect();
+        assert_eq!(names, [Some("Layer 1"), Some("Hidden")]);
+        assert!(page.layers[0].visible);
+        assert!(!page.layers[1].visible);
+
+        // The stroke deleted in place is left out
+        assert_eq!(pens(&page.layers[0]), [Pen::Ballpoint, Pen::Highlighter]);
+        let ballpoint = &page.layers[0].strokes[0];
+        assert_eq!(ballpoint.thickness, 2.0);
+        assert_eq!((ballpoint.points[0].x, ballpoint.points[0].width, ballpoint.points[0].pressure), (-500.0, 3.0, 1.0));
+        // Its own colour wins over the palette
+        assert_eq!(page.layers[0].strokes[1].color, Rgb(0, 255, 0));
+        assert_eq!(pens(&page.layers[1]), [Pen::Fineliner]);
+
+        // The tombstoned highlight is gone
+        assert_eq!(page.highlights.len(), 1);
+        let highlight = &page.highlights[0];
+        assert_eq!(highlight.text, "A highlighted passage");
+        assert_eq!(highlight.start, Some(20));
+        assert_eq!((highlight.left, highlight.top), (-300.0, 400.0));
+        assert_eq!(highlight.color, Rgb(251, 247, 25));
+    }
+
+    #[test]
+    fn hidden_layers_leave_a_page_blank() {
+        let mut page = Page::parse(&fixtures::v6()).unwrap();
+        page.layers.remove(0);
+        assert!(page.is_blank());
+    }
+
+    #[test]
+    fn truncated_files_fail_without_panicking() {
+        assert!(Page::parse(b"").is_err());
+        assert!(Page::parse(b"reMarkable .lines file, version=9          ").is_err());
+        let v5 = fixtures::v3(true);
+        for len in 0..v5.len() {
+            assert!(Page::parse(&v5[..len]).is_err(), "version 5 cut at {} bytes", len);
+        }
+        // Version 6 is a list of blocks, so a cut between two reads as a
+        // shorter page; a cut inside one fails
+        let v6 = fixtures::v6();
+        for len in 0..v6.len() {
+            let _ = Page::parse(&v6[..len]);
+        }
+        assert!(Page::parse(&v6[..v6.len() - 3]).is_err());
+        assert!(Page::parse(&v6[..HEADER_LEN + 2]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::SortOrder;
+use ratatui::widgets::ListState;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// --- Local filesystem pane ---
+
+#[derive(Debug, Clone)]
+pub struct LocalEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub modified: Option<SystemTime>,
+}
+
+/// The left-hand browser of the dual-pane layout.
+pub struct LocalPane {
+    pub dir: PathBuf,
+    all_entries: Vec<LocalEntry>,
+    /// `all_entries` after filtering and sorting; what is drawn.
+    pub entries: Vec<LocalEntry>,
+    pub state: ListState,
+    pub marked: HashSet<PathBuf>,
+    pub filter: String,
+    pub sort: Option<SortOrder>,
+}
+
+impl LocalPane {
+    pub fn new(dir: PathBuf) -> Self {
+        let mut pane = Self {
+            dir,
+            all_entries: Vec::new(),
+            entries: Vec::new(),
+            state: ListState::default(),
+            marked: HashSet::new(),
+            filter: String::new(),
+            sort: Some(SortOrder::Type),
+        };
+        pane.reload();
+        pane
+    }
+
+    /// Re-reads the directory. Unreadable entries are skipped.
+    pub fn reload(&mut self) -> Option<String> {
+        let read = match std::fs::read_dir(&self.dir) {
+            Ok(read) => read,
+            Err(e) => {
+                self.all_entries.clear();
+                self.apply_view();
+                return Some(format!("Cannot read {}: {}", self.dir.display(), e));
+            }
+        };
+
+        self.all_entries = read
+            .flatten()
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some(LocalEntry {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    path: entry.path(),
+                    is_dir: metadata.is_dir(),
+                    modified: metadata.modified().ok(),
+                })
+            })
+            .collect();
+        self.marked.retain(|path| path.exists());
+        self.apply_view();
+        None
+    }
+
+    /// Rebuilds `entries` from the filter and sort order, keeping the
+    /// selected entry selected where possible.
+    pub fn apply_view(&mut self) {
+        let selected = self.selected().map(|e| e.path.clone());
+        let needle = self.filter.to_lowercase();
+
+        self.entries = self
+            .all_entries
+            .iter()
+            .filter(|e| needle.is_empty() || e.name.to_lowercase().contains(&needle))
+            .cloned()
+            .collect();
+        match self.sort {
+            Some(SortOrder::Name) => self.entries.sort_by_cached_key(|e| e.name.to_lowercase()),
+            Some(SortOrder::Modified) => self.entries.sort_by_key(|e| std::cmp::Reverse(e.modified)),
+            Some(SortOrder::Type) => self.entries.sort_by_cached_key(|e| (!e.is_dir, e.name.to_lowercase())),
+            None => {}
+        }
+
+        let index = selected.and_then(|path| self.entries.iter().position(|e| e.path == path));
+        self.state.select(index.or(if self.entries.is_empty() { None } else { Some(0) }));
+    }
+
+    pub fn selected(&self) -> Option<&LocalEntry> {
+        self.state.selected().and_then(|i| self.entries.get(i))
+    }
+
+    /// Marked entries, or the selected one if nothing is marked.
+    pub fn targets(&self) -> Vec<LocalEntry> {
+        if self.marked.is_empty() {
+            self.selected().cloned().into_iter().collect()
+        } else {
+            self.all_entries.iter().filter(|e| self.marked.contains(&e.path)).cloned().collect()
+        }
+    }
+
+    pub fn toggle_mark(&mut self) {
+        if let Some(path) = self.selected().map(|e| e.path.clone()) {
+            if !self.marked.remove(&path) {
+                self.marked.insert(path);
+            }
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.entries.is_empty() { return; }
+        let i = self.state.selected().map_or(0, |i| (i + 1) % self.entries.len());
+        self.state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.entries.is_empty() { return; }
+        let i = self.state.selected().map_or(0, |i| if i == 0 { self.entries.len() - 1 } else { i - 1 });
+        self.state.select(Some(i));
+    }
+
+    pub fn first(&mut self) {
+        if !self.entries.is_empty() {
+            self.state.select(Some(0));
+        }
+    }
+
+    pub fn last(&mut self) {
+        if !self.entries.is_empty() {
+            self.state.select(Some(self.entries.len() - 1));
+        }
+    }
+
+    fn change_dir(&mut self, dir: PathBuf) -> Option<String> {
+        self.dir = dir;
+        self.filter.clear();
+        self.marked.clear();
+        self.state.select(None);
+        self.reload()
+    }
+
+    pub fn enter(&mut self) -> Option<String> {
+        let dir = self.selected().filter(|e| e.is_dir).map(|e| e.path.clone())?;
+        self.change_dir(dir)
+    }
+
+    /// Goes to the parent directory, selecting the one we came from.
+    pub fn go_back(&mut self) -> Option<String> {
+        let Some(parent) = self.dir.parent().map(Path::to_path_buf) else {
+            return Some("Already at /.".into());
+        };
+        let came_from = self.dir.clone();
+        let err = self.change_dir(parent);
+        if let Some(i) = self.entries.iter().position(|e| e.path == came_from) {
+            self.state.select(Some(i));
+        }
+        err
+    }
+}
@@ -1,37 +1,67 @@
+mod backend;
+mod cli;
+mod commands;
+mod config;
+mod conflict;
+mod device;
+mod dryrun;
+mod duplicate;
+mod filename;
+mod hooks;
+mod integrity;
+mod keymap;
+mod ledger;
+mod lines;
+mod local;
+mod naming;
+mod notes;
+mod palette;
+mod render;
+mod sync;
+mod viewer;
+mod watch;
+
 use anyhow::Result;
+use cli::{Cli, Command};
+use chrono::{DateTime, Local, Utc};
+use config::Config;
+use conflict::{Choice, ConflictPrompt, Subject};
+use hooks::{HookConfig, HookEvent, HookRun, Transfer};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use futures::{future::BoxFuture, StreamExt};
+use keymap::{Action, Context, KeyChord, Keymap, Resolution};
+use local::LocalPane;
+use palette::{Command as PaletteCommand, CommandSpec, Palette};
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::Line,
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Row, Table, Wrap},
     Frame, Terminal,
 };
-use reqwest::{multipart, Client};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use std::{
+    collections::{HashSet, VecDeque},
     error::Error,
     io,
-    path::Path,
-    time::Duration,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 use tokio::sync::mpsc;
 
-fn sanitize_filename(name: &str, is_folder: bool) -> String {
-    let safe_name = name.replace(|c: char| !c.is_alphanumeric() && c != '.' && c != '-' && c != '_', "_");
-    if !is_folder && !safe_name.ends_with(".pdf") {
-        format!("{}.pdf", safe_name)
-    } else {
-        safe_name
-    }
-}
-
 // --- Data Structures ---
 
 #[derive(Debug, Deserialize, Clone)]
@@ -42,27 +72,200 @@ struct Item {
     visible_name: String,
     #[serde(rename = "Type")]
     item_type: String,
+    #[serde(rename = "ModifiedClient", default)]
+    modified: Option<String>,
+    #[serde(rename = "pageCount", default)]
+    page_count: Option<u32>,
+    #[serde(rename = "fileType", default)]
+    file_type: Option<String>,
+    #[serde(rename = "sizeInBytes", default)]
+    size: Option<serde_json::Value>, // A string on current firmware
+    #[serde(rename = "Parent", default)]
+    parent: String, // Empty at the top level
+    #[serde(rename = "Bookmarked", default)]
+    pinned: bool, // Starred as a favourite
+    #[serde(rename = "Tags", default, deserialize_with = "tag_names")]
+    tags: Vec<String>,
+    #[serde(skip)]
+    page_tags: Vec<String>, // Tags of single pages, each name once
+    #[serde(skip)]
+    origin: Option<String>, // Folder a trashed item came from, when known; empty for the top level
+}
+
+/// Tags as plain names or as `{"name": ...}` objects.
+fn tag_names<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Name(String),
+        Tag { name: String },
+    }
+    let raw = Vec::<Raw>::deserialize(deserializer)?;
+    Ok(raw.into_iter().map(|r| match r { Raw::Name(name) | Raw::Tag { name } => name }).collect())
 }
 
 impl Item {
     fn is_folder(&self) -> bool {
         self.item_type == "CollectionType"
     }
+
+    /// Whether the item or one of its pages carries `tag`, ignoring case.
+    fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.to_lowercase();
+        self.tags.iter().chain(&self.page_tags).any(|t| t.to_lowercase() == tag)
+    }
+
+    fn modified_at(&self) -> Option<DateTime<Utc>> {
+        let raw = self.modified.as_deref()?;
+        DateTime::parse_from_rfc3339(raw).ok().map(|t| t.with_timezone(&Utc))
+    }
+
+    /// Stored size of the document; its PDF export may differ.
+    fn size_bytes(&self) -> Option<u64> {
+        match self.size.as_ref()? {
+            serde_json::Value::String(s) => s.parse().ok(),
+            value => value.as_u64(),
+        }
+    }
+
+    /// Naming template values for this item inside the tablet folders `path`.
+    fn fields<'a>(&'a self, path: &'a [String]) -> naming::Fields<'a> {
+        naming::Fields {
+            path,
+            name: &self.visible_name,
+            guid: &self.id,
+            modified: self.modified_at(),
+            pages: self.page_count,
+            format: self.file_type.as_deref(),
+        }
+    }
+}
+
+/// Items from anywhere in the library, shown instead of one folder.
+#[derive(Clone, PartialEq, Eq)]
+enum Library {
+    Favourites,
+    /// Items with this tag, or with any tag for `None`.
+    Tagged(Option<String>),
+    /// What was moved to the tablet's trash.
+    Trash,
+}
+
+impl Library {
+    fn includes(&self, item: &Item) -> bool {
+        match self {
+            Library::Favourites => item.pinned,
+            Library::Tagged(Some(tag)) => item.has_tag(tag),
+            Library::Tagged(None) => !item.tags.is_empty() || !item.page_tags.is_empty(),
+            Library::Trash => item.parent == "trash",
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Library::Favourites => "Favourites".into(),
+            Library::Tagged(Some(tag)) => format!("Tagged \"{}\"", tag),
+            Library::Tagged(None) => "Tagged".into(),
+            Library::Trash => "Trash".into(),
+        }
+    }
 }
 
 enum InputMode {
     Normal,
     Uploading,
     Downloading,
+    Command,
+    Filtering,
+    Conflict,
+    SyncReview,
+    Renaming, // Editing the name of `editing[0]` in the list
+    Moving, // Browsing for a folder to drop `editing` into
+    ConfirmTrash,
+    MakingFolder,
+    Tagging, // Editing the tags of `editing`, comma-separated
+    ConfirmPurge,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Local,
+    Tablet,
+}
+
+/// Something on screen that reacts to a click, recorded while drawing.
+#[derive(Clone, Copy)]
+enum ClickTarget {
+    Crumb(usize), // Depth to jump back to; 0 is the root
+    Button(Action),
+    Suggestion(usize),
+}
+
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Full-screen panels over the list, closed by any key but movement.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Overlay {
+    Help,
+    Log,
+    Preview,
+    Device, // Templates or splash screens
+}
+
+/// A line of the activity log, with any output shown below it.
+struct LogEntry {
+    at: DateTime<Local>,
+    ok: bool,
+    summary: String,
+    detail: Vec<String>,
+}
+
+const LOG_LIMIT: usize = 200;
+
+/// Status while the tablet list is being fetched.
+const LOADING: &str = "Loading...";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Name,
+    Modified,
+    Type,
 }
 
 enum AppMessage {
     DocumentsFetched(Vec<Item>), // items
-    DownloadComplete(String, String), // name, path
+    LibraryFetched(Vec<Item>), // every item, for a library view
+    Navigated(Vec<(String, String)>), // (id, name) of each folder from root
+    DownloadComplete(String, String, usize), // name, path, files skipped
+    HookFinished(HookRun),
+    Watch(watch::WatchEvent),
+    ViewReady(PathBuf),
+    Conflict(ConflictPrompt),
+    Preview(dryrun::Preview),
+    SyncPlanned(Vec<sync::Plan>),
+    SyncDone(String, sync::Report), // pair name, outcome
     UploadComplete(String),
+    UploadSkipped(String, String), // path, why
+    Edited(String, Vec<String>), // what was done, errors
+    NotesExported(PathBuf, notes::Report), // notes directory, outcome
+    DeviceDone(device::Outcome, bool), // outcome, whether it is a listing to show
     Error(String),
 }
 
+/// The tags every one of `items` has, in the order of the first.
+fn common_tags(items: &[Item]) -> Vec<String> {
+    let Some((first, rest)) = items.split_first() else { return Vec::new() };
+    first.tags.iter().filter(|tag| rest.iter().all(|i| i.tags.contains(tag))).cloned().collect()
+}
+
+/// `"name"` for one item, `N items` for several.
+fn describe_items(items: &[Item]) -> String {
+    match items {
+        [one] => format!("\"{}\"", one.visible_name),
+        many => format!("{} items", many.len()),
+    }
+}
+
 fn expand_path(path: &str) -> String {
     if path == "~" {
         return std::env::var("HOME").unwrap_or_else(|_| "~".to_string());
@@ -76,28 +279,98 @@ fn expand_path(path: &str) -> String {
 }
 
 struct AppLogic {
-    items: Vec<Item>,
+    all_items: Vec<Item>, // Everything in the current folder
+    items: Vec<Item>, // all_items after filtering and sorting; what is drawn
     state: ListState,
+    marked: HashSet<String>, // IDs of marked tablet items
+    filter: String,
     current_guid: Option<String>,
     history: Vec<Option<String>>, // Stack of previous locations
+    crumbs: Vec<String>, // Folder names from root, parallel to history
+    sort: Option<SortOrder>, // None keeps the tablet's order
+    view: Option<Library>, // Shown instead of the current folder
+    library: Vec<Item>, // Every item, as last fetched for `view`
     input_mode: InputMode,
     input_buffer: String,
     status_msg: String,
-    client: Client,
+    dual_pane: bool,
+    focus: Pane,
+    local: LocalPane,
+    backend: Arc<dyn backend::Backend>,
+    profile: String, // Name of the profile `backend` came from
+    config: Config,
+    keymap: Keymap,
+    pending_keys: Vec<KeyChord>, // Chords typed so far of a multi-key binding
+    palette: Palette,
+    commands: Vec<CommandSpec>,
+    overlay: Option<Overlay>,
+    overlay_scroll: u16,
+    log: Vec<LogEntry>, // Transfers and hook runs, oldest first, capped at LOG_LIMIT
+    view_request: Option<PathBuf>, // Opened by run_app, which owns the terminal
+    list_area: Rect,
+    local_area: Rect,
+    click_targets: Vec<(Rect, ClickTarget)>,
+    last_click: Option<(Instant, Pane, usize)>, // For double-click detection on list rows
+    conflicts: VecDeque<ConflictPrompt>, // Questions from running downloads, oldest first
+    apply_to_all: bool,
+    sync_plans: Vec<sync::Plan>, // Waiting for review in SyncReview mode
+    editing: Vec<Item>, // Being renamed, moved or trashed
+    dry_run: bool, // Transfers only report what they would do
+    previews: Vec<dryrun::Preview>, // Shown in the preview overlay, oldest first
+    device: Option<device::Outcome>, // Last templates or splash listing, for its overlay
+    should_quit: bool,
     tx: mpsc::Sender<AppMessage>,
 }
 
 impl AppLogic {
-    fn new(tx: mpsc::Sender<AppMessage>) -> Self {
+    fn new(tx: mpsc::Sender<AppMessage>, config: Config, keymap: Keymap) -> Self {
+        let (profile, settings) = config.profile().expect("profile checked at startup");
+        let local_dir = match &config.downloads.directory {
+            Some(dir) => PathBuf::from(expand_path(dir)),
+            None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+        };
+
         Self {
+            all_items: Vec::new(),
             items: Vec::new(),
             state: ListState::default(),
+            marked: HashSet::new(),
+            filter: String::new(),
             current_guid: None,
             history: Vec::new(),
+            crumbs: Vec::new(),
+            sort: None,
+            view: None,
+            library: Vec::new(),
             input_mode: InputMode::Normal,
             input_buffer: String::new(),
             status_msg: "Ready.".into(),
-            client: Client::new(),
+            dual_pane: config.ui.dual_pane,
+            focus: Pane::Tablet,
+            local: LocalPane::new(local_dir),
+            backend: settings.open(),
+            profile,
+            config,
+            keymap,
+            pending_keys: Vec::new(),
+            palette: Palette::default(),
+            commands: palette::commands(),
+            overlay: None,
+            overlay_scroll: 0,
+            log: Vec::new(),
+            view_request: None,
+            list_area: Rect::default(),
+            local_area: Rect::default(),
+            click_targets: Vec::new(),
+            last_click: None,
+            conflicts: VecDeque::new(),
+            apply_to_all: false,
+            sync_plans: Vec::new(),
+            editing: Vec::new(),
+            dry_run: false,
+            previews: Vec::new(),
+            device: None,
+            should_quit: false,
             tx,
         }
     }
@@ -120,13 +393,173 @@ impl AppLogic {
         self.state.select(Some(i));
     }
 
+    fn first(&mut self) {
+        if !self.items.is_empty() {
+            self.state.select(Some(0));
+        }
+    }
+
+    fn last(&mut self) {
+        if !self.items.is_empty() {
+            self.state.select(Some(self.items.len() - 1));
+        }
+    }
+
+    fn local_focused(&self) -> bool {
+        self.dual_pane && self.focus == Pane::Local
+    }
+
+    /// Reports an error from a local pane operation, if there was one.
+    fn local_result(&mut self, err: Option<String>) {
+        if let Some(e) = err {
+            self.status_msg = e;
+        }
+    }
+
+    fn toggle_panes(&mut self) {
+        self.dual_pane = !self.dual_pane;
+        if self.dual_pane {
+            let err = self.local.reload();
+            self.local_result(err);
+        } else {
+            self.focus = Pane::Tablet;
+        }
+    }
+
+    fn switch_pane(&mut self) {
+        if !self.dual_pane {
+            self.status_msg = "Open the local pane first.".into();
+            return;
+        }
+        self.focus = match self.focus {
+            Pane::Local => Pane::Tablet,
+            Pane::Tablet => Pane::Local,
+        };
+    }
+
+    fn set_items(&mut self, items: Vec<Item>) {
+        self.all_items = items;
+        let ids: HashSet<&str> = self.all_items.iter().map(|i| i.id.as_str()).collect();
+        self.marked.retain(|id| ids.contains(id.as_str()));
+        self.apply_view();
+    }
+
+    fn toggle_mark(&mut self) {
+        if self.local_focused() {
+            self.local.toggle_mark();
+            self.local.next();
+        } else if let Some(id) = self.state.selected().and_then(|i| self.items.get(i)).map(|i| i.id.clone()) {
+            if !self.marked.remove(&id) {
+                self.marked.insert(id);
+            }
+            self.next();
+        }
+    }
+
+    /// Marked tablet items, or the selected one if nothing is marked.
+    fn tablet_targets(&self) -> Vec<Item> {
+        if self.marked.is_empty() {
+            self.state.selected().and_then(|i| self.items.get(i)).cloned().into_iter().collect()
+        } else {
+            self.all_items.iter().filter(|i| self.marked.contains(&i.id)).cloned().collect()
+        }
+    }
+
+    fn start_filter(&mut self) {
+        self.input_mode = InputMode::Filtering;
+        self.input_buffer = if self.local_focused() { self.local.filter.clone() } else { self.filter.clone() };
+        self.status_msg = "Filter:".into();
+    }
+
+    /// Applies the filter prompt to the focused pane as it is typed.
+    fn update_filter(&mut self) {
+        if self.local_focused() {
+            self.local.filter = self.input_buffer.clone();
+            self.local.apply_view();
+        } else {
+            self.filter = self.input_buffer.clone();
+            self.apply_view();
+        }
+    }
+
+    fn finish_filter(&mut self, keep: bool) {
+        if !keep {
+            self.input_buffer.clear();
+        }
+        self.update_filter();
+        self.input_mode = InputMode::Normal;
+        self.input_buffer.clear();
+        self.status_msg = if keep { "Filter applied.".into() } else { "Filter cleared.".into() };
+    }
+
+    /// Copies the focused pane's marked items to the other pane: local files
+    /// are uploaded into the current tablet folder, tablet items are
+    /// downloaded into the local pane's directory.
+    fn copy_across(&mut self) {
+        if !self.dual_pane {
+            self.status_msg = "Open the local pane to copy between panes.".into();
+            return;
+        }
+
+        match self.focus {
+            Pane::Local => {
+                let (dirs, files): (Vec<_>, Vec<_>) = self.local.targets().into_iter().partition(|e| e.is_dir);
+                if files.is_empty() {
+                    self.status_msg = "Nothing to upload (folders can't be uploaded).".into();
+                    return;
+                }
+                let paths = files.iter().map(|e| e.path.to_string_lossy().to_string()).collect();
+                if !self.dry_run {
+                    self.local.marked.clear();
+                }
+                self.spawn_upload(paths);
+                if !dirs.is_empty() {
+                    self.status_msg.push_str(&format!(" Skipped {} folder(s).", dirs.len()));
+                }
+            }
+            Pane::Tablet => {
+                let targets = self.tablet_targets();
+                if targets.is_empty() {
+                    self.status_msg = "Nothing selected to download.".into();
+                    return;
+                }
+                let dest = format!("{}{}", self.local.dir.display(), std::path::MAIN_SEPARATOR);
+                if !self.dry_run {
+                    self.marked.clear();
+                }
+                for item in targets {
+                    self.spawn_download(item, dest.clone());
+                }
+            }
+        }
+    }
+
     fn refresh(&mut self) {
-        self.status_msg = "Loading...".into();
-        let client = self.client.clone();
+        self.status_msg = LOADING.into();
+        let backend = self.backend.clone();
         let guid = self.current_guid.clone();
         let tx = self.tx.clone();
+        if let Some(view) = &self.view {
+            let trash = *view == Library::Trash;
+            tokio::spawn(async move {
+                // The trash view places items by the folders outside it
+                let fetched = match backend.library().await {
+                    Ok(mut items) if trash => backend.trash().await.map(|trashed| {
+                        items.extend(trashed);
+                        items
+                    }),
+                    fetched => fetched,
+                };
+                let msg = match fetched {
+                    Ok(items) => AppMessage::LibraryFetched(items),
+                    Err(e) => AppMessage::Error(format!("Error: {}", e)),
+                };
+                let _ = tx.send(msg).await;
+            });
+            return;
+        }
         tokio::spawn(async move {
-            match fetch_documents(&client, &guid).await {
+            match backend.list(&guid).await {
                 Ok(items) => {
                     let _ = tx.send(AppMessage::DocumentsFetched(items)).await;
                 }
@@ -140,8 +573,15 @@ impl AppLogic {
     fn enter(&mut self) {
         if let Some(i) = self.state.selected() {
             if let Some(item) = self.items.get(i) {
-                if item.is_folder() {
+                if item.is_folder() && self.view == Some(Library::Trash) {
+                    self.status_msg = "Restore the folder to open it.".into();
+                } else if item.is_folder() && self.view.is_some() {
+                    let mut trail = self.library_trail(item);
+                    trail.push((item.id.clone(), item.visible_name.clone()));
+                    self.navigate_to(trail);
+                } else if item.is_folder() {
                     self.history.push(self.current_guid.clone());
+                    self.crumbs.push(item.visible_name.clone());
                     self.current_guid = Some(item.id.clone());
                     self.state.select(None);
                     self.refresh();
@@ -151,7 +591,12 @@ impl AppLogic {
     }
 
     fn go_back(&mut self) {
-        if let Some(prev) = self.history.pop() {
+        if let Some(view) = self.view.take() {
+            self.state.select(None);
+            self.refresh();
+            self.status_msg = format!("Left {}.", view.title());
+        } else if let Some(prev) = self.history.pop() {
+            self.crumbs.pop();
             self.current_guid = prev;
             self.state.select(None);
             self.refresh();
@@ -160,11 +605,110 @@ impl AppLogic {
         }
     }
 
+    /// Resolves a slash-separated folder path (absolute, or relative to the
+    /// current folder) on the tablet and navigates there.
+    fn change_directory(&mut self, path: &str) {
+        let trail = if path.starts_with('/') { Vec::new() } else { self.trail() };
+
+        let backend = self.backend.clone();
+        let tx = self.tx.clone();
+        let path = path.to_string();
+        self.status_msg = format!("Opening {}...", path);
+
+        tokio::spawn(async move {
+            let msg = match resolve_trail(backend.as_ref(), trail, &path).await {
+                Ok(trail) => AppMessage::Navigated(trail),
+                Err(e) => AppMessage::Error(format!("cd failed: {}", e)),
+            };
+            let _ = tx.send(msg).await;
+        });
+    }
+
+    /// The `(id, name)` pairs of the folders from the root to the current one.
+    fn trail(&self) -> Vec<(String, String)> {
+        self.history
+            .iter()
+            .skip(1)
+            .chain(std::iter::once(&self.current_guid))
+            .flatten()
+            .cloned()
+            .zip(self.crumbs.iter().cloned())
+            .collect()
+    }
+
+    /// Jumps back up to an ancestor of the current folder.
+    fn go_to_depth(&mut self, depth: usize) {
+        if self.view.take().is_some() && depth >= self.crumbs.len() {
+            self.state.select(None);
+            self.refresh();
+            return;
+        }
+        if depth >= self.crumbs.len() {
+            return;
+        }
+        self.current_guid = self.history[depth].clone();
+        self.history.truncate(depth);
+        self.crumbs.truncate(depth);
+        self.state.select(None);
+        self.refresh();
+    }
+
+    fn navigate_to(&mut self, trail: Vec<(String, String)>) {
+        self.history = std::iter::once(None)
+            .chain(trail.iter().map(|(id, _)| Some(id.clone())))
+            .collect();
+        self.current_guid = self.history.pop().flatten();
+        self.crumbs = trail.into_iter().map(|(_, name)| name).collect();
+        self.view = None;
+        self.state.select(None);
+        self.refresh();
+    }
+
+    fn set_sort(&mut self, order: SortOrder) {
+        if self.local_focused() {
+            self.local.sort = Some(order);
+            self.local.apply_view();
+        } else {
+            self.sort = Some(order);
+            self.apply_view();
+        }
+        self.status_msg = match order {
+            SortOrder::Name => "Sorted by name.",
+            SortOrder::Modified => "Sorted by last modified.",
+            SortOrder::Type => "Sorted folders first.",
+        }
+        .into();
+    }
+
+    /// Rebuilds the visible list from the filter and sort order, keeping
+    /// the selected item selected.
+    fn apply_view(&mut self) {
+        let selected_id = self.state.selected().and_then(|i| self.items.get(i)).map(|i| i.id.clone());
+        let needle = self.filter.to_lowercase();
+        // `#name` matches tags instead of names
+        let matches = |item: &Item| match needle.strip_prefix('#') {
+            Some(tag) => item.tags.iter().chain(&item.page_tags).any(|t| t.to_lowercase().contains(tag)),
+            None => item.visible_name.to_lowercase().contains(&needle),
+        };
+        self.items = self.all_items.iter().filter(|i| matches(i)).cloned().collect();
+        match self.sort {
+            Some(SortOrder::Name) => self.items.sort_by_cached_key(|i| i.visible_name.to_lowercase()),
+            Some(SortOrder::Modified) => self.items.sort_by(|a, b| b.modified.cmp(&a.modified)),
+            Some(SortOrder::Type) => self.items.sort_by_cached_key(|i| (!i.is_folder(), i.visible_name.to_lowercase())),
+            None => {}
+        }
+        if let Some(id) = selected_id {
+            self.state.select(self.items.iter().position(|i| i.id == id));
+        } else if self.state.selected().is_some_and(|i| i >= self.items.len()) {
+            self.state.select(None);
+        }
+    }
+
     fn download(&mut self) {
         if let Some(i) = self.state.selected() {
             if let Some(item) = self.items.get(i) {
                 self.input_mode = InputMode::Downloading;
-                self.input_buffer.clear();
+                self.input_buffer = self.config.downloads.directory.clone().unwrap_or_default();
                 self.status_msg = format!("Enter download path for '{}':", item.visible_name);
             }
         }
@@ -186,28 +730,52 @@ impl AppLogic {
     
             if let Some(i) = self.state.selected() {
                 if let Some(item) = self.items.get(i) {
-                    let client = self.client.clone();
-                    let item_clone = item.clone(); // Clone the item
-                    let name = item.visible_name.clone();
-                    let tx = self.tx.clone();
-                    let dest_path = path_str.clone();
-    
                     self.input_mode = InputMode::Normal;
-                    self.status_msg = format!("Downloading {} to {}...", name, dest_path);
-                    
-                    tokio::spawn(async move {
-                        match download_selection(client, item_clone, dest_path).await {
-                            Ok(final_path) => {
-                                let _ = tx.send(AppMessage::DownloadComplete(name, final_path)).await;
-                            },
-                            Err(e) => {
-                                let _ = tx.send(AppMessage::Error(format!("Download failed: {}", e))).await;
-                            }
-                        }
-                    });
+                    self.spawn_download(item.clone(), path_str);
                 }
             }
         }
+
+    fn spawn_download(&mut self, item: Item, dest_path: String) {
+        let job = Arc::new(DownloadJob {
+            backend: self.backend.clone(),
+            tx: self.tx.clone(),
+            choice: Mutex::new(self.config.downloads.on_conflict.into()),
+            skipped: AtomicUsize::new(0),
+            ascii_only: self.config.downloads.ascii_filenames,
+            template: self.config.downloads.name_template.clone(),
+            flatten: self.config.downloads.flatten,
+            names: Mutex::new(filename::Siblings::default()),
+            hooks: hooks::matching(&self.config.hooks, HookEvent::Download),
+            tablet_dir: self.item_dir(&item),
+            preview: self.dry_run.then(|| Mutex::new(dryrun::Preview::new(dryrun::Direction::Download))),
+            checksums: self.config.downloads.checksums,
+        });
+        let name = item.visible_name.clone();
+        let tx = self.tx.clone();
+
+        self.status_msg = if self.dry_run {
+            format!("Working out what downloading {} would do...", name)
+        } else {
+            format!("Downloading {} to {}...", name, dest_path)
+        };
+
+        tokio::spawn(async move {
+            match download_selection(job.clone(), item, dest_path).await {
+                Ok(_) if job.preview.is_some() => {
+                    let preview = job.preview.as_ref().unwrap().lock().unwrap().clone();
+                    let _ = tx.send(AppMessage::Preview(preview)).await;
+                },
+                Ok(final_path) => {
+                    let skipped = job.skipped.load(Ordering::Relaxed);
+                    let _ = tx.send(AppMessage::DownloadComplete(name, final_path, skipped)).await;
+                },
+                Err(e) => {
+                    let _ = tx.send(AppMessage::Error(format!("Download failed: {}", e))).await;
+                }
+            }
+        });
+    }
     
         fn start_upload(&mut self) {
             self.input_mode = InputMode::Uploading;
@@ -236,191 +804,1753 @@ impl AppLogic {
         }
 
         self.input_mode = InputMode::Normal;
-        self.status_msg = format!("Uploading {}...", path_str);
-        
-        let client = self.client.clone();
+        self.spawn_upload(vec![path_str]);
+    }
+
+    /// Uploads files one after another into the current folder, checking
+    /// each against the folder's documents for duplicates first.
+    fn spawn_upload(&mut self, paths: Vec<String>) {
+        if !self.check_folder() {
+            return;
+        }
+        if self.dry_run {
+            let preview = self.preview_upload(&paths);
+            self.show_preview(preview);
+            return;
+        }
+        self.status_msg = match paths.as_slice() {
+            [one] => format!("Uploading {}...", one),
+            many => format!("Uploading {} files...", many.len()),
+        };
+
+        let backend = self.backend.clone();
         let current_guid = self.current_guid.clone();
         let tx = self.tx.clone();
-        let file_path = path_str.clone();
+        let upload_hooks = hooks::matching(&self.config.hooks, HookEvent::Upload);
+        let tablet_dir = self.tablet_dir();
+        let mut sticky: Option<Choice> = self.config.uploads.on_duplicate.into();
 
         tokio::spawn(async move {
-            // 1. Fetch current list to ensure target (per requirements)
-            if let Err(e) = fetch_documents(&client, &current_guid).await {
-                 let _ = tx.send(AppMessage::Error(format!("Upload pre-check failed: {}", e))).await;
-                 return;
-            }
-            
-            // 2. Upload
-            match upload_file(&client, &file_path).await {
-                Ok(_) => {
-                     let _ = tx.send(AppMessage::UploadComplete(file_path)).await;
-                },
-                Err(e) => {
-                     let _ = tx.send(AppMessage::Error(format!("Upload failed: {}", e))).await;
-                }
-            }
-        });
-    }
+            // Earlier uploads tell a re-sent file from a different one of the same name
+            let mut ledger = ledger::Ledger::open_default().ok();
+            for file_path in paths {
+                // 1. Fetch current list to ensure target (per requirements)
+                let listing = match backend.list(&current_guid).await {
+                    Ok(listing) => listing,
+                    Err(e) => {
+                        let _ = tx.send(AppMessage::Error(format!("Upload pre-check failed: {}", e))).await;
+                        break;
+                    }
+                };
+                let path = PathBuf::from(&file_path);
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let transfer = |status, name: &str| Transfer {
+                    event: HookEvent::Upload,
+                    status,
+                    tablet_path: format!("{}/{}", tablet_dir, name),
+                    guid: String::new(),
+                    format: path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default(),
+                    local_path: path.clone(),
+                };
 
-    fn get_help_text(&self) -> String {
-            match self.input_mode {
-                InputMode::Uploading => "[Enter] Confirm Upload [Esc] Cancel".to_string(),
-                InputMode::Downloading => "[Enter] Confirm Download [Esc] Cancel".to_string(),
-                InputMode::Normal => {
-                    let mut actions = vec!["[q] Quit", "[u] Upload", "[r] Refresh", "[j/k] Nav"];
-                    
-                    if !self.history.is_empty() {
-                        actions.push("[h] Back");
+                // 2. Check for a document of the same name
+                let mut upload_name = file_name.clone();
+                let duplicate = match duplicate::find(&listing, &path, ledger.as_ref(), &tablet_dir) {
+                    Ok(duplicate) => duplicate,
+                    Err(e) => {
+                        let _ = tx.send(AppMessage::Error(format!("Could not read {}: {}", file_path, e))).await;
+                        continue;
                     }
-    
-                    if let Some(i) = self.state.selected() {
-                        if let Some(item) = self.items.get(i) {
-                            if item.is_folder() {
-                                actions.push("[l/Enter] Open");
-                            } else {
-                                actions.push("[d] Download");
+                };
+                if let Some(duplicate) = duplicate {
+                    let choice = match sticky {
+                        Some(choice) => choice,
+                        None => {
+                            let (choice, all) = ask_conflict(&tx, path.clone(), Subject::Upload { duplicate: duplicate.describe() }).await;
+                            if all {
+                                sticky = Some(choice);
                             }
+                            choice
                         }
+                    };
+                    match choice {
+                        Choice::Skip => {
+                            spawn_hooks(upload_hooks.clone(), transfer(hooks::Status::Skipped, &file_name), tx.clone());
+                            let _ = tx.send(AppMessage::UploadSkipped(file_path, duplicate.describe())).await;
+                            continue;
+                        }
+                        Choice::Rename => upload_name = duplicate::suffixed(&listing, &file_name),
+                        Choice::Abort => break,
+                        _ => {}
                     }
-                    
-                    actions.join(" | ")
                 }
-            }
-        }}
 
-// --- Network Helpers ---
+                // 3. Upload
+                let result = backend.upload(&current_guid, &path, &upload_name).await;
+                let status = if result.is_ok() { hooks::Status::Ok } else { hooks::Status::Failed };
+                spawn_hooks(upload_hooks.clone(), transfer(status, &upload_name), tx.clone());
 
-const BASE_URL: &str = "http://10.11.99.1";
+                match result {
+                    Ok(_) => {
+                        if let Some(ledger) = &mut ledger {
+                            record_upload(ledger, &path, &tablet_dir);
+                        }
+                        let name = if upload_name == file_name { file_path } else { format!("{} as {}", file_path, upload_name) };
+                        let _ = tx.send(AppMessage::UploadComplete(name)).await;
+                    },
+                    Err(e) => {
+                         let _ = tx.send(AppMessage::Error(format!("Upload failed: {}", e))).await;
+                    }
+                }
+            }
+            if let Err(e) = backend.settle().await {
+                let _ = tx.send(AppMessage::Error(format!("Uploaded, but the tablet was not refreshed: {}", e))).await;
+            }
+        });
+    }
 
-async fn fetch_documents(client: &Client, guid: &Option<String>) -> Result<Vec<Item>> {
-    let url = match guid {
-        Some(id) => format!("{}/documents/{}", BASE_URL, id),
-        None => format!("{}/documents/", BASE_URL),
-    };
+    /// What uploading `paths` into the current folder would do, including
+    /// what `on_duplicate` would make of same-named documents.
+    fn preview_upload(&self, paths: &[String]) -> dryrun::Preview {
+        let mut preview = dryrun::Preview::new(dryrun::Direction::Upload);
+        let tablet_dir = self.tablet_dir();
+        let ledger = ledger::Ledger::open_default().ok();
+        for path_str in paths {
+            let path = Path::new(path_str);
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let duplicate = duplicate::find(&self.all_items, path, ledger.as_ref(), &tablet_dir).ok().flatten();
+            let conflict = duplicate.map(|d| {
+                let outcome = match self.config.uploads.on_duplicate.into() {
+                    Some(Choice::Skip) => "would be skipped".to_string(),
+                    Some(Choice::Anyway) => "would be uploaded anyway".to_string(),
+                    Some(Choice::Rename) => format!("would be uploaded as {}", duplicate::suffixed(&self.all_items, &file_name)),
+                    _ => "you would be asked".to_string(),
+                };
+                format!("{}; {}", d.describe(), outcome)
+            });
+            preview.files.push(dryrun::Planned {
+                source: path_str.clone(),
+                target: format!("{}/{}", tablet_dir, file_name),
+                size: std::fs::metadata(path).ok().map(|m| m.len()),
+                conflict,
+            });
+        }
+        preview
+    }
 
-    let resp = client.get(&url).send().await?;
-    let items: Vec<Item> = resp.json().await?;
-    Ok(items)
-}
+    /// Adds a finished dry run to the preview overlay and the log.
+    fn show_preview(&mut self, preview: dryrun::Preview) {
+        // A new batch starts once the previous one has been looked at
+        if self.overlay != Some(Overlay::Preview) {
+            self.previews.clear();
+        }
+        self.status_msg = preview.to_string();
+        self.log(true, preview.to_string(), preview.lines());
+        self.previews.push(preview);
+        self.show_overlay(Overlay::Preview);
+    }
 
-fn download_recursive(client: Client, item: Item, target_path: std::path::PathBuf) -> BoxFuture<'static, Result<String>> {
-    Box::pin(async move {
-        if item.is_folder() {
-            tokio::fs::create_dir_all(&target_path).await?;
-            let children = fetch_documents(&client, &Some(item.id)).await?;
-            for child in children {
-                let child_name = sanitize_filename(&child.visible_name, child.is_folder());
-                let child_path = target_path.join(child_name);
-                download_recursive(client.clone(), child, child_path).await?;
-            }
-            Ok(target_path.to_string_lossy().to_string())
+    fn toggle_dry_run(&mut self) {
+        self.dry_run = !self.dry_run;
+        self.status_msg = if self.dry_run {
+            "Dry run on: downloads, uploads and syncs are only previewed.".into()
         } else {
-            let url = format!("{}/download/{}/pdf", BASE_URL, item.id);
-            let resp = client.get(&url).send().await?;
-            
-            // Ensure parent exists (should be handled by caller usually, but good for safety)
-            if let Some(parent) = target_path.parent() {
-                if !parent.exists() {
-                     tokio::fs::create_dir_all(parent).await?;
-                }
+            "Dry run off.".into()
+        };
+    }
+
+    /// Opens the selection in the viewer, fetching tablet documents into the
+    /// cache unless the cached copy is still current.
+    fn view(&mut self) {
+        if self.local_focused() {
+            match self.local.selected() {
+                Some(entry) if !entry.is_dir => self.view_request = Some(entry.path.clone()),
+                _ => self.status_msg = "Select a file to view.".into(),
             }
+            return;
+        }
 
-            let mut file = tokio::fs::File::create(&target_path).await?;
-            let mut stream = resp.bytes_stream();
+        let Some(item) = self.state.selected().and_then(|i| self.items.get(i)).cloned() else { return };
+        if item.is_folder() {
+            self.status_msg = "Select a document to view.".into();
+            return;
+        }
 
-            while let Some(chunk_res) = stream.next().await {
-                let chunk = chunk_res?;
-                use tokio::io::AsyncWriteExt;
-                file.write_all(&chunk).await?;
-            }
-            Ok(target_path.to_string_lossy().to_string())
+        let (path, fresh) = viewer::cached(&item.id, item.modified.as_deref());
+        if fresh {
+            self.view_request = Some(path);
+            return;
         }
-    })
-}
 
-async fn download_selection(client: Client, item: Item, dest_path: String) -> Result<String> {
-    let output_path = Path::new(&dest_path);
-    let item_name = sanitize_filename(&item.visible_name, item.is_folder());
+        self.status_msg = format!("Fetching {} for viewing...", item.visible_name);
+        let backend = self.backend.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let fetched = async {
+                tokio::fs::create_dir_all(viewer::cache_dir()).await?;
+                stream_pdf(backend.as_ref(), &item, &path).await?;
+                viewer::mark_fresh(&item.id, item.modified.as_deref())?;
+                anyhow::Ok(path)
+            };
+            let msg = match fetched.await {
+                Ok(path) => AppMessage::ViewReady(path),
+                Err(e) => AppMessage::Error(format!("Could not fetch {}: {}", item.visible_name, e)),
+            };
+            let _ = tx.send(msg).await;
+        });
+    }
 
-    // Determine final path
-    let is_dir_target = dest_path.ends_with('/') || dest_path.ends_with(std::path::MAIN_SEPARATOR) || output_path.is_dir();
+    /// The current tablet folder as `/A/B`, or empty at the root.
+    fn tablet_dir(&self) -> String {
+        self.crumbs.iter().map(|name| format!("/{}", name)).collect()
+    }
 
-    let final_path = if is_dir_target {
-        if !output_path.exists() {
-             return Err(anyhow::anyhow!("Directory '{}' does not exist.", dest_path));
+    /// The tablet folder `item` is in: the current one, or in a library
+    /// view wherever the library has it.
+    fn item_dir(&self, item: &Item) -> String {
+        if self.view.is_none() {
+            return self.tablet_dir();
         }
-        output_path.join(item_name)
-    } else {
-        // If user gave a file-like path for a folder download, we treat it as the folder name
-        output_path.to_path_buf()
-    };
+        self.library_trail(item).iter().map(|(_, name)| format!("/{}", name)).collect()
+    }
 
-    // Ensure parent dir exists
-    if let Some(parent) = final_path.parent() {
-        if !parent.exists() {
-            return Err(anyhow::anyhow!("Directory '{}' does not exist.", parent.display()));
+    /// The `(id, name)` pairs of the folders from the root to the one
+    /// holding `item`, from the last library fetch.
+    fn library_trail(&self, item: &Item) -> Vec<(String, String)> {
+        let mut trail = Vec::new();
+        let mut parent = match (&item.origin, item.parent.as_str()) {
+            (Some(origin), "trash") => origin.as_str(),
+            _ => item.parent.as_str(),
+        };
+        while let Some(folder) = self.library.iter().find(|i| i.id == parent) {
+            if trail.len() > self.library.len() {
+                break; // A loop in damaged metadata
+            }
+            trail.push((folder.id.clone(), folder.visible_name.clone()));
+            parent = &folder.parent;
         }
+        trail.reverse();
+        trail
     }
 
-    download_recursive(client, item, final_path).await
-}
+    /// Checks that a folder is open rather than a library view, for actions
+    /// that put something into the current folder.
+    fn check_folder(&mut self) -> bool {
+        match &self.view {
+            Some(view) => {
+                self.status_msg = format!("{} is not a folder; open one first.", view.title());
+                false
+            }
+            None => true,
+        }
+    }
 
-async fn upload_file(client: &Client, path_str: &str) -> Result<()> {
-    let path = Path::new(path_str);
-    let file_name = path.file_name().ok_or_else(|| anyhow::anyhow!("Invalid filename"))?
-        .to_string_lossy().to_string();
+    /// Switches to library view `view`, or back to the folder if it is
+    /// already showing.
+    fn show_view(&mut self, view: Library) {
+        if self.local_focused() {
+            self.status_msg = "Switch to the tablet pane first.".into();
+            return;
+        }
+        self.view = if self.view.as_ref() == Some(&view) { None } else { Some(view) };
+        self.state.select(None);
+        self.refresh();
+    }
 
-    let file_bytes = tokio::fs::read(path).await?;
-    
-    // Create multipart form
-    // The API expects file=@path. 
-    // In reqwest multipart, we add a part.
-    let part = multipart::Part::bytes(file_bytes)
-        .file_name(file_name);
-    
-    let form = multipart::Form::new()
-        .part("file", part);
-
-    let url = format!("{}/upload", BASE_URL);
-    let resp = client.post(&url)
-        .multipart(form)
-        .send()
-        .await?;
-        
-    if resp.status().is_success() {
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!("Upload failed with status: {}", resp.status()))
+    /// Shows the items of the last library fetch that belong in the view.
+    fn set_library(&mut self, mut library: Vec<Item>) {
+        let Some(view) = &self.view else { return };
+        library.sort_by_cached_key(|i| (!i.is_folder(), i.visible_name.to_lowercase()));
+        let mut items: Vec<Item> = library.iter().filter(|i| view.includes(i)).cloned().collect();
+        if *view == Library::Trash {
+            items.sort_by(|a, b| b.modified.cmp(&a.modified));
+        }
+        self.library = library;
+        self.set_items(items);
     }
-}
 
-// --- Main ---
+    fn show_overlay(&mut self, overlay: Overlay) {
+        self.overlay = Some(overlay);
+        // The log opens at its newest entries
+        self.overlay_scroll = if overlay == Overlay::Log { u16::MAX } else { 0 };
+    }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    fn log(&mut self, ok: bool, summary: String, detail: Vec<String>) {
+        self.push_log(LogEntry { at: Local::now(), ok, summary, detail });
+    }
 
-    // Create App
-    let (tx, mut rx) = mpsc::channel(10);
-    let mut app = AppLogic::new(tx);
-    
-    // Initial fetch
-    app.refresh();
+    fn push_log(&mut self, entry: LogEntry) {
+        self.log.push(entry);
+        if self.log.len() > LOG_LIMIT {
+            self.log.remove(0);
+        }
+    }
 
-    let res = run_app(&mut terminal, &mut app, &mut rx).await;
+    fn record_hook(&mut self, run: HookRun) {
+        let why = match &run.result {
+            Ok(code) => format!("exit {}", code),
+            Err(e) => e.clone(),
+        };
+        if !run.succeeded() {
+            self.status_msg = match self.keymap.primary(Action::Log) {
+                Some(key) => format!("Hook `{}` failed ({}). Press {} for the log.", run.command, why, key),
+                None => format!("Hook `{}` failed ({}). Run :log for details.", run.command, why),
+            };
+        }
+        let mut detail = vec![format!("$ {}", run.command)];
+        detail.extend(run.output.lines().map(String::from));
+        self.push_log(LogEntry {
+            at: run.started,
+            ok: run.succeeded(),
+            summary: format!("Hook after {} ({})", run.transfer, why),
+            detail,
+        });
+    }
 
-    // Restore terminal
+    fn record_watch(&mut self, event: watch::WatchEvent) {
+        use watch::WatchEvent;
+        let (ok, msg) = match event {
+            WatchEvent::Uploaded { local, tablet_folder } => {
+                if tablet_folder == self.tablet_dir() {
+                    self.refresh();
+                }
+                let to = if tablet_folder.is_empty() { "/" } else { &tablet_folder };
+                (true, format!("Watch: uploaded {} to {}", local.display(), to))
+            }
+            WatchEvent::Failed { local, error } => (false, format!("Watch: could not upload {}: {}", local.display(), error)),
+            WatchEvent::Skipped { local, reason } => (true, format!("Watch: skipped {}: {}", local.display(), reason)),
+            WatchEvent::Offline { queued } => (false, format!("Watch: tablet unreachable; {} file(s) queued", queued)),
+            WatchEvent::Online => (true, "Watch: tablet reachable again".to_string()),
+        };
+        self.status_msg = msg.clone();
+        self.log(ok, msg, Vec::new());
+    }
+
+    /// Shows the oldest pending conflict question, if the UI is free.
+    fn next_conflict(&mut self) {
+        if let (InputMode::Normal, Some(prompt)) = (&self.input_mode, self.conflicts.front()) {
+            self.input_mode = InputMode::Conflict;
+            self.apply_to_all = false;
+            self.status_msg = match prompt.subject {
+                Subject::Download { .. } => format!("{} already exists.", prompt.path.display()),
+                Subject::Upload { .. } => format!("{} may already be on the tablet.", prompt.path.display()),
+            };
+        }
+    }
+
+    fn answer_conflict(&mut self, choice: Choice) {
+        let Some(prompt) = self.conflicts.pop_front() else { return };
+        if !prompt.offers(choice) {
+            // A key meant for the other kind of question
+            self.conflicts.push_front(prompt);
+            return;
+        }
+        let verb = match prompt.subject {
+            Subject::Download { .. } => "Download",
+            Subject::Upload { .. } => "Upload",
+        };
+        prompt.answer(choice, self.apply_to_all);
+        self.input_mode = InputMode::Normal;
+        self.status_msg = match choice {
+            Choice::Abort => format!("{} cancelled.", verb),
+            _ => format!("{}ing...", verb),
+        };
+        self.next_conflict();
+    }
+
+    /// Whether `action` works with the active backend.
+    fn available(&self, action: Action) -> bool {
+        !matches!(
+            action,
+            Action::Rename
+                | Action::Move
+                | Action::Delete
+                | Action::Mkdir
+                | Action::Star
+                | Action::EditTags
+                | Action::ShowTrash
+                | Action::Restore
+                | Action::Purge
+        ) || self.backend.can_edit()
+    }
+
+    /// Checks that the tablet pane is focused and its backend can change
+    /// documents, saying why not otherwise.
+    fn check_editable(&mut self) -> bool {
+        if self.local_focused() {
+            self.status_msg = "Switch to the tablet pane to change documents.".into();
+            false
+        } else if !self.backend.can_edit() {
+            self.status_msg = format!("Profile {} cannot change documents; switch to an ssh or local profile.", self.profile);
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Starts editing the selected item's name in the list.
+    fn start_rename(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let Some(item) = self.state.selected().and_then(|i| self.items.get(i)).cloned() else {
+            self.status_msg = "Select something to rename first.".into();
+            return;
+        };
+        self.input_buffer = item.visible_name.clone();
+        self.editing = vec![item];
+        self.input_mode = InputMode::Renaming;
+        self.status_msg = "Type the new name.".into();
+    }
+
+    fn confirm_rename(&mut self) {
+        self.input_mode = InputMode::Normal;
+        let name = std::mem::take(&mut self.input_buffer).trim().to_string();
+        let items = std::mem::take(&mut self.editing);
+        let Some(item) = items.first() else { return };
+        if name.is_empty() {
+            self.status_msg = "Name cannot be empty.".into();
+        } else if name == item.visible_name {
+            self.status_msg = "Name unchanged.".into();
+        } else {
+            let what = format!("renamed \"{}\" to \"{}\"", item.visible_name, name);
+            self.spawn_edit(items, backend::Edit::Rename(name), what);
+        }
+    }
+
+    /// Picks up the marked items (or the selection) to move; pressed again
+    /// while moving, drops them into the current folder.
+    fn start_move(&mut self) {
+        if let InputMode::Moving = self.input_mode {
+            self.finish_move();
+            return;
+        }
+        if !self.check_editable() {
+            return;
+        }
+        let items = self.tablet_targets();
+        if items.is_empty() {
+            self.status_msg = "Select something to move first.".into();
+            return;
+        }
+        let key = |action| self.keymap.primary(action).unwrap_or_else(|| "-".into());
+        self.status_msg = format!(
+            "Moving {}: open the destination and press {} there, or {} to cancel.",
+            describe_items(&items),
+            key(Action::Move),
+            key(Action::Cancel)
+        );
+        self.editing = items;
+        self.marked.clear();
+        self.input_mode = InputMode::Moving;
+    }
+
+    fn finish_move(&mut self) {
+        if !self.check_folder() {
+            return;
+        }
+        self.input_mode = InputMode::Normal;
+        let items = std::mem::take(&mut self.editing);
+        // The folders on the way here, which nothing may be moved into
+        let trail: HashSet<&String> = self.history.iter().chain(std::iter::once(&self.current_guid)).flatten().collect();
+        if items.iter().any(|item| trail.contains(&item.id)) {
+            self.status_msg = "A folder cannot be moved into itself.".into();
+            return;
+        }
+        let items: Vec<Item> = items.into_iter().filter(|item| !self.all_items.iter().any(|here| here.id == item.id)).collect();
+        if items.is_empty() {
+            self.status_msg = "Already in this folder.".into();
+            return;
+        }
+        let dir = self.tablet_dir();
+        let what = format!("moved {} to {}", describe_items(&items), if dir.is_empty() { "the top level" } else { &dir });
+        self.spawn_edit(items, backend::Edit::Move(self.current_guid.clone()), what);
+    }
+
+    fn start_trash(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        if self.view == Some(Library::Trash) {
+            let key = self.keymap.primary(Action::Purge).unwrap_or_else(|| "-".into());
+            self.status_msg = format!("Already in the trash; {} deletes for good.", key);
+            return;
+        }
+        let items = self.tablet_targets();
+        if items.is_empty() {
+            self.status_msg = "Select something to delete first.".into();
+            return;
+        }
+        self.status_msg = format!("Move {} to the trash?", describe_items(&items));
+        self.editing = items;
+        self.input_mode = InputMode::ConfirmTrash;
+    }
+
+    fn confirm_trash(&mut self) {
+        self.input_mode = InputMode::Normal;
+        let items = std::mem::take(&mut self.editing);
+        let what = format!("moved {} to the trash", describe_items(&items));
+        self.marked.clear();
+        self.spawn_edit(items, backend::Edit::Trash, what);
+    }
+
+    fn start_mkdir(&mut self) {
+        if !self.check_editable() || !self.check_folder() {
+            return;
+        }
+        self.input_buffer.clear();
+        self.input_mode = InputMode::MakingFolder;
+        self.status_msg = "Name the new folder (A/B creates both):".into();
+    }
+
+    /// Creates the typed folder, and any missing folders on its path,
+    /// inside the current folder.
+    fn confirm_mkdir(&mut self) {
+        self.input_mode = InputMode::Normal;
+        let name = std::mem::take(&mut self.input_buffer).trim().trim_matches('/').to_string();
+        if name.is_empty() {
+            self.status_msg = "Name cannot be empty.".into();
+            return;
+        }
+        let path = format!("{}/{}", self.tablet_dir(), name);
+        let what = format!("created {}", path);
+        if self.dry_run {
+            let msg = format!("Dry run: would have {}.", what);
+            self.status_msg = msg.clone();
+            self.log(true, msg, Vec::new());
+            return;
+        }
+        self.status_msg = format!("Creating {}...", path);
+        let backend = self.backend.clone();
+        let tx = self.tx.clone();
+        let trail = self.trail();
+        tokio::spawn(async move {
+            let made = make_folders(backend.as_ref(), trail, &name, true).await;
+            let settled = backend.settle().await;
+            let msg = match (made, settled) {
+                (Ok(0), _) => AppMessage::Error(format!("{} already exists", path)),
+                (Ok(_), Ok(())) => AppMessage::Edited(what, Vec::new()),
+                (Ok(_), Err(e)) => AppMessage::Edited(what, vec![format!("tablet not refreshed: {:#}", e)]),
+                (Err(e), _) => AppMessage::Error(format!("mkdir failed: {:#}", e)),
+            };
+            let _ = tx.send(msg).await;
+        });
+    }
+
+    /// Stars the marked items (or the selection), or unstars them when all
+    /// of them are starred already.
+    fn toggle_star(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let items = self.tablet_targets();
+        if items.is_empty() {
+            self.status_msg = "Select something to star first.".into();
+            return;
+        }
+        let pin = !items.iter().all(|i| i.pinned);
+        let what = format!("{} {}", if pin { "starred" } else { "unstarred" }, describe_items(&items));
+        self.spawn_edit(items, backend::Edit::Pin(pin), what);
+    }
+
+    /// Opens the tags of the marked items (or the selection) for editing;
+    /// for several items, the tags they all share.
+    fn start_tagging(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let items = self.tablet_targets();
+        if items.is_empty() {
+            self.status_msg = "Select something to tag first.".into();
+            return;
+        }
+        self.input_buffer = common_tags(&items).join(", ");
+        self.status_msg = format!("Tags of {}, separated by commas:", describe_items(&items));
+        self.editing = items;
+        self.input_mode = InputMode::Tagging;
+    }
+
+    /// Adds the typed tags that are new and removes the shared ones that
+    /// were deleted, leaving other tags of each item alone.
+    fn confirm_tagging(&mut self) {
+        self.input_mode = InputMode::Normal;
+        let typed = std::mem::take(&mut self.input_buffer);
+        let items = std::mem::take(&mut self.editing);
+        if items.is_empty() {
+            return;
+        }
+        let mut tags: Vec<String> = Vec::new();
+        for tag in typed.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            if !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        let common = common_tags(&items);
+        let add: Vec<String> = tags.iter().filter(|t| !common.contains(t)).cloned().collect();
+        let remove: Vec<String> = common.into_iter().filter(|t| !tags.contains(t)).collect();
+        if add.is_empty() && remove.is_empty() {
+            self.status_msg = "Tags unchanged.".into();
+            return;
+        }
+        let changes: Vec<String> = add.iter().map(|t| format!("+{}", t)).chain(remove.iter().map(|t| format!("-{}", t))).collect();
+        let what = format!("changed the tags of {} ({})", describe_items(&items), changes.join(", "));
+        self.marked.clear();
+        self.spawn_edit(items, backend::Edit::Tags { add, remove }, what);
+    }
+
+    /// Opens the trash view; only backends that can change documents can
+    /// read the trash too.
+    fn show_trash(&mut self) {
+        if self.backend.can_edit() {
+            self.show_view(Library::Trash);
+        } else {
+            self.status_msg = format!("Profile {} cannot see the trash; switch to an ssh or local profile.", self.profile);
+        }
+    }
+
+    /// The marked items (or the selection) of the trash view, saying why
+    /// there are none otherwise.
+    fn trash_targets(&mut self, verb: &str) -> Vec<Item> {
+        if !self.check_editable() {
+            return Vec::new();
+        }
+        if self.view != Some(Library::Trash) {
+            let key = self.keymap.primary(Action::ShowTrash).unwrap_or_else(|| "-".into());
+            self.status_msg = format!("Open the trash with {} to {} from it.", key, verb);
+            return Vec::new();
+        }
+        let items = self.tablet_targets();
+        if items.is_empty() {
+            self.status_msg = format!("Select something to {} first.", verb);
+        }
+        items
+    }
+
+    /// Where restoring `item` puts it: the folder it was trashed from while
+    /// that is still outside the trash, otherwise the top level.
+    fn restore_target(&self, item: &Item) -> Option<String> {
+        let origin = item.origin.as_deref().filter(|o| !o.is_empty())?;
+        // The library holds every folder outside the trash
+        self.library.iter().any(|i| i.id == origin && i.parent != "trash").then(|| origin.to_string())
+    }
+
+    fn restore(&mut self) {
+        let items = self.trash_targets("restore");
+        if items.is_empty() {
+            return;
+        }
+        let mut lost = 0;
+        let mut edits = Vec::new();
+        for item in &items {
+            let target = self.restore_target(item);
+            if target.is_none() && item.origin.as_deref() != Some("") {
+                lost += 1;
+            }
+            edits.push((item.clone(), backend::Edit::Move(target)));
+        }
+        let mut what = format!("restored {}", describe_items(&items));
+        if lost > 0 {
+            what.push_str(&format!(" ({} to the top level, as the original folder is unknown or gone)", lost));
+        }
+        self.marked.clear();
+        self.spawn_edits(edits, what);
+    }
+
+    fn start_purge(&mut self) {
+        let items = self.trash_targets("delete");
+        if items.is_empty() {
+            return;
+        }
+        self.status_msg = format!("Delete {} for good?", describe_items(&items));
+        self.editing = items;
+        self.input_mode = InputMode::ConfirmPurge;
+    }
+
+    fn confirm_purge(&mut self) {
+        self.input_mode = InputMode::Normal;
+        let items = std::mem::take(&mut self.editing);
+        let what = format!("deleted {} for good", describe_items(&items));
+        self.marked.clear();
+        self.spawn_edit(items, backend::Edit::Purge, what);
+    }
+
+    fn cancel_edit(&mut self) {
+        self.input_mode = InputMode::Normal;
+        self.input_buffer.clear();
+        self.editing.clear();
+        self.status_msg = "Cancelled.".into();
+    }
+
+    /// Applies `edit` to `items` in the background, then refreshes. `what`
+    /// describes the change, e.g. `moved 2 items to /Work`.
+    fn spawn_edit(&mut self, items: Vec<Item>, edit: backend::Edit, what: String) {
+        let edits = items.into_iter().map(|item| (item, edit.clone())).collect();
+        self.spawn_edits(edits, what);
+    }
+
+    /// Like `spawn_edit`, with a change of its own for each item.
+    fn spawn_edits(&mut self, edits: Vec<(Item, backend::Edit)>, what: String) {
+        if self.dry_run {
+            let msg = format!("Dry run: would have {}.", what);
+            self.status_msg = msg.clone();
+            self.log(true, msg, Vec::new());
+            return;
+        }
+        self.status_msg = "Saving changes...".into();
+        let backend = self.backend.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let mut errors = Vec::new();
+            for (item, edit) in &edits {
+                if let Err(e) = backend.edit(item, edit).await {
+                    errors.push(format!("{}: {:#}", item.visible_name, e));
+                }
+            }
+            if let Err(e) = backend.settle().await {
+                errors.push(format!("tablet not refreshed: {:#}", e));
+            }
+            let _ = tx.send(AppMessage::Edited(what, errors)).await;
+        });
+    }
+
+    fn record_edit(&mut self, what: String, errors: Vec<String>) {
+        let ok = errors.is_empty();
+        let msg = if ok {
+            format!("{}{}.", what[..1].to_uppercase(), &what[1..])
+        } else {
+            format!("Not everything could be {}: {} error(s).", what, errors.len())
+        };
+        self.refresh();
+        self.status_msg = msg.clone();
+        self.log(ok, msg, errors);
+    }
+
+    /// Works out what syncing the `[[sync]]` pairs (or just `name`) would do.
+    fn plan_sync(&mut self, name: Option<String>) {
+        let pairs: Vec<sync::SyncConfig> = match sync::select(&self.config.sync, name.as_deref()) {
+            Ok(pairs) => pairs.into_iter().cloned().collect(),
+            Err(e) => {
+                self.status_msg = e.to_string();
+                return;
+            }
+        };
+        self.status_msg = "Planning sync...".into();
+        let backend = self.backend.clone();
+        let ascii_only = self.config.downloads.ascii_filenames;
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let mut plans = Vec::new();
+            for pair in &pairs {
+                match sync::plan(backend.as_ref(), pair, ascii_only).await {
+                    Ok(plan) => plans.push(plan),
+                    Err(e) => {
+                        let _ = tx.send(AppMessage::Error(format!("Sync `{}`: {:#}", pair.name(), e))).await;
+                        return;
+                    }
+                }
+            }
+            let _ = tx.send(AppMessage::SyncPlanned(plans)).await;
+        });
+    }
+
+    /// Asks for a go-ahead when the plans would change anything; plans that
+    /// only record files already in step run straight away.
+    fn review_sync(&mut self, plans: Vec<sync::Plan>) {
+        if self.dry_run || plans.iter().any(|plan| plan.visible().next().is_some()) {
+            self.sync_plans = plans;
+            self.input_mode = InputMode::SyncReview;
+            self.status_msg = "Review the sync plan.".into();
+        } else {
+            self.status_msg = "Everything is in sync.".into();
+            self.run_sync(plans);
+        }
+    }
+
+    fn confirm_sync(&mut self) {
+        self.input_mode = InputMode::Normal;
+        if self.dry_run {
+            self.sync_plans.clear();
+            self.status_msg = "Dry run: the sync was not started.".into();
+            return;
+        }
+        let plans = std::mem::take(&mut self.sync_plans);
+        self.status_msg = "Syncing...".into();
+        self.run_sync(plans);
+    }
+
+    fn cancel_sync(&mut self) {
+        self.input_mode = InputMode::Normal;
+        self.sync_plans.clear();
+        self.status_msg = "Sync cancelled.".into();
+    }
+
+    fn run_sync(&mut self, plans: Vec<sync::Plan>) {
+        let backend = self.backend.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            for plan in plans {
+                let name = plan.pair.name().to_string();
+                let msg = match sync::run(backend.as_ref(), plan).await {
+                    Ok(report) => AppMessage::SyncDone(name, report),
+                    Err(e) => AppMessage::Error(format!("Sync `{}` failed: {:#}", name, e)),
+                };
+                let _ = tx.send(msg).await;
+            }
+        });
+    }
+
+    fn record_sync(&mut self, name: String, report: sync::Report) {
+        let ok = report.errors.is_empty();
+        let msg = format!("Synced {}: {}", name, report);
+        self.status_msg = msg.clone();
+        self.log(ok, msg, report.errors);
+        if report.downloaded + report.uploaded + report.deleted > 0 {
+            self.refresh();
+            if self.dual_pane {
+                let err = self.local.reload();
+                self.local_result(err);
+            }
+        }
+    }
+
+    /// Writes the notes of the marked or selected documents (folders with
+    /// everything in them) into `dir`, or the configured notes directory.
+    fn export_notes(&mut self, dir: Option<String>) {
+        if self.local_focused() {
+            self.status_msg = "Switch to the tablet pane to export notes.".into();
+            return;
+        }
+        let targets = self.tablet_targets();
+        if targets.is_empty() {
+            self.status_msg = "Select something to export notes from first.".into();
+            return;
+        }
+        let Some(dir) = dir.or_else(|| notes_directory(&self.config)) else {
+            self.status_msg = "No notes directory; give one (`:notes ~/Notes`) or set notes.directory.".into();
+            return;
+        };
+        let dir = PathBuf::from(expand_path(&dir));
+        self.status_msg = format!("Exporting notes from {}...", describe_items(&targets));
+        let backend = self.backend.clone();
+        let tx = self.tx.clone();
+        let targets: Vec<(String, Item)> = targets.into_iter().map(|item| (self.item_dir(&item), item)).collect();
+        let (format, ascii_only, dry_run) = (self.config.notes.format, self.config.downloads.ascii_filenames, self.dry_run);
+        tokio::spawn(async move {
+            let mut documents = Vec::new();
+            for (tablet_dir, item) in targets {
+                let tablet_path = format!("{}/{}", tablet_dir, item.visible_name);
+                let folders = if item.is_folder() { vec![item.visible_name.clone()] } else { Vec::new() };
+                match notes::documents(backend.as_ref(), item, tablet_path, folders).await {
+                    Ok(found) => documents.extend(found),
+                    Err(e) => {
+                        let _ = tx.send(AppMessage::Error(format!("notes failed: {:#}", e))).await;
+                        return;
+                    }
+                }
+            }
+            let report = notes::export(backend.as_ref(), documents, &dir, format, ascii_only, dry_run).await;
+            let _ = tx.send(AppMessage::NotesExported(dir, report)).await;
+        });
+    }
+
+    fn record_notes(&mut self, dir: PathBuf, report: notes::Report) {
+        let ok = report.errors.is_empty();
+        let msg = if self.dry_run {
+            format!("Dry run: would have exported notes of {} to {}.", report, dir.display())
+        } else {
+            format!("Exported notes of {} to {}.", report, dir.display())
+        };
+        let detail = report.written.iter().map(|f| f.display().to_string()).chain(report.errors).collect();
+        self.status_msg = msg.clone();
+        self.log(ok, msg, detail);
+    }
+
+    /// Lists or changes the tablet's templates or splash screens. Listings
+    /// open in an overlay; changes go to the status line and the log.
+    fn manage_device(&mut self, request: device::Request) {
+        let listing = request.is_list();
+        self.status_msg = if listing { "Reading from the tablet...".into() } else { "Changing the tablet...".into() };
+        let backend = self.backend.clone();
+        let tx = self.tx.clone();
+        let (profile, dry_run) = (self.profile.clone(), self.dry_run);
+        tokio::spawn(async move {
+            let message = match device::run(backend.as_ref(), &profile, request, dry_run).await {
+                Ok(outcome) => AppMessage::DeviceDone(outcome, listing),
+                Err(e) => AppMessage::Error(format!("{:#}", e)),
+            };
+            let _ = tx.send(message).await;
+        });
+    }
+
+    fn record_device(&mut self, outcome: device::Outcome, listing: bool) {
+        if listing {
+            self.status_msg = outcome.summary.clone();
+            self.device = Some(outcome);
+            self.show_overlay(Overlay::Device);
+            return;
+        }
+        self.status_msg = format!("{}.", outcome.summary);
+        self.log(true, outcome.summary, outcome.lines);
+    }
+
+    fn open_palette(&mut self) {
+        self.input_mode = InputMode::Command;
+        self.palette.open();
+    }
+
+    fn run_palette(&mut self) {
+        let line = self.palette.submit();
+        self.input_mode = InputMode::Normal;
+        match palette::parse(&self.commands, &line) {
+            Ok(command) => self.run_command(command),
+            Err(e) => self.status_msg = e,
+        }
+    }
+
+    fn run_command(&mut self, command: PaletteCommand) {
+        match command {
+            PaletteCommand::Action(action) => self.perform(action),
+            PaletteCommand::Cd(path) => self.change_directory(&path),
+            PaletteCommand::Download(None) => self.download(),
+            PaletteCommand::Download(Some(path)) => {
+                self.download();
+                if let InputMode::Downloading = self.input_mode {
+                    self.input_buffer = path;
+                    self.confirm_download();
+                } else {
+                    self.status_msg = "Select something to download first.".into();
+                }
+            }
+            PaletteCommand::Upload(None) => self.start_upload(),
+            PaletteCommand::Upload(Some(path)) => {
+                self.start_upload();
+                self.input_buffer = path;
+                self.confirm_upload();
+            }
+            PaletteCommand::Rename(None) => self.start_rename(),
+            PaletteCommand::Rename(Some(name)) => {
+                self.start_rename();
+                if let InputMode::Renaming = self.input_mode {
+                    self.input_buffer = name;
+                    self.confirm_rename();
+                }
+            }
+            PaletteCommand::Mkdir(None) => self.start_mkdir(),
+            PaletteCommand::Mkdir(Some(name)) => {
+                self.start_mkdir();
+                if let InputMode::MakingFolder = self.input_mode {
+                    self.input_buffer = name;
+                    self.confirm_mkdir();
+                }
+            }
+            PaletteCommand::EditTags(None) => self.start_tagging(),
+            PaletteCommand::EditTags(Some(tags)) => {
+                self.start_tagging();
+                if let InputMode::Tagging = self.input_mode {
+                    self.input_buffer = tags;
+                    self.confirm_tagging();
+                }
+            }
+            PaletteCommand::Tagged(tag) => self.show_view(Library::Tagged(tag)),
+            PaletteCommand::Sync(name) => self.plan_sync(name),
+            PaletteCommand::Notes(dir) => self.export_notes(dir),
+            PaletteCommand::Profile(name) => self.switch_profile(name),
+            PaletteCommand::Device(request) => self.manage_device(request),
+        }
+    }
+
+    /// Shows the active profile, or reconnects through profile `name`,
+    /// starting again at the root.
+    fn switch_profile(&mut self, name: Option<String>) {
+        let Some(name) = name else {
+            let names = self.config.profile_names();
+            self.status_msg = format!(
+                "Profile {} ({}); configured: {}",
+                self.profile,
+                self.backend.describe(),
+                if names.is_empty() { "none".to_string() } else { names.join(", ") }
+            );
+            return;
+        };
+        let Some(settings) = self.config.profiles.get(&name) else {
+            self.status_msg = format!("No profile named `{}`.", name);
+            return;
+        };
+        self.backend = settings.open();
+        self.config.device.profile = Some(name.clone());
+        self.profile = name;
+        self.current_guid = None;
+        self.view = None;
+        self.history.clear();
+        self.crumbs.clear();
+        self.marked.clear();
+        self.state.select(None);
+        self.refresh();
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let (x, y) = (mouse.column, mouse.row);
+        let modal_open = !matches!(self.input_mode, InputMode::Normal);
+
+        let over_local = self.dual_pane && self.local_area.contains((x, y).into());
+
+        match mouse.kind {
+            MouseEventKind::ScrollDown if self.overlay.is_some() => self.overlay_scroll = self.overlay_scroll.saturating_add(1),
+            MouseEventKind::ScrollUp if self.overlay.is_some() => self.overlay_scroll = self.overlay_scroll.saturating_sub(1),
+            MouseEventKind::ScrollDown if !modal_open && over_local => self.local.next(),
+            MouseEventKind::ScrollUp if !modal_open && over_local => self.local.previous(),
+            MouseEventKind::ScrollDown if !modal_open => self.next(),
+            MouseEventKind::ScrollUp if !modal_open => self.previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                if self.overlay.take().is_some() {
+                    return;
+                }
+
+                let target = self
+                    .click_targets
+                    .iter()
+                    .find(|(area, _)| area.contains((x, y).into()))
+                    .map(|(_, target)| *target);
+                match target {
+                    Some(ClickTarget::Crumb(depth)) if !modal_open => self.go_to_depth(depth),
+                    Some(ClickTarget::Button(action)) => self.perform(action),
+                    Some(ClickTarget::Suggestion(index)) => self.palette.choose(&self.commands, index),
+                    _ if !modal_open => self.click_list(x, y),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn click_list(&mut self, x: u16, y: u16) {
+        let pane = if self.dual_pane && self.local_area.contains((x, y).into()) { Pane::Local } else { Pane::Tablet };
+        let (area, offset, len) = match pane {
+            Pane::Local => (self.local_area, self.local.state.offset(), self.local.entries.len()),
+            Pane::Tablet => (self.list_area, self.state.offset(), self.items.len()),
+        };
+        let inner = Block::default().borders(Borders::ALL).inner(area);
+        if !inner.contains((x, y).into()) {
+            return;
+        }
+        if self.dual_pane {
+            self.focus = pane;
+        }
+        let row = (y - inner.y) as usize + offset;
+        if row >= len {
+            return;
+        }
+
+        let now = Instant::now();
+        let double = matches!(self.last_click, Some((at, last_pane, last_row)) if last_pane == pane && last_row == row && now.duration_since(at) < DOUBLE_CLICK);
+        self.last_click = if double { None } else { Some((now, pane, row)) };
+
+        match pane {
+            Pane::Local => {
+                self.local.state.select(Some(row));
+                if double {
+                    let err = self.local.enter();
+                    self.local_result(err);
+                }
+            }
+            Pane::Tablet => {
+                self.state.select(Some(row));
+                if double {
+                    if self.items[row].is_folder() {
+                        self.enter();
+                    } else {
+                        self.download();
+                    }
+                }
+            }
+        }
+    }
+
+    fn context(&self) -> Context {
+        match self.input_mode {
+            InputMode::Normal | InputMode::Moving => Context::Browse,
+            InputMode::Uploading
+            | InputMode::Downloading
+            | InputMode::Filtering
+            | InputMode::SyncReview
+            | InputMode::Renaming
+            | InputMode::ConfirmTrash
+            | InputMode::MakingFolder
+            | InputMode::Tagging
+            | InputMode::ConfirmPurge => Context::Prompt,
+            InputMode::Command => Context::Palette,
+            InputMode::Conflict => Context::Conflict,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if self.overlay.is_some() {
+            // Overlays scroll with the movement keys; anything else closes them
+            match self.keymap.resolve(Context::Browse, &[KeyChord::from(key)]) {
+                Resolution::Action(Action::Down) => self.overlay_scroll = self.overlay_scroll.saturating_add(1),
+                Resolution::Action(Action::Up) => self.overlay_scroll = self.overlay_scroll.saturating_sub(1),
+                _ => self.overlay = None,
+            }
+            return;
+        }
+
+        let context = self.context();
+        self.pending_keys.push(KeyChord::from(key));
+
+        match self.keymap.resolve(context, &self.pending_keys) {
+            Resolution::Action(action) => {
+                self.pending_keys.clear();
+                self.perform(action);
+            }
+            Resolution::Pending => {}
+            Resolution::Unbound if matches!(self.input_mode, InputMode::Moving) => {
+                // Browsing keys pick the destination, so cancel is looked up separately
+                let typed = std::mem::take(&mut self.pending_keys);
+                if let Resolution::Action(Action::Cancel) = self.keymap.resolve(Context::Prompt, &typed) {
+                    self.cancel_edit();
+                }
+            }
+            Resolution::Unbound => {
+                let typed = std::mem::take(&mut self.pending_keys);
+                // Unbound keys in a prompt are text input
+                for chord in typed {
+                    match (context, chord.as_char()) {
+                        (Context::Browse, _) => {}
+                        (Context::Palette, Some(c)) => self.palette.push(c),
+                        (Context::Palette, None) if chord.code() == KeyCode::Backspace => self.palette.pop(),
+                        (Context::Prompt, _)
+                            if matches!(self.input_mode, InputMode::SyncReview | InputMode::ConfirmTrash | InputMode::ConfirmPurge) => {}
+                        (Context::Prompt, Some(c)) => self.input_buffer.push(c),
+                        (Context::Prompt, None) if chord.code() == KeyCode::Backspace => { self.input_buffer.pop(); },
+                        _ => {}
+                    }
+                }
+                if let InputMode::Filtering = self.input_mode {
+                    self.update_filter();
+                }
+            }
+        }
+    }
+
+    fn perform(&mut self, action: Action) {
+        if self.local_focused() {
+            let handled = match action {
+                Action::Down => { self.local.next(); true },
+                Action::Up => { self.local.previous(); true },
+                Action::Top => { self.local.first(); true },
+                Action::Bottom => { self.local.last(); true },
+                Action::Open => { let err = self.local.enter(); self.local_result(err); true },
+                Action::Back => { let err = self.local.go_back(); self.local_result(err); true },
+                Action::Refresh => { let err = self.local.reload(); self.local_result(err); true },
+                _ => false,
+            };
+            if handled {
+                return;
+            }
+        }
+
+        if let InputMode::Moving = self.input_mode {
+            let picking = matches!(
+                action,
+                Action::Quit
+                    | Action::Down
+                    | Action::Up
+                    | Action::Top
+                    | Action::Bottom
+                    | Action::Open
+                    | Action::Back
+                    | Action::Refresh
+                    | Action::Move
+                    | Action::Help
+            );
+            if !picking {
+                self.status_msg = "Finish moving first.".into();
+                return;
+            }
+        }
+
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::Down => self.next(),
+            Action::Up => self.previous(),
+            Action::Top => self.first(),
+            Action::Bottom => self.last(),
+            Action::Open => self.enter(),
+            Action::Back => self.go_back(),
+            Action::Download => self.download(),
+            Action::Upload => self.start_upload(),
+            Action::Rename => self.start_rename(),
+            Action::Move => self.start_move(),
+            Action::Delete => self.start_trash(),
+            Action::Mkdir => self.start_mkdir(),
+            Action::Star => self.toggle_star(),
+            Action::EditTags => self.start_tagging(),
+            Action::Refresh => self.refresh(),
+            Action::SortName => self.set_sort(SortOrder::Name),
+            Action::SortModified => self.set_sort(SortOrder::Modified),
+            Action::SortType => self.set_sort(SortOrder::Type),
+            Action::Mark => self.toggle_mark(),
+            Action::Filter => self.start_filter(),
+            Action::Favourites => self.show_view(Library::Favourites),
+            Action::Tagged => self.show_view(Library::Tagged(None)),
+            Action::ShowTrash => self.show_trash(),
+            Action::Restore => self.restore(),
+            Action::Purge => self.start_purge(),
+            Action::TogglePanes => self.toggle_panes(),
+            Action::SwitchPane => self.switch_pane(),
+            Action::Copy => self.copy_across(),
+            Action::DryRun => self.toggle_dry_run(),
+            Action::View => self.view(),
+            Action::Log => self.show_overlay(Overlay::Log),
+            Action::Help => self.show_overlay(Overlay::Help),
+            Action::Palette => self.open_palette(),
+            Action::Confirm => match self.input_mode {
+                InputMode::Uploading => self.confirm_upload(),
+                InputMode::Downloading => self.confirm_download(),
+                InputMode::Command => self.run_palette(),
+                InputMode::Filtering => self.finish_filter(true),
+                InputMode::SyncReview => self.confirm_sync(),
+                InputMode::Renaming => self.confirm_rename(),
+                InputMode::ConfirmTrash => self.confirm_trash(),
+                InputMode::MakingFolder => self.confirm_mkdir(),
+                InputMode::Tagging => self.confirm_tagging(),
+                InputMode::ConfirmPurge => self.confirm_purge(),
+                InputMode::Conflict | InputMode::Normal | InputMode::Moving => {}
+            },
+            Action::Cancel => match self.input_mode {
+                InputMode::Uploading => self.cancel_upload(),
+                InputMode::Downloading => self.cancel_download(),
+                InputMode::Command => self.input_mode = InputMode::Normal,
+                InputMode::Filtering => self.finish_filter(false),
+                InputMode::Conflict => self.answer_conflict(Choice::Abort),
+                InputMode::SyncReview => self.cancel_sync(),
+                InputMode::Renaming
+                | InputMode::Moving
+                | InputMode::ConfirmTrash
+                | InputMode::MakingFolder
+                | InputMode::Tagging
+                | InputMode::ConfirmPurge => self.cancel_edit(),
+                InputMode::Normal => {}
+            },
+            Action::Complete => self.palette.complete(&self.commands),
+            Action::HistoryPrev => self.palette.history_prev(),
+            Action::HistoryNext => self.palette.history_next(),
+            Action::ConflictOverwrite => self.answer_conflict(Choice::Overwrite),
+            Action::ConflictSkip => self.answer_conflict(Choice::Skip),
+            Action::ConflictRename => self.answer_conflict(Choice::Rename),
+            Action::ConflictNewer => self.answer_conflict(Choice::Newer),
+            Action::UploadAnyway => self.answer_conflict(Choice::Anyway),
+            Action::ApplyToAll => self.apply_to_all = !self.apply_to_all,
+        }
+    }
+
+    /// The one-line key hint, trimmed to `width` columns. The help entry is
+    /// always kept so the full list stays reachable.
+    fn get_help_text(&self, width: usize) -> String {
+        let key = |action: Action| self.keymap.primary(action).unwrap_or_else(|| "-".into());
+        let entry = |action: Action, label: &str| format!("[{}] {}", key(action), label);
+
+        match self.input_mode {
+            InputMode::Uploading => format!("{} {}", entry(Action::Confirm, "Confirm Upload"), entry(Action::Cancel, "Cancel")),
+            InputMode::Downloading => format!("{} {}", entry(Action::Confirm, "Confirm Download"), entry(Action::Cancel, "Cancel")),
+            InputMode::Filtering => format!("{} {}", entry(Action::Confirm, "Keep Filter"), entry(Action::Cancel, "Clear")),
+            InputMode::SyncReview => format!("{} {}", entry(Action::Confirm, "Run Sync"), entry(Action::Cancel, "Cancel")),
+            InputMode::Renaming => format!("{} {}", entry(Action::Confirm, "Rename"), entry(Action::Cancel, "Cancel")),
+            InputMode::ConfirmTrash => format!("{} {}", entry(Action::Confirm, "Move to Trash"), entry(Action::Cancel, "Cancel")),
+            InputMode::MakingFolder => format!("{} {}", entry(Action::Confirm, "Create"), entry(Action::Cancel, "Cancel")),
+            InputMode::Tagging => format!("{} {}", entry(Action::Confirm, "Save Tags"), entry(Action::Cancel, "Cancel")),
+            InputMode::ConfirmPurge => format!("{} {}", entry(Action::Confirm, "Delete for Good"), entry(Action::Cancel, "Cancel")),
+            InputMode::Moving => [
+                entry(Action::Move, "Move Here"),
+                entry(Action::Open, "Open"),
+                entry(Action::Back, "Back"),
+                format!("[{}/{}] Nav", key(Action::Down), key(Action::Up)),
+                entry(Action::Cancel, "Cancel"),
+            ]
+            .join(" | "),
+            InputMode::Conflict if matches!(self.conflicts.front().map(|p| &p.subject), Some(Subject::Upload { .. })) => [
+                entry(Action::ConflictSkip, "Skip"),
+                entry(Action::UploadAnyway, "Upload Anyway"),
+                entry(Action::ConflictRename, "Rename"),
+                entry(Action::ApplyToAll, "All"),
+                entry(Action::Cancel, "Abort"),
+            ]
+            .join(" "),
+            InputMode::Conflict => [
+                entry(Action::ConflictOverwrite, "Overwrite"),
+                entry(Action::ConflictSkip, "Skip"),
+                entry(Action::ConflictRename, "Rename"),
+                entry(Action::ConflictNewer, "If Newer"),
+                entry(Action::ApplyToAll, "All"),
+                entry(Action::Cancel, "Abort"),
+            ]
+            .join(" "),
+            InputMode::Command => format!(
+                "{} {} {} {}",
+                entry(Action::Confirm, "Run"),
+                entry(Action::Complete, "Complete"),
+                format_args!("[{}/{}] History", key(Action::HistoryPrev), key(Action::HistoryNext)),
+                entry(Action::Cancel, "Cancel")
+            ),
+            InputMode::Normal if self.dual_pane => {
+                let copy = match self.focus {
+                    Pane::Local => "Upload →",
+                    Pane::Tablet => "← Download",
+                };
+                let mut actions = vec![
+                    entry(Action::Quit, "Quit"),
+                    entry(Action::SwitchPane, "Switch"),
+                    entry(Action::Copy, copy),
+                    entry(Action::Mark, "Mark"),
+                    entry(Action::Filter, "Filter"),
+                    format!("[{}/{}] Nav", key(Action::Down), key(Action::Up)),
+                    entry(Action::TogglePanes, "Single Pane"),
+                    entry(Action::Palette, "Commands"),
+                ];
+
+                let help = entry(Action::Help, "Help");
+                while actions.len() > 1 && actions.join(" | ").chars().count() + help.chars().count() + 3 > width {
+                    actions.pop();
+                }
+                actions.push(help);
+                actions.join(" | ")
+            }
+            InputMode::Normal => {
+                let mut actions = vec![
+                    entry(Action::Quit, "Quit"),
+                    entry(Action::Upload, "Upload"),
+                    entry(Action::Refresh, "Refresh"),
+                    format!("[{}/{}] Nav", key(Action::Down), key(Action::Up)),
+                ];
+
+                if self.view == Some(Library::Trash) {
+                    actions.push(entry(Action::Restore, "Restore"));
+                    actions.push(entry(Action::Purge, "Delete for Good"));
+                }
+                if self.view.is_some() {
+                    actions.push(entry(Action::Back, "Leave View"));
+                } else if !self.history.is_empty() {
+                    actions.push(entry(Action::Back, "Back"));
+                }
+
+                if let Some(i) = self.state.selected() {
+                    if let Some(item) = self.items.get(i) {
+                        if item.is_folder() {
+                            actions.push(entry(Action::Open, "Open"));
+                        } else {
+                            actions.push(entry(Action::Download, "Download"));
+                        }
+                    }
+                }
+
+                actions.push(entry(Action::Palette, "Commands"));
+
+                let help = entry(Action::Help, "Help");
+                while actions.len() > 1 && actions.join(" | ").chars().count() + help.chars().count() + 3 > width {
+                    actions.pop();
+                }
+                actions.push(help);
+                actions.join(" | ")
+            }
+        }
+    }
+}
+
+// --- Transfer Helpers ---
+
+/// Walks `path` (folder names separated by `/`, `..` allowed) from `trail`,
+/// the `(id, name)` pairs of the starting folder, and returns the new trail.
+/// Names match exactly first, then ignoring case.
+async fn resolve_trail(backend: &dyn backend::Backend, mut trail: Vec<(String, String)>, path: &str) -> Result<Vec<(String, String)>> {
+    for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if component == ".." {
+            trail.pop();
+            continue;
+        }
+        let parent = trail.last().map(|(id, _)| id.clone());
+        let children = backend.list(&parent).await?;
+        let folders = || children.iter().filter(|c| c.is_folder());
+        let folder = folders()
+            .find(|c| c.visible_name == component)
+            .or_else(|| folders().find(|c| c.visible_name.eq_ignore_ascii_case(component)))
+            .ok_or_else(|| anyhow::anyhow!("No folder named '{}'", component))?;
+        trail.push((folder.id.clone(), folder.visible_name.clone()));
+    }
+    Ok(trail)
+}
+
+/// Walks `path` from `trail` like `resolve_trail`, creating each folder
+/// that does not exist yet (as `mkdir -p` does), and returns how many
+/// folders were missing. Names must match exactly, since the
+/// tablet allows folders that differ only in case. Without `create`, stops
+/// at the first missing folder and counts it and those below it.
+async fn make_folders(
+    backend: &dyn backend::Backend,
+    mut trail: Vec<(String, String)>,
+    path: &str,
+    create: bool,
+) -> Result<usize> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty() && *c != ".").collect();
+    let mut missing = 0;
+    for (i, component) in components.iter().enumerate() {
+        if *component == ".." {
+            trail.pop();
+            continue;
+        }
+        let parent = trail.last().map(|(id, _)| id.clone());
+        let children = backend.list(&parent).await?;
+        let id = match children.iter().find(|c| c.is_folder() && c.visible_name == *component) {
+            Some(folder) => folder.id.clone(),
+            None if !create => return Ok(components.len() - i),
+            None => {
+                missing += 1;
+                backend.mkdir(&parent, component).await?
+            }
+        };
+        trail.push((id, component.to_string()));
+    }
+    Ok(missing)
+}
+
+/// Shared state of one download (a file or a whole folder tree).
+struct DownloadJob {
+    backend: Arc<dyn backend::Backend>,
+    tx: mpsc::Sender<AppMessage>,
+    /// The answer applied to every conflict; `None` asks each time.
+    choice: Mutex<Option<Choice>>,
+    skipped: AtomicUsize,
+    ascii_only: bool,
+    template: naming::Template,
+    flatten: bool,
+    /// Every path written so far, relative to the download root.
+    names: Mutex<filename::Siblings>,
+    hooks: Vec<HookConfig>,
+    /// Tablet folder the download started in, as `/A/B`.
+    tablet_dir: String,
+    /// Set for dry runs, which collect files here instead of writing them.
+    preview: Option<Mutex<dryrun::Preview>>,
+    /// List each file in the download directory's checksum manifest.
+    checksums: bool,
+}
+
+impl DownloadJob {
+    /// Decides where an existing `target` should be written, or `None` to
+    /// leave it alone.
+    async fn resolve_conflict(&self, item: &Item, target: PathBuf) -> Result<Option<PathBuf>> {
+        if !target.exists() {
+            return Ok(Some(target));
+        }
+
+        let tablet_newer = conflict::tablet_is_newer(item.modified_at(), &target);
+        let sticky = *self.choice.lock().unwrap();
+        let choice = match sticky {
+            Some(choice) => choice,
+            None => {
+                let (choice, all) = ask_conflict(&self.tx, target.clone(), Subject::Download { tablet_newer }).await;
+                if all {
+                    *self.choice.lock().unwrap() = Some(choice);
+                }
+                choice
+            }
+        };
+
+        let resolved = match choice {
+            Choice::Overwrite | Choice::Anyway => Some(target),
+            Choice::Skip => None,
+            Choice::Rename => Some(conflict::numbered_path(&target)),
+            Choice::Newer => tablet_newer.unwrap_or(false).then_some(target),
+            Choice::Abort => return Err(anyhow::anyhow!("cancelled at {}", target.display())),
+        };
+        if resolved.is_none() {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(resolved)
+    }
+}
+
+/// Puts a conflict question to the UI and waits for the answer, with
+/// whether it applies to the rest of the job. A closed UI counts as abort.
+async fn ask_conflict(tx: &mpsc::Sender<AppMessage>, path: PathBuf, subject: Subject) -> (Choice, bool) {
+    let (prompt, reply) = ConflictPrompt::new(path, subject);
+    if tx.send(AppMessage::Conflict(prompt)).await.is_err() {
+        return (Choice::Abort, false);
+    }
+    reply.await.unwrap_or((Choice::Abort, false))
+}
+
+/// Downloads `item` below `root`. `folders` is where it sits on the tablet
+/// relative to the download, including the item itself for folders;
+/// `tablet_path` is its full path on the tablet.
+fn download_recursive(job: Arc<DownloadJob>, item: Item, root: PathBuf, folders: Vec<String>, tablet_path: String) -> BoxFuture<'static, Result<()>> {
+    Box::pin(async move {
+        if item.is_folder() {
+            let children = job.backend.list(&Some(item.id)).await?;
+            for child in children {
+                let mut path = folders.clone();
+                if child.is_folder() {
+                    path.push(child.visible_name.clone());
+                }
+                let child_tablet_path = format!("{}/{}", tablet_path, child.visible_name);
+                download_recursive(job.clone(), child, root.clone(), path, child_tablet_path).await?;
+            }
+        } else {
+            let relative = job.template.render(&item.fields(&folders), job.flatten, job.ascii_only);
+            let relative = job.names.lock().unwrap().claim(relative);
+            fetch_file(&job, &item, &root, root.join(relative), tablet_path).await?;
+        }
+        Ok(())
+    })
+}
+
+/// Downloads one document and runs the download hooks on the outcome.
+async fn fetch_file(job: &DownloadJob, item: &Item, root: &Path, target_path: PathBuf, tablet_path: String) -> Result<Option<PathBuf>> {
+    if let Some(preview) = &job.preview {
+        let conflict = target_path.exists().then(|| {
+            let tablet_newer = conflict::tablet_is_newer(item.modified_at(), &target_path);
+            conflict::describe(*job.choice.lock().unwrap(), &target_path, tablet_newer)
+        });
+        preview.lock().unwrap().files.push(dryrun::Planned {
+            source: tablet_path,
+            target: target_path.display().to_string(),
+            size: item.size_bytes(),
+            conflict,
+        });
+        return Ok(None);
+    }
+
+    let result = write_file(job, item, root, target_path.clone()).await;
+    let (status, local_path) = match &result {
+        Ok(Some(written)) => (hooks::Status::Ok, written.clone()),
+        Ok(None) => (hooks::Status::Skipped, target_path),
+        Err(_) => (hooks::Status::Failed, target_path),
+    };
+    let transfer = Transfer {
+        event: HookEvent::Download,
+        status,
+        local_path,
+        tablet_path,
+        guid: item.id.clone(),
+        format: item.file_type.clone().unwrap_or_default(),
+    };
+    spawn_hooks(job.hooks.clone(), transfer, job.tx.clone());
+    result
+}
+
+/// Streams one document's PDF to `target_path`, after checking for an
+/// existing file, and lists it in the manifest in `root`. Returns where it
+/// was written, if anywhere.
+async fn write_file(job: &DownloadJob, item: &Item, root: &Path, target_path: PathBuf) -> Result<Option<PathBuf>> {
+    let Some(target_path) = job.resolve_conflict(item, target_path).await? else {
+        return Ok(None);
+    };
+
+    if let Some(parent) = target_path.parent() {
+        if !parent.exists() {
+             tokio::fs::create_dir_all(parent).await?;
+        }
+    }
+
+    let sha256 = stream_pdf(job.backend.as_ref(), item, &target_path).await?;
+    if job.checksums {
+        integrity::record(root, &target_path, &sha256)?;
+    }
+    Ok(Some(target_path))
+}
+
+/// Streams the PDF of `item` into `target_path` and returns its SHA-256.
+/// The target is only replaced once the whole body has arrived and looks
+/// like a complete PDF.
+async fn stream_pdf(backend: &dyn backend::Backend, item: &Item, target_path: &Path) -> Result<String> {
+    let body = backend.download(item).await?;
+    let expected = body.len;
+
+    let temp = integrity::temp_path(target_path);
+    let written = async {
+        use tokio::io::AsyncWriteExt;
+        let mut file = tokio::fs::File::create(&temp).await?;
+        let mut stream = body.chunks;
+        let mut hasher = Sha256::new();
+        let mut received = 0u64;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            received += chunk.len() as u64;
+            file.write_all(&chunk).await?;
+        }
+        // tokio finishes writes in the background; wait for them, then for
+        // the disk, so the rename never exposes a file that is not there yet
+        file.flush().await?;
+        file.sync_all().await?;
+
+        if let Some(expected) = expected.filter(|&n| n != received) {
+            return Err(anyhow::anyhow!("connection closed after {} of {} bytes", received, expected));
+        }
+        integrity::check_pdf(&temp)?;
+        anyhow::Ok(format!("{:x}", hasher.finalize()))
+    };
+
+    match written.await {
+        Ok(sha256) => {
+            tokio::fs::rename(&temp, target_path).await?;
+            Ok(sha256)
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&temp).await;
+            Err(e)
+        }
+    }
+}
+
+async fn download_selection(job: Arc<DownloadJob>, item: Item, dest_path: String) -> Result<String> {
+    let output_path = Path::new(&dest_path);
+    let tablet_path = format!("{}/{}", job.tablet_dir, item.visible_name);
+    let is_dir_target = dest_path.ends_with('/') || dest_path.ends_with(std::path::MAIN_SEPARATOR) || output_path.is_dir();
+
+    if is_dir_target {
+        if !output_path.exists() {
+             return Err(anyhow::anyhow!("Directory '{}' does not exist.", dest_path));
+        }
+        if item.is_folder() {
+            let folders = vec![item.visible_name.clone()];
+            download_recursive(job, item, output_path.to_path_buf(), folders, tablet_path).await?;
+            return Ok(dest_path);
+        }
+        let relative = job.template.render(&item.fields(&[]), job.flatten, job.ascii_only);
+        let target = output_path.join(relative);
+        let written = fetch_file(&job, &item, output_path, target.clone(), tablet_path).await?;
+        // A skipped file reports where it would have gone
+        return Ok(written.unwrap_or(target).to_string_lossy().to_string());
+    }
+
+    // A file-like path names the file itself, or the folder for a folder download
+    let parent = match output_path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    if !parent.exists() {
+        return Err(anyhow::anyhow!("Directory '{}' does not exist.", parent.display()));
+    }
+    if item.is_folder() {
+        let name = output_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        download_recursive(job, item, parent.to_path_buf(), vec![name], tablet_path).await?;
+    } else {
+        fetch_file(&job, &item, parent, output_path.to_path_buf(), tablet_path).await?;
+    }
+    Ok(dest_path)
+}
+
+/// Runs `hooks` one after another in the background, reporting each.
+fn spawn_hooks(hooks: Vec<HookConfig>, transfer: Transfer, tx: mpsc::Sender<AppMessage>) {
+    if hooks.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        for hook in hooks {
+            let run = hooks::run(hook, transfer.clone()).await;
+            let _ = tx.send(AppMessage::HookFinished(run)).await;
+        }
+    });
+}
+
+/// Notes a finished upload in the ledger. A ledger that cannot be written
+/// only weakens later duplicate checks, so the upload still counts.
+fn record_upload(ledger: &mut ledger::Ledger, path: &Path, tablet_folder: &str) {
+    let Ok(sha256) = ledger::sha256_file(path) else { return };
+    let _ = ledger.record(ledger::UploadRecord {
+        sha256,
+        size: std::fs::metadata(path).map(|m| m.len()).unwrap_or_default(),
+        local_path: path.to_path_buf(),
+        tablet_folder: tablet_folder.to_string(),
+        uploaded_at: Utc::now(),
+    });
+}
+
+// --- Main ---
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = match Cli::parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let config = match Config::load(cli.config.as_deref()).and_then(|config| config.with_profile(cli.profile.clone())) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let outcome = match cli.command {
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            Ok(true)
+        }
+        Command::Config => config.describe().map(|text| {
+            print!("{}", text);
+            true
+        }),
+        Command::Watch => commands::run_watch(&config, cli.dry_run).await.map(|()| true),
+        Command::Verify { dir } => commands::run_verify(&config, dir),
+        Command::Mkdir { path } => commands::run_mkdir(&config, &path.unwrap_or_default(), cli.dry_run).await.map(|()| true),
+        Command::Render { files } => Ok(commands::run_render(&files)),
+        Command::Notes { path, dir, format } => {
+            let format = format.unwrap_or(config.notes.format);
+            commands::run_notes(&config, &path.unwrap_or_default(), dir, format, cli.dry_run).await
+        }
+        Command::Device(request) => commands::run_device(&config, request, cli.dry_run).await.map(|()| true),
+        Command::Sync { name, yes } => commands::run_sync(&config, name.as_deref(), cli.dry_run, yes).await,
+        Command::Tui => return run_tui(config, cli.dry_run).await,
+    };
+    match outcome {
+        Ok(true) => Ok(()),
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The full-screen browser, until it is quit.
+async fn run_tui(config: Config, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    // Setup terminal
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mouse = config.ui.mouse;
+    if mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    // Create App
+    let (tx, mut rx) = mpsc::channel(10);
+    let keymap = Keymap::new(&config.keys)?;
+    let watching = start_watchers(&config, dry_run, &tx);
+    let mut app = AppLogic::new(tx, config, keymap);
+    app.dry_run = dry_run;
+
+    // Initial fetch
+    app.refresh();
+    if let Err(e) = watching {
+        app.status_msg = format!("Error: watch folders disabled: {:#}", e);
+    }
+
+    let res = run_app(&mut terminal, &mut app, &mut rx).await;
+
+    // Restore terminal
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    if mouse {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
     if let Err(err) = res {
@@ -430,12 +2560,54 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Starts the watch folders for the TUI, passing their events on to `tx`,
+/// and returns how many were started. Watch folders upload on their own,
+/// so a dry run starts none.
+fn start_watchers(config: &Config, dry_run: bool, tx: &mpsc::Sender<AppMessage>) -> Result<usize> {
+    if dry_run {
+        return Ok(0);
+    }
+    let mut events = spawn_watchers(config)?;
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let _ = tx.send(AppMessage::Watch(event)).await;
+        }
+    });
+    Ok(config.watch.len())
+}
+
+/// Starts a task per `[[watch]]` entry, all reporting on one channel.
+fn spawn_watchers(config: &Config) -> Result<mpsc::Receiver<watch::WatchEvent>> {
+    let (tx, rx) = mpsc::channel(16);
+    if config.watch.is_empty() {
+        return Ok(rx);
+    }
+    let backend = config.profile()?.1.open();
+    let ledger = Arc::new(Mutex::new(ledger::Ledger::open_default()?));
+    for entry in &config.watch {
+        tokio::spawn(watch::run(
+            entry.clone(),
+            backend.clone(),
+            ledger.clone(),
+            config.uploads.on_duplicate,
+            tx.clone(),
+        ));
+    }
+    Ok(rx)
+}
+
+/// `notes.directory`, falling back to the download directory.
+fn notes_directory(config: &Config) -> Option<String> {
+    config.notes.directory.clone().or_else(|| config.downloads.directory.clone())
+}
+
 async fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     app: &mut AppLogic,
     rx: &mut mpsc::Receiver<AppMessage>,
 ) -> io::Result<()> {
-    let tick_rate = Duration::from_millis(100);
+    let tick_rate = Duration::from_millis(app.config.ui.tick_rate_ms);
     let mut last_tick = std::time::Instant::now();
 
     loop {
@@ -446,36 +2618,13 @@ async fn run_app<B: Backend>(
             .unwrap_or_else(|| Duration::from_secs(0));
 
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match app.input_mode {
-                        InputMode::Normal => match key.code {
-                            KeyCode::Char('q') => return Ok(()),
-                            KeyCode::Char('j') | KeyCode::Down => app.next(),
-                            KeyCode::Char('k') | KeyCode::Up => app.previous(),
-                            KeyCode::Char('l') | KeyCode::Enter => app.enter(),
-                            KeyCode::Char('h') | KeyCode::Backspace | KeyCode::Left => app.go_back(),
-                            KeyCode::Char('d') => app.download(),
-                            KeyCode::Char('u') => app.start_upload(),
-                            KeyCode::Char('r') => app.refresh(),
-                            _ => {}
-                        },
-                        InputMode::Uploading => match key.code {
-                            KeyCode::Enter => app.confirm_upload(),
-                            KeyCode::Esc => app.cancel_upload(),
-                            KeyCode::Char(c) => app.input_buffer.push(c),
-                            KeyCode::Backspace => { app.input_buffer.pop(); },
-                            _ => {}
-                        },
-                        InputMode::Downloading => match key.code {
-                            KeyCode::Enter => app.confirm_download(),
-                            KeyCode::Esc => app.cancel_download(),
-                            KeyCode::Char(c) => app.input_buffer.push(c),
-                            KeyCode::Backspace => { app.input_buffer.pop(); },
-                            _ => {}
-                        },
-                    }
-                }
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => app.handle_key(key),
+                Event::Mouse(mouse) => app.handle_mouse(mouse),
+                _ => {}
+            }
+            if app.should_quit {
+                return Ok(());
             }
         }
 
@@ -486,23 +2635,96 @@ async fn run_app<B: Backend>(
         // Process async messages
         while let Ok(msg) = rx.try_recv() {
             match msg {
+                // A folder listing that was on its way when a library view
+                // opened, or the other way round, is stale
+                AppMessage::DocumentsFetched(_) if app.view.is_some() => {}
+                AppMessage::LibraryFetched(_) if app.view.is_none() => {}
+                AppMessage::LibraryFetched(items) => {
+                    app.set_library(items);
+                    if app.status_msg == LOADING {
+                        app.status_msg = format!("Loaded {} items.", app.items.len());
+                    }
+                },
                 AppMessage::DocumentsFetched(items) => {
-                    app.items = items;
-                    app.status_msg = format!("Loaded {} items.", app.items.len());
+                    app.set_items(items);
+                    // Keep news that arrived while loading, like a finished edit
+                    if app.status_msg == LOADING {
+                        app.status_msg = format!("Loaded {} items.", app.items.len());
+                    }
                 },
-                AppMessage::DownloadComplete(name, path) => {
-                    app.status_msg = format!("Downloaded {} to {}.", name, path);
+                AppMessage::Navigated(trail) => {
+                    app.navigate_to(trail);
+                },
+                AppMessage::DownloadComplete(name, path, skipped) => {
+                    app.status_msg = match skipped {
+                        0 => format!("Downloaded {} to {}.", name, path),
+                        n => format!("Downloaded {} to {} ({} existing file(s) skipped).", name, path, n),
+                    };
+                    app.log(true, app.status_msg.clone(), Vec::new());
+                    if app.dual_pane {
+                        let err = app.local.reload();
+                        app.local_result(err);
+                    }
                 },
                 AppMessage::UploadComplete(name) => {
+                    app.log(true, format!("Uploaded {}.", name), Vec::new());
                     app.status_msg = format!("Uploaded {}. Refreshing...", name);
                     app.refresh();
                 },
+                AppMessage::UploadSkipped(path, why) => {
+                    let msg = format!("Skipped {}: {}.", path, why);
+                    app.log(true, msg.clone(), Vec::new());
+                    app.status_msg = msg;
+                },
+                AppMessage::ViewReady(path) => {
+                    app.view_request = Some(path);
+                },
+                AppMessage::HookFinished(run) => {
+                    app.record_hook(run);
+                },
+                AppMessage::Watch(event) => {
+                    app.record_watch(event);
+                },
+                AppMessage::Conflict(prompt) => {
+                    app.conflicts.push_back(prompt);
+                },
+                AppMessage::Preview(preview) => {
+                    app.show_preview(preview);
+                },
+                AppMessage::SyncPlanned(plans) => {
+                    app.review_sync(plans);
+                },
+                AppMessage::Edited(what, errors) => {
+                    app.record_edit(what, errors);
+                }
+                AppMessage::NotesExported(dir, report) => {
+                    app.record_notes(dir, report);
+                }
+                AppMessage::DeviceDone(outcome, listing) => {
+                    app.record_device(outcome, listing);
+                }
+                AppMessage::SyncDone(name, report) => {
+                    app.record_sync(name, report);
+                },
                 AppMessage::Error(e) => {
                     app.status_msg = format!("Error: {}", e);
                 },
 
             }
         }
+        // Conflicts wait until any open prompt is done with
+        app.next_conflict();
+
+        if let Some(path) = app.view_request.take() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            app.status_msg = match viewer::open(&app.config.viewer, &path, app.config.ui.mouse).await {
+                Ok(()) => format!("Opened {}.", name),
+                Err(e) => format!("Error: could not open {}: {}", name, e),
+            };
+            if app.config.viewer.terminal {
+                terminal.clear()?;
+            }
+        }
     }
 }
 
@@ -515,33 +2737,124 @@ fn ui(f: &mut Frame, app: &mut AppLogic) {
         ])
         .split(f.area());
 
+    let (local_area, list_area) = if app.dual_pane {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(main_chunks[0]);
+        (panes[0], panes[1])
+    } else {
+        (Rect::default(), main_chunks[0])
+    };
+    app.list_area = list_area;
+    app.local_area = local_area;
+    app.click_targets.clear();
+
+    if app.dual_pane {
+        render_local_pane(f, app, local_area);
+    }
+
+    let colors = &app.config.ui.colors;
+    let icons = &app.config.ui.icons;
+
     // List
     let items: Vec<ListItem> = app
         .items
         .iter()
         .map(|i| {
-            let icon = if i.is_folder() { "📁" } else { "📄" };
-            let content = format!("{} {}", icon, i.visible_name);
-            ListItem::new(Line::from(content))
+            let icon = if i.is_folder() { &icons.folder } else { &icons.document };
+            let renaming = matches!(app.input_mode, InputMode::Renaming) && app.editing.first().is_some_and(|e| e.id == i.id);
+            if renaming {
+                let content = format!("{} {}▏", icon, app.input_buffer);
+                return ListItem::new(Line::from(content)).style(Style::default().bg(colors.input_bg.0).fg(colors.input_fg.0));
+            }
+            let mut spans = vec![Span::raw(format!("{} {}", icon, i.visible_name))];
+            if i.pinned {
+                spans.push(Span::styled(format!(" {}", icons.star), Style::default().fg(colors.star_fg.0)));
+            }
+            // Page tags are set in italics after the document's own
+            let chip = Style::default().fg(colors.tag_fg.0).bg(colors.tag_bg.0);
+            for tag in &i.tags {
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(format!(" {} ", tag), chip));
+            }
+            for tag in i.page_tags.iter().filter(|t| !i.tags.contains(t)) {
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(format!(" {} ", tag), chip.add_modifier(Modifier::ITALIC)));
+            }
+            let dir = app.item_dir(i);
+            let place = if dir.is_empty() { "/" } else { &dir };
+            let place = match &app.view {
+                Some(Library::Trash) => {
+                    let from = if i.origin.is_some() { format!("from {}", place) } else { "origin unknown".to_string() };
+                    match i.modified_at() {
+                        Some(at) => format!("{}, deleted {}", from, at.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+                        None => from,
+                    }
+                }
+                Some(_) => place.to_string(),
+                None => String::new(),
+            };
+            if !place.is_empty() {
+                spans.push(Span::styled(format!("  {}", place), Style::default().add_modifier(Modifier::DIM)));
+            }
+            let style = if app.marked.contains(&i.id) {
+                Style::default().fg(colors.marked_fg.0)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(spans)).style(style)
         })
         .collect();
 
+    // Breadcrumb title; each segment is clickable
+    let mut title = vec![Span::raw(" "), Span::raw("Documents")];
+    let mut crumb_areas = vec![(1, 0)];
+    if let Some(view) = &app.view {
+        title.push(Span::raw(format!(" : {}", view.title())));
+    } else if app.crumbs.is_empty() {
+        title.push(Span::raw(" / (Root)"));
+    }
+    let crumbs: &[String] = if app.view.is_some() { &[] } else { &app.crumbs };
+    for (depth, name) in crumbs.iter().enumerate() {
+        title.push(Span::raw(" / "));
+        crumb_areas.push((title.len(), depth + 1));
+        title.push(Span::raw(name.as_str()));
+    }
+    if !app.filter.is_empty() {
+        title.push(Span::raw(format!(" [/{}]", app.filter)));
+    }
+    if !app.config.profiles.is_empty() {
+        title.push(Span::raw(format!(" @{}", app.profile)));
+    }
+    title.push(Span::raw(" "));
+
+    let mut x = list_area.x + 1;
+    for (i, span) in title.iter().enumerate() {
+        let width = span.width() as u16;
+        if let Some((_, depth)) = crumb_areas.iter().find(|(index, _)| *index == i) {
+            let area = Rect { x, y: list_area.y, width, height: 1 }.intersection(list_area);
+            app.click_targets.push((area, ClickTarget::Crumb(*depth)));
+        }
+        x = x.saturating_add(width);
+    }
+
+    let mut list_block = Block::default().borders(Borders::ALL).title(Line::from(title));
+    if app.dual_pane && app.focus == Pane::Tablet {
+        list_block = list_block.border_style(Style::default().fg(colors.focus_border.0));
+    }
+
     let items_list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title(
-            match &app.current_guid {
-                Some(id) => format!(" Documents / {} ", id),
-                None => " Documents / (Root) ".to_string(),
-            }
-        ))
-        .highlight_style(Style::default().add_modifier(Modifier::BOLD).bg(Color::DarkGray))
+        .block(list_block)
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).bg(colors.highlight_bg.0))
         .highlight_symbol("> ");
 
-    f.render_stateful_widget(items_list, main_chunks[0], &mut app.state);
+    f.render_stateful_widget(items_list, list_area, &mut app.state);
 
     // Bottom Box (Status + Keybinds)
     let bottom_block = Block::default()
         .borders(Borders::ALL)
-        .title(" Status ");
+        .title(if app.dry_run { " Status [DRY RUN] " } else { " Status " });
     
     let bottom_area = main_chunks[1];
     f.render_widget(bottom_block.clone(), bottom_area);
@@ -558,35 +2871,439 @@ fn ui(f: &mut Frame, app: &mut AppLogic) {
 
     // Status Bar
     let status_style = match app.input_mode {
-        InputMode::Uploading | InputMode::Downloading => Style::default().bg(Color::Blue).fg(Color::White),
-        InputMode::Normal => Style::default().fg(Color::White),
+        InputMode::Uploading
+        | InputMode::Downloading
+        | InputMode::Command
+        | InputMode::Filtering
+        | InputMode::Conflict
+        | InputMode::SyncReview
+        | InputMode::Renaming
+        | InputMode::Moving
+        | InputMode::ConfirmTrash
+        | InputMode::MakingFolder
+        | InputMode::Tagging
+        | InputMode::ConfirmPurge => Style::default().bg(colors.input_bg.0).fg(colors.input_fg.0),
+        InputMode::Normal => Style::default().fg(colors.text.0),
     };
-    let status = Paragraph::new(app.status_msg.clone()).style(status_style);
+    let status_text = match app.input_mode {
+        InputMode::Command => format!(":{}", app.palette.input),
+        InputMode::Filtering => format!("/{}", app.input_buffer),
+        _ => app.status_msg.clone(),
+    };
+    let status = Paragraph::new(status_text).style(status_style);
     f.render_widget(status, bottom_chunks[0]);
 
     // Keybinds Bar
-    let help_text = app.get_help_text();
-    let help = Paragraph::new(help_text).style(Style::default().fg(Color::White));
+    let help_text = app.get_help_text(bottom_chunks[1].width as usize);
+    let help = Paragraph::new(help_text).style(Style::default().fg(colors.text.0));
     f.render_widget(help, bottom_chunks[1]);
 
     // Input Modal
-    if let InputMode::Uploading | InputMode::Downloading = app.input_mode {
+    if let InputMode::Uploading | InputMode::Downloading | InputMode::MakingFolder | InputMode::Tagging = app.input_mode {
         let area = centered_rect(60, 20, f.area());
         f.render_widget(Clear, area); // Clear background
 
-        let title = if let InputMode::Uploading = app.input_mode { " Upload File " } else { " Download File " };
+        let title = match app.input_mode {
+            InputMode::Uploading => " Upload File ",
+            InputMode::MakingFolder => " New Folder ",
+            InputMode::Tagging => " Tags ",
+            _ => " Download File ",
+        };
 
         let input_block = Block::default()
             .borders(Borders::ALL)
             .title(title)
-            .style(Style::default().bg(Color::Black));
-        
+            .style(Style::default().bg(colors.modal_bg.0));
+        let inner = input_block.inner(area);
+        f.render_widget(input_block, area);
+
+        let modal_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(inner);
+
         let input_text = Paragraph::new(app.input_buffer.clone())
-            .block(input_block)
             .wrap(Wrap { trim: true });
-            
-        f.render_widget(input_text, area);
+        f.render_widget(input_text, modal_chunks[0]);
+
+        render_buttons(f, app, modal_chunks[1], &[(" OK ", Action::Confirm), (" Cancel ", Action::Cancel)]);
+    }
+
+    if let InputMode::Command = app.input_mode {
+        render_palette(f, app, main_chunks[0]);
+    }
+
+    if let InputMode::Conflict = app.input_mode {
+        render_conflict(f, app);
+    }
+
+    if let InputMode::SyncReview = app.input_mode {
+        render_sync_review(f, app);
+    }
+
+    if let InputMode::ConfirmTrash | InputMode::ConfirmPurge = app.input_mode {
+        render_trash_confirm(f, app);
+    }
+
+    match app.overlay {
+        Some(Overlay::Help) => render_help(f, app),
+        Some(Overlay::Log) => render_log(f, app),
+        Some(Overlay::Preview) => render_preview(f, app),
+        Some(Overlay::Device) => render_device(f, app),
+        None => {}
+    }
+}
+
+fn render_local_pane(f: &mut Frame, app: &mut AppLogic, area: Rect) {
+    let colors = &app.config.ui.colors;
+    let icons = &app.config.ui.icons;
+
+    let entries: Vec<ListItem> = app
+        .local
+        .entries
+        .iter()
+        .map(|e| {
+            let icon = if e.is_dir { &icons.folder } else { &icons.document };
+            let style = if app.local.marked.contains(&e.path) {
+                Style::default().fg(colors.marked_fg.0)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(format!("{} {}", icon, e.name))).style(style)
+        })
+        .collect();
+
+    let mut title = format!(" Local: {} ", app.local.dir.display());
+    if !app.local.filter.is_empty() {
+        title.push_str(&format!("[/{}] ", app.local.filter));
+    }
+    let mut block = Block::default().borders(Borders::ALL).title(title);
+    if app.focus == Pane::Local {
+        block = block.border_style(Style::default().fg(colors.focus_border.0));
+    }
+
+    let list = List::new(entries)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).bg(colors.highlight_bg.0))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, area, &mut app.local.state);
+}
+
+/// The question for the oldest pending transfer conflict.
+fn render_conflict(f: &mut Frame, app: &mut AppLogic) {
+    let Some(prompt) = app.conflicts.front() else { return };
+    let (title, detail, buttons): (_, _, &[(&str, Action)]) = match &prompt.subject {
+        Subject::Download { tablet_newer } => {
+            let age = match tablet_newer {
+                Some(true) => "The tablet copy is newer.",
+                Some(false) => "The local copy is newer or the same age.",
+                None => "Could not compare modification times.",
+            };
+            (" File Exists ", "already exists. ".to_string() + age, &[
+                (" Overwrite ", Action::ConflictOverwrite),
+                (" Skip ", Action::ConflictSkip),
+                (" Rename ", Action::ConflictRename),
+                (" If newer ", Action::ConflictNewer),
+                (" Abort ", Action::Cancel),
+            ])
+        }
+        Subject::Upload { duplicate } => (" Possible Duplicate ", format!("is being uploaded, but {}.", duplicate), &[
+            (" Skip ", Action::ConflictSkip),
+            (" Upload anyway ", Action::UploadAnyway),
+            (" Rename ", Action::ConflictRename),
+            (" Abort ", Action::Cancel),
+        ]),
+    };
+    let text = vec![
+        Line::from(prompt.path.display().to_string()),
+        Line::from(detail),
+        Line::from(format!(
+            "[{}] Apply to all remaining conflicts ({})",
+            if app.apply_to_all { "x" } else { " " },
+            app.keymap.describe(Action::ApplyToAll)
+        )),
+    ];
+
+    let area = centered_rect(70, 30, f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .style(Style::default().bg(app.config.ui.colors.modal_bg.0));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(inner);
+    f.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), chunks[0]);
+
+    render_buttons(f, app, chunks[1], buttons);
+}
+
+/// The pending sync plans, one summary line per pair followed by its steps.
+fn render_sync_review(f: &mut Frame, app: &mut AppLogic) {
+    let area = centered_rect(80, 60, f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Sync Plan ")
+        .style(Style::default().bg(app.config.ui.colors.modal_bg.0));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(inner);
+
+    let mut lines = Vec::new();
+    for plan in &app.sync_plans {
+        lines.push(Line::from(plan.summary()).style(Style::default().add_modifier(Modifier::BOLD)));
+        lines.extend(plan.visible().map(|step| Line::from(format!("  {}", step))));
+    }
+    let room = chunks[0].height as usize;
+    if lines.len() > room {
+        let more = lines.len() - room + 1;
+        lines.truncate(room.saturating_sub(1));
+        lines.push(Line::from(format!("  ... and {} more", more)));
     }
+    f.render_widget(Paragraph::new(lines), chunks[0]);
+
+    render_buttons(f, app, chunks[1], &[(" Run ", Action::Confirm), (" Cancel ", Action::Cancel)]);
+}
+
+/// Asks before trashing, or purging from the trash, the items in `editing`.
+fn render_trash_confirm(f: &mut Frame, app: &mut AppLogic) {
+    let (title, intro, button) = match app.input_mode {
+        InputMode::ConfirmPurge => (" Delete for Good ", "These will be deleted from the tablet and cannot be restored:", " Delete "),
+        _ => (" Move to Trash ", "These can be restored from the trash:", " Move to Trash "),
+    };
+    let area = centered_rect(60, 40, f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .style(Style::default().bg(app.config.ui.colors.modal_bg.0));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(inner);
+
+    let mut lines = vec![Line::from(intro)];
+    lines.extend(app.editing.iter().map(|item| {
+        let kind = if item.is_folder() { " (folder and everything in it)" } else { "" };
+        Line::from(format!("  {}{}", item.visible_name, kind))
+    }));
+    let room = chunks[0].height as usize;
+    if lines.len() > room {
+        let more = lines.len() - room + 1;
+        lines.truncate(room.saturating_sub(1));
+        lines.push(Line::from(format!("  ... and {} more", more)));
+    }
+    f.render_widget(Paragraph::new(lines), chunks[0]);
+
+    render_buttons(f, app, chunks[1], &[(button, Action::Confirm), (" Cancel ", Action::Cancel)]);
+}
+
+/// A right-aligned row of clickable buttons.
+fn render_buttons(f: &mut Frame, app: &mut AppLogic, area: Rect, buttons: &[(&str, Action)]) {
+    let style = Style::default().bg(app.config.ui.colors.input_bg.0).fg(app.config.ui.colors.input_fg.0);
+    let total: u16 = buttons.iter().map(|(label, _)| Span::raw(*label).width() as u16 + 1).sum();
+    let mut x = area.x + area.width.saturating_sub(total);
+
+    for (label, action) in buttons {
+        let width = Span::raw(*label).width() as u16;
+        let button = Rect { x, y: area.y, width, height: 1 }.intersection(area);
+        f.render_widget(Paragraph::new(*label).style(style), button);
+        app.click_targets.push((button, ClickTarget::Button(*action)));
+        x = x.saturating_add(width + 1);
+    }
+}
+
+/// Suggestions for the command palette, drawn over the bottom of the list.
+fn render_palette(f: &mut Frame, app: &mut AppLogic, list_area: Rect) {
+    let colors = &app.config.ui.colors;
+    let suggestions = app.palette.suggestions(&app.commands);
+    let highlighted = app.palette.highlighted(suggestions.len());
+
+    let height = (suggestions.len() as u16 + 2).min(12).min(list_area.height);
+    let area = Rect {
+        y: list_area.y + list_area.height - height,
+        height,
+        ..list_area
+    };
+    f.render_widget(Clear, area);
+
+    let first_row = area.y + 1;
+    let visible = area.height.saturating_sub(2) as usize;
+    let targets: Vec<(Rect, ClickTarget)> = (0..suggestions.len().min(visible))
+        .map(|i| (Rect { y: first_row + i as u16, height: 1, ..area }, ClickTarget::Suggestion(i)))
+        .collect();
+
+    let rows: Vec<Row> = suggestions
+        .iter()
+        .enumerate()
+        .map(|(i, spec)| {
+            let mut style = if i == highlighted {
+                Style::default().add_modifier(Modifier::BOLD).bg(colors.highlight_bg.0)
+            } else {
+                Style::default()
+            };
+            if spec.action().is_some_and(|action| !app.available(action)) {
+                style = style.fg(Color::DarkGray);
+            }
+            Row::new(vec![
+                format!("{} {}", spec.name, spec.args),
+                spec.description.to_string(),
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let table = Table::new(rows, [Constraint::Length(24), Constraint::Min(0)]).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Commands ")
+            .style(Style::default().bg(colors.modal_bg.0).fg(colors.text.0)),
+    );
+    f.render_widget(table, area);
+    app.click_targets.extend(targets);
+}
+
+fn render_help(f: &mut Frame, app: &mut AppLogic) {
+    let colors = &app.config.ui.colors;
+    let area = centered_rect(80, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let heading = Style::default().add_modifier(Modifier::BOLD);
+    let unavailable = Style::default().fg(Color::DarkGray);
+    let mut rows = Vec::new();
+    for &context in Context::ALL {
+        if !rows.is_empty() {
+            rows.push(Row::new(vec![""]));
+        }
+        rows.push(Row::new(vec![context.title()]).style(heading));
+        for &action in Action::ALL.iter().filter(|a| a.context() == context) {
+            let command = if context == Context::Browse && action != Action::Palette {
+                format!(":{}", CommandSpec::name_for(action))
+            } else {
+                String::new()
+            };
+            let row = Row::new(vec![app.keymap.describe(action), command, action.description().to_string()]);
+            rows.push(if app.available(action) { row } else { row.style(unavailable) });
+        }
+    }
+    rows.push(Row::new(vec![""]));
+    rows.push(Row::new(vec!["With arguments"]).style(heading));
+    for spec in app.commands.iter().filter(|spec| !spec.args.is_empty()) {
+        rows.push(Row::new(vec![String::new(), format!(":{} {}", spec.name, spec.args), spec.description.to_string()]));
+    }
+
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let max_scroll = rows.len().saturating_sub(inner_height);
+    let scroll = (app.overlay_scroll as usize).min(max_scroll);
+    app.overlay_scroll = scroll as u16;
+
+    let help = Table::new(
+        rows.into_iter().skip(scroll),
+        [Constraint::Length(20), Constraint::Length(20), Constraint::Min(0)],
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Help (movement keys scroll, any other key closes) ")
+            .style(Style::default().bg(colors.modal_bg.0).fg(colors.text.0)),
+    );
+    f.render_widget(help, area);
+}
+
+/// Recent transfers and hook runs, newest at the bottom.
+fn render_log(f: &mut Frame, app: &mut AppLogic) {
+    let colors = &app.config.ui.colors;
+    let area = centered_rect(80, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let mut lines = Vec::new();
+    for entry in &app.log {
+        let (mark, color) = if entry.ok { ("ok", Color::Green) } else { ("FAILED", Color::Red) };
+        lines.push(Line::from(vec![
+            Span::raw(format!("{} ", entry.at.format("%H:%M:%S"))),
+            Span::styled(mark, Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::raw(format!(" {}", entry.summary)),
+        ]));
+        lines.extend(entry.detail.iter().map(|l| Line::from(Span::styled(format!("  {}", l), Style::default().add_modifier(Modifier::DIM)))));
+    }
+    if lines.is_empty() {
+        lines.push(Line::from("Nothing has been transferred yet."));
+    }
+
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let max_scroll = lines.len().saturating_sub(inner_height);
+    let scroll = (app.overlay_scroll as usize).min(max_scroll);
+    app.overlay_scroll = scroll as u16;
+
+    let log = Paragraph::new(lines.into_iter().skip(scroll).collect::<Vec<_>>()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Activity Log (movement keys scroll, any other key closes) ")
+            .style(Style::default().bg(colors.modal_bg.0).fg(colors.text.0)),
+    );
+    f.render_widget(log, area);
+}
+
+/// The dry runs since the overlay was last closed.
+fn render_preview(f: &mut Frame, app: &mut AppLogic) {
+    let colors = &app.config.ui.colors;
+    let area = centered_rect(80, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let mut lines = Vec::new();
+    for preview in &app.previews {
+        lines.push(Line::from(Span::styled(preview.to_string(), Style::default().add_modifier(Modifier::BOLD))));
+        lines.extend(preview.lines().into_iter().map(|l| Line::from(format!("  {}", l))));
+    }
+
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let max_scroll = lines.len().saturating_sub(inner_height);
+    let scroll = (app.overlay_scroll as usize).min(max_scroll);
+    app.overlay_scroll = scroll as u16;
+
+    let preview = Paragraph::new(lines.into_iter().skip(scroll).collect::<Vec<_>>()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Dry Run (nothing was transferred) ")
+            .style(Style::default().bg(colors.modal_bg.0).fg(colors.text.0)),
+    );
+    f.render_widget(preview, area);
+}
+
+/// The last templates or splash screen listing.
+fn render_device(f: &mut Frame, app: &mut AppLogic) {
+    let colors = &app.config.ui.colors;
+    let area = centered_rect(80, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let Some(outcome) = &app.device else { return };
+    let mut lines = vec![Line::from(Span::styled(outcome.summary.clone(), Style::default().add_modifier(Modifier::BOLD)))];
+    lines.extend(outcome.lines.iter().map(|l| Line::from(format!("  {}", l))));
+
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let max_scroll = lines.len().saturating_sub(inner_height);
+    let scroll = (app.overlay_scroll as usize).min(max_scroll);
+    app.overlay_scroll = scroll as u16;
+
+    let listing = Paragraph::new(lines.into_iter().skip(scroll).collect::<Vec<_>>()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Templates and Splash Screens (movement keys scroll, any other key closes) ")
+            .style(Style::default().bg(colors.modal_bg.0).fg(colors.text.0)),
+    );
+    f.render_widget(listing, area);
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -607,4 +3324,25 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
             Constraint::Percentage((100 - percent_x) / 2),
         ])
         .split(popup_layout[1])[1]
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn dry_runs_start_no_watchers() {
+        let scans = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.watch.push(watch::WatchConfig {
+            directory: scans.path().display().to_string(),
+            tablet_folder: "/Scans".into(),
+            extensions: vec!["pdf".into()],
+        });
+        // Starting them for real fails on the missing profile
+        config.device.profile = Some("missing".into());
+        let (tx, _rx) = mpsc::channel(1);
+        assert_eq!(start_watchers(&config, true, &tx).unwrap(), 0);
+        assert!(start_watchers(&config, false, &tx).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::filename;
+use chrono::{format::StrftimeItems, format::Item as FormatItem, DateTime, Local, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, path::PathBuf, str::FromStr};
+
+// --- Download naming templates ---
+
+pub const DEFAULT_TEMPLATE: &str = "{path}/{name}.{ext}";
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// Tablet folders between the download root and the document.
+    Path,
+    Name,
+    Guid,
+    Modified,
+    Pages,
+    Format,
+    Ext,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "path" => Field::Path,
+            "name" => Field::Name,
+            "guid" => Field::Guid,
+            "modified" => Field::Modified,
+            "pages" => Field::Pages,
+            "format" => Field::Format,
+            "ext" => Field::Ext,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// A placeholder and its format, e.g. `{modified:%Y}`.
+    Field(Field, Option<String>),
+}
+
+/// A file name pattern such as `{path}/{name}_{modified:%Y-%m-%d}.{ext}`.
+/// `/` separates directories; `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+/// What a template can refer to for one document.
+pub struct Fields<'a> {
+    pub path: &'a [String],
+    pub name: &'a str,
+    pub guid: &'a str,
+    pub modified: Option<DateTime<Utc>>,
+    pub pages: Option<u32>,
+    pub format: Option<&'a str>,
+}
+
+impl Template {
+    /// The path of a downloaded document relative to the download root.
+    /// Every value is sanitized on its own, so a `/` in a document name never
+    /// creates a directory. With `flatten` the directories are folded into
+    /// the file name, joined by ` - `.
+    pub fn render(&self, fields: &Fields, flatten: bool, ascii_only: bool) -> PathBuf {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Field(field, format) => out.push_str(&self.value(*field, format.as_deref(), fields, ascii_only)),
+            }
+        }
+
+        let parts: Vec<String> = out
+            .split('/')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(|part| filename::sanitize(part, ascii_only))
+            .collect();
+        match (flatten, parts.is_empty()) {
+            (_, true) => PathBuf::from(filename::sanitize(fields.name, ascii_only)),
+            (true, false) => PathBuf::from(parts.join(" - ")),
+            (false, false) => parts.iter().collect(),
+        }
+    }
+
+    fn value(&self, field: Field, format: Option<&str>, fields: &Fields, ascii_only: bool) -> String {
+        let clean = |text: &str| filename::sanitize(text, ascii_only);
+        match field {
+            Field::Path => fields.path.iter().map(|p| clean(p)).collect::<Vec<_>>().join("/"),
+            Field::Name => clean(strip_pdf(fields.name)),
+            Field::Guid => fields.guid.to_string(),
+            Field::Modified => fields
+                .modified
+                .map(|t| clean(&t.with_timezone(&Local).format(format.unwrap_or(DEFAULT_DATE_FORMAT)).to_string()))
+                .unwrap_or_else(|| "unknown".into()),
+            Field::Pages => fields.pages.map_or_else(|| "unknown".into(), |n| n.to_string()),
+            Field::Format => fields.format.filter(|f| !f.is_empty()).map_or_else(|| "unknown".into(), clean),
+            // The tablet always exports PDF
+            Field::Ext => "pdf".into(),
+        }
+    }
+}
+
+/// Names of imported PDFs usually carry the extension already.
+fn strip_pdf(name: &str) -> &str {
+    match name.len().checked_sub(4) {
+        Some(cut) if name.is_char_boundary(cut) && name[cut..].eq_ignore_ascii_case(".pdf") && cut > 0 => &name[..cut],
+        _ => name,
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE.parse().expect("default template is valid")
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err("unmatched `}` (write `}}` for a literal brace)".into()),
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => inner.push(c),
+                            None => return Err(format!("unclosed `{{{}`", inner)),
+                        }
+                    }
+                    let (name, format) = match inner.split_once(':') {
+                        Some((name, format)) => (name, Some(format.to_string())),
+                        None => (inner.as_str(), None),
+                    };
+                    let field = Field::parse(name.trim()).ok_or_else(|| {
+                        format!("unknown placeholder `{{{}}}`; use path, name, guid, modified, pages, format or ext", name)
+                    })?;
+                    if let Some(format) = &format {
+                        if field != Field::Modified {
+                            return Err(format!("`{{{}}}` does not take a format", name));
+                        }
+                        if StrftimeItems::new(format).any(|item| item == FormatItem::Error) {
+                            return Err(format!("invalid date format `{}`", format));
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field, format));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        if !segments.iter().any(|s| matches!(s, Segment::Field(Field::Name | Field::Guid, _))) {
+            return Err("must contain `{name}` or `{guid}` so documents get distinct names".into());
+        }
+        Ok(Self { source: source.to_string(), segments })
+    }
+}
+
+impl<'de> Deserialize<'de> for Template {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Template {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::collections::BTreeMap;
+
+    fn render(template: &str, name: &str, flatten: bool, ascii_only: bool) -> String {
+        let path = ["Work".to_string(), "Q3: Plans".to_string()];
+        let fields = Fields {
+            path: &path,
+            name,
+            guid: "1234-abcd",
+            // Midday in the middle of the year is the same date in every timezone
+            modified: Some(Utc.with_ymd_and_hms(2026, 6, 15, 12, 0, 0).unwrap()),
+            pages: Some(12),
+            format: Some("pdf"),
+        };
+        let template: Template = template.parse().unwrap();
+        template.render(&fields, flatten, ascii_only).display().to_string()
+    }
+
+    #[test]
+    fn refuses_bad_templates() {
+        let error = |source: &str| source.parse::<Template>().unwrap_err();
+        assert_eq!(error("{title}.{ext}"), "unknown placeholder `{title}`; use path, name, guid, modified, pages, format or ext");
+        assert_eq!(error("{path}/{modified}.{ext}"), "must contain `{name}` or `{guid}` so documents get distinct names");
+        assert_eq!(error("{name"), "unclosed `{name`");
+        assert_eq!(error("{name}}.pdf"), "unmatched `}` (write `}}` for a literal brace)");
+        assert_eq!(error("{name:%Y}"), "`{name}` does not take a format");
+        assert_eq!(error("{name}_{modified:%Q}"), "invalid date format `%Q`");
+        assert!("{guid}".parse::<Template>().is_ok());
+        assert_eq!(toml::from_str::<BTreeMap<String, Template>>("t = \"{nope}\"").unwrap_err().message(), error("{nope}"));
+    }
+
+    #[test]
+    fn renders_each_field() {
+        assert_eq!(render(DEFAULT_TEMPLATE, "Paper.pdf", false, false), "Work/Q3_ Plans/Paper.pdf");
+        assert_eq!(
+            render("{name} ({pages}p, {format}) {modified} {modified:%Y}/{guid}.{ext}", "Paper", false, false),
+            "Paper (12p, pdf) 2026-06-15 2026/1234-abcd.pdf"
+        );
+        assert_eq!(render("{{{name}}}.{ext}", "Paper", false, false), "{Paper}.pdf");
+    }
+
+    #[test]
+    fn values_never_add_directories() {
+        assert_eq!(render("{path}/{name}.{ext}", "a/b", false, false), "Work/Q3_ Plans/a_b.pdf");
+        // Empty parts are dropped and an empty result falls back to the name
+        assert_eq!(render("/{name}//{guid}", "Paper", false, false), "Paper/1234-abcd");
+        assert_eq!(render("{name}", "", false, false), "_");
+    }
+
+    #[test]
+    fn flatten_folds_directories_into_the_name() {
+        assert_eq!(render(DEFAULT_TEMPLATE, "Paper", true, false), "Work - Q3_ Plans - Paper.pdf");
+        assert_eq!(render("{name}.{ext}", "Paper", true, false), "Paper.pdf");
+    }
+
+    #[test]
+    fn ascii_only_transliterates() {
+        assert_eq!(render("{name}.{ext}", "Réunion – Q3", false, false), "Réunion – Q3.pdf");
+        assert_eq!(render("{name}.{ext}", "Réunion – Q3", false, true), "Reunion - Q3.pdf");
+        assert_eq!(render("Notizen für {name}.{ext}", "Ärger", false, true), "Notizen fur Arger.pdf");
+    }
+}
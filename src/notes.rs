@@ -0,0 +1,664 @@
+use crate::{
+    backend::{Backend, PageNotes},
+    filename, lines, render, Item,
+};
+use anyhow::{Context, Result};
+use chrono::Local;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+// --- Reading notes ---
+//
+// Highlighted text and handwriting pulled out of documents, for note-taking
+// apps rather than for reading the PDF. Each document gets one Markdown or
+// JSON file, and every page with strokes on it an SVG in a folder of the
+// same name beside it. For an Obsidian vault, notebooks keep every page and
+// folders get a note of their own linking to what is inside.
+//
+// Exports are meant to be repeated: a file is only rewritten when its
+// contents change, so sync tools and vault histories see just the pages
+// that were drawn on since.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotesFormat {
+    #[default]
+    Markdown,
+    Json,
+    /// Markdown with front-matter and wiki-links.
+    Obsidian,
+}
+
+impl NotesFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            "obsidian" => Ok(Self::Obsidian),
+            _ => Err(anyhow::anyhow!("Unknown notes format `{}`; use markdown, json or obsidian", name)),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown | Self::Obsidian => "md",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotesConfig {
+    /// Where notes are written, e.g. a folder in an Obsidian vault. `~` is
+    /// expanded. Falls back to the download directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    pub format: NotesFormat,
+}
+
+/// A document found below the folder being exported.
+pub struct Document {
+    pub item: Item,
+    /// Full path on the tablet, as `/A/B/name`.
+    pub tablet_path: String,
+    /// Tablet folders between the exported folder and the document.
+    pub folders: Vec<String>,
+}
+
+/// `item` itself if it is a document, otherwise every document below it.
+pub fn documents(backend: &dyn Backend, item: Item, tablet_path: String, folders: Vec<String>) -> BoxFuture<'_, Result<Vec<Document>>> {
+    Box::pin(async move {
+        if !item.is_folder() {
+            return Ok(vec![Document { item, tablet_path, folders }]);
+        }
+        let mut found = Vec::new();
+        for child in backend.list(&Some(item.id)).await? {
+            let mut path = folders.clone();
+            if child.is_folder() {
+                path.push(child.visible_name.clone());
+            }
+            let child_tablet_path = format!("{}/{}", tablet_path, child.visible_name);
+            found.extend(documents(backend, child, child_tablet_path, path).await?);
+        }
+        Ok(found)
+    })
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub documents: usize,
+    pub highlights: usize,
+    pub images: usize,
+    /// Files written because they were new or changed, or that would be
+    /// in a dry run.
+    pub written: Vec<PathBuf>,
+    /// Files already up to date.
+    pub unchanged: usize,
+    /// Page images of pages that no longer exist.
+    pub removed: usize,
+    /// Documents with nothing highlighted or written on them.
+    pub empty: usize,
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} document(s) with {} highlight(s) and {} page image(s); {} file(s) written, {} unchanged",
+            self.documents,
+            self.highlights,
+            self.images,
+            self.written.len(),
+            self.unchanged
+        )?;
+        if self.removed > 0 {
+            write!(f, ", {} removed", self.removed)?;
+        }
+        if self.empty > 0 {
+            write!(f, ", {} without notes", self.empty)?;
+        }
+        if !self.errors.is_empty() {
+            write!(f, ", {} failed", self.errors.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl Report {
+    /// Writes each of `files` (relative to `dir`) whose contents differ,
+    /// then removes page images in `images` that are not among them.
+    fn apply(&mut self, dir: &Path, files: Vec<(PathBuf, Vec<u8>)>, images: &Path, dry_run: bool) -> Result<()> {
+        let keep: BTreeSet<PathBuf> = files.iter().map(|(path, _)| dir.join(path)).collect();
+        for (path, bytes) in files {
+            let path = dir.join(path);
+            if std::fs::read(&path).is_ok_and(|old| old == bytes) {
+                self.unchanged += 1;
+                continue;
+            }
+            if !dry_run {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).with_context(|| format!("Could not create {}", parent.display()))?;
+                }
+                std::fs::write(&path, bytes).with_context(|| format!("Could not write {}", path.display()))?;
+            }
+            self.written.push(path);
+        }
+        let Ok(entries) = std::fs::read_dir(dir.join(images)) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with("page-") && name.ends_with(".svg") && !keep.contains(&entry.path()) {
+                if !dry_run {
+                    std::fs::remove_file(entry.path())?;
+                }
+                self.removed += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Notebooks, as opposed to imported PDFs and EPUBs.
+fn is_notebook(item: &Item) -> bool {
+    matches!(item.file_type.as_deref(), None | Some("" | "notebook"))
+}
+
+/// `path` relative to the notes directory, with `/` between folders.
+fn slashed(path: &Path) -> String {
+    path.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// An Obsidian link to the note at `path`, shown as `label`.
+fn wiki(path: &Path, label: &str) -> String {
+    format!("[[{}|{}]]", slashed(&path.with_extension("")), label)
+}
+
+/// Writes the notes of every document in `documents` below `dir`, keeping
+/// their tablet folders. A failing document is reported and skipped.
+pub async fn export(
+    backend: &dyn Backend,
+    documents: Vec<Document>,
+    dir: &Path,
+    format: NotesFormat,
+    ascii_only: bool,
+    dry_run: bool,
+) -> Report {
+    let mut report = Report::default();
+    let mut names = filename::Siblings::default();
+    let sanitized = |folders: &[String]| -> PathBuf { folders.iter().map(|f| filename::sanitize(f, ascii_only)).collect() };
+
+    // Folder notes (`Work/Work.md`) claim their names first, so links to
+    // them do not depend on which documents turn out to have notes
+    let mut folder_notes = BTreeMap::new();
+    if format == NotesFormat::Obsidian {
+        let folders: BTreeSet<&[String]> = documents.iter().flat_map(|d| (1..=d.folders.len()).map(|n| &d.folders[..n])).collect();
+        for folders in folders {
+            let last = filename::sanitize(&folders[folders.len() - 1], ascii_only);
+            let note = names.claim(sanitized(folders).join(format!("{}.md", last)));
+            folder_notes.insert(folders.to_vec(), FolderNote { note, tablet_path: String::new(), entries: Vec::new() });
+        }
+    }
+
+    for doc in documents {
+        let notes = match backend.notes(&doc.item).await {
+            Ok(notes) => notes,
+            Err(e) => {
+                report.errors.push(format!("{}: {:#}", doc.tablet_path, e));
+                continue;
+            }
+        };
+        // A notebook in a vault is its pages; elsewhere only what was
+        // marked or written counts
+        let whole = format == NotesFormat::Obsidian && is_notebook(&doc.item);
+        let pages: Vec<&PageNotes> = notes
+            .pages
+            .iter()
+            .filter(|p| whole || !p.highlights.is_empty() || p.strokes.as_ref().is_some_and(|s| !s.is_blank()))
+            .collect();
+        if pages.is_empty() {
+            report.empty += 1;
+            continue;
+        }
+
+        let stem = filename::sanitize(&doc.item.visible_name, ascii_only);
+        let note = names.claim(sanitized(&doc.folders).join(format!("{}.{}", stem, format.extension())));
+        let images = note.with_extension("");
+        let images_name = images.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let blank = lines::Page::default();
+        let mut files = Vec::new();
+        let mut page_json = Vec::new();
+        for page in pages {
+            let strokes = match &page.strokes {
+                Some(strokes) if whole || !strokes.is_blank() => Some(strokes),
+                None if whole => Some(&blank),
+                _ => None,
+            };
+            let image = strokes.map(|strokes| {
+                let name = format!("page-{:03}.svg", page.number);
+                files.push((images.join(&name), render::svg(strokes).into_bytes()));
+                format!("{}/{}", images_name, name)
+            });
+            report.images += image.is_some() as usize;
+            report.highlights += page.highlights.len();
+            page_json.push(PageJson {
+                page: page.number,
+                source_page: page.source,
+                highlights: page.highlights.iter().map(|h| HighlightJson { text: h.text.clone(), color: hex(h.color) }).collect(),
+                image,
+            });
+        }
+
+        let text = match format {
+            NotesFormat::Markdown => markdown(&doc, &page_json),
+            NotesFormat::Json => {
+                let json = DocumentJson {
+                    name: &doc.item.visible_name,
+                    id: &doc.item.id,
+                    tablet_path: &doc.tablet_path,
+                    modified: doc.item.modified.as_deref(),
+                    tags: &notes.tags,
+                    pages: page_json,
+                };
+                match serde_json::to_string_pretty(&json) {
+                    Ok(json) => json + "\n",
+                    Err(e) => {
+                        report.errors.push(format!("{}: {:#}", doc.tablet_path, e));
+                        continue;
+                    }
+                }
+            }
+            NotesFormat::Obsidian => {
+                let trail = breadcrumbs(&folder_notes, &doc.folders);
+                let folder = note.parent().map(slashed).filter(|f| !f.is_empty());
+                obsidian(&doc, &notes.tags, &trail, folder.as_deref(), &page_json)
+            }
+        };
+        files.push((note.clone(), text.into_bytes()));
+
+        // Every folder on the way lists what is below it
+        let parts: Vec<&str> = doc.tablet_path.split('/').collect();
+        for n in 1..=doc.folders.len() {
+            if let Some(folder) = folder_notes.get_mut(&doc.folders[..n]) {
+                folder.tablet_path = parts[..parts.len() - (doc.folders.len() - n + 1)].join("/");
+                let entry = match doc.folders.get(n) {
+                    Some(sub) => Entry::Folder(doc.folders[..=n].to_vec(), sub.clone()),
+                    None => Entry::Document(note.clone(), doc.item.visible_name.clone()),
+                };
+                if !folder.entries.contains(&entry) {
+                    folder.entries.push(entry);
+                }
+            }
+        }
+
+        match report.apply(dir, files, &images, dry_run) {
+            Ok(()) => report.documents += 1,
+            Err(e) => report.errors.push(format!("{}: {:#}", doc.tablet_path, e)),
+        }
+    }
+
+    for (folders, folder) in &folder_notes {
+        if folder.entries.is_empty() {
+            continue;
+        }
+        let text = folder_note(&folder_notes, folders, folder);
+        if let Err(e) = report.apply(dir, vec![(folder.note.clone(), text.into_bytes())], Path::new(""), dry_run) {
+            report.errors.push(format!("{}: {:#}", folder.tablet_path, e));
+        }
+    }
+    report
+}
+
+/// The note of a tablet folder in a vault, and what goes in it.
+struct FolderNote {
+    note: PathBuf,
+    tablet_path: String,
+    entries: Vec<Entry>,
+}
+
+#[derive(PartialEq)]
+enum Entry {
+    /// A subfolder, by its tablet folders and name.
+    Folder(Vec<String>, String),
+    /// A document's note and name.
+    Document(PathBuf, String),
+}
+
+/// Links to the notes of each folder in `folders`, outermost first.
+fn breadcrumbs(folder_notes: &BTreeMap<Vec<String>, FolderNote>, folders: &[String]) -> Vec<String> {
+    (1..=folders.len())
+        .filter_map(|n| folder_notes.get(&folders[..n]).map(|f| wiki(&f.note, &folders[n - 1])))
+        .collect()
+}
+
+/// YAML front-matter. Strings are written as JSON, which YAML reads too.
+fn front_matter(fields: &[(&str, String)]) -> String {
+    let mut out = "---\n".to_string();
+    for (key, value) in fields {
+        out += &format!("{}: {}\n", key, value);
+    }
+    out + "---\n\n"
+}
+
+fn quoted(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+fn folder_note(folder_notes: &BTreeMap<Vec<String>, FolderNote>, folders: &[String], folder: &FolderNote) -> String {
+    let name = &folders[folders.len() - 1];
+    let mut out = front_matter(&[("title", quoted(name)), ("tablet_path", quoted(&folder.tablet_path))]);
+    let trail = breadcrumbs(folder_notes, &folders[..folders.len() - 1]);
+    if !trail.is_empty() {
+        out += &format!("{}\n\n", trail.join(" / "));
+    }
+    // Subfolders first, as on the tablet
+    let mut entries: Vec<&Entry> = folder.entries.iter().filter(|e| matches!(e, Entry::Folder(..))).collect();
+    entries.extend(folder.entries.iter().filter(|e| matches!(e, Entry::Document(..))));
+    for entry in entries {
+        let link = match entry {
+            Entry::Folder(sub, name) => folder_notes.get(sub).map(|f| wiki(&f.note, name)),
+            Entry::Document(note, name) => Some(wiki(note, name)),
+        };
+        if let Some(link) = link {
+            out += &format!("- {}\n", link);
+        }
+    }
+    out
+}
+
+fn obsidian(doc: &Document, tags: &[String], trail: &[String], folder: Option<&str>, pages: &[PageJson]) -> String {
+    // Obsidian tags cannot contain spaces
+    let tags: Vec<String> = tags.iter().map(|t| quoted(&t.split_whitespace().collect::<Vec<_>>().join("-"))).collect();
+    let mut fields = vec![("title", quoted(&doc.item.visible_name)), ("tablet_path", quoted(&doc.tablet_path))];
+    if let Some(modified) = doc.item.modified_at() {
+        fields.push(("modified", modified.with_timezone(&Local).format("%Y-%m-%dT%H:%M:%S").to_string()));
+    }
+    fields.push(("tags", format!("[{}]", tags.join(", "))));
+    fields.push(("guid", quoted(&doc.item.id)));
+    let mut out = front_matter(&fields);
+    if !trail.is_empty() {
+        out += &format!("{}\n\n", trail.join(" / "));
+    }
+    for page in pages {
+        out += &page_heading(page);
+        out += &quotes(page);
+        if let Some(image) = &page.image {
+            let path = folder.map_or(image.clone(), |f| format!("{}/{}", f, image));
+            out += &format!("![[{}]]\n\n", path);
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out + "\n"
+}
+
+#[derive(Serialize)]
+struct DocumentJson<'a> {
+    name: &'a str,
+    id: &'a str,
+    tablet_path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    pages: Vec<PageJson>,
+}
+
+#[derive(Serialize)]
+struct PageJson {
+    page: usize,
+    /// Page of the imported PDF or EPUB, if it shows one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_page: Option<usize>,
+    highlights: Vec<HighlightJson>,
+    /// Relative to the notes file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+}
+
+#[derive(Serialize)]
+struct HighlightJson {
+    text: String,
+    color: String,
+}
+
+fn hex(lines::Rgb(r, g, b): lines::Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn page_heading(page: &PageJson) -> String {
+    match page.source_page {
+        Some(source) if source != page.page => format!("## Page {} (p. {} of the original)\n\n", page.page, source),
+        _ => format!("## Page {}\n\n", page.page),
+    }
+}
+
+/// One quote per highlight; blank lines inside would end it.
+fn quotes(page: &PageJson) -> String {
+    page.highlights
+        .iter()
+        .map(|h| {
+            let text: Vec<&str> = h.text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+            format!("> {}\n\n", text.join(" "))
+        })
+        .collect()
+}
+
+fn markdown(doc: &Document, pages: &[PageJson]) -> String {
+    let mut out = format!("# {}\n\nNotes from `{}` on the reMarkable", doc.item.visible_name, doc.tablet_path);
+    if let Some(modified) = doc.item.modified_at() {
+        out += &format!(", last changed {}", modified.with_timezone(&Local).format("%Y-%m-%d"));
+    }
+    out += ".\n\n";
+    for page in pages {
+        out += &page_heading(page);
+        out += &quotes(page);
+        if let Some(image) = &page.image {
+            out += &format!("![Handwritten notes on page {}]({})\n\n", page.page, link(image));
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out + "\n"
+}
+
+/// `path` as a Markdown link target; spaces and brackets would end it.
+fn link(path: &str) -> String {
+    path.replace('%', "%25").replace(' ', "%20").replace('(', "%28").replace(')', "%29")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::{Body, Notes},
+        lines::Highlight,
+    };
+
+    /// Hands out the notes `notes` makes for each document.
+    struct Fake(fn(&Item) -> Notes);
+
+    impl Backend for Fake {
+        fn list<'a>(&'a self, _parent: &'a Option<String>) -> BoxFuture<'a, Result<Vec<Item>>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn download<'a>(&'a self, _item: &'a Item) -> BoxFuture<'a, Result<Body>> {
+            Box::pin(async { Err(anyhow::anyhow!("not in these tests")) })
+        }
+
+        fn notes<'a>(&'a self, item: &'a Item) -> BoxFuture<'a, Result<Notes>> {
+            Box::pin(async move { Ok((self.0)(item)) })
+        }
+
+        fn upload<'a>(&'a self, _parent: &'a Option<String>, _path: &'a Path, _file_name: &'a str) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async { Err(anyhow::anyhow!("not in these tests")) })
+        }
+
+        fn describe(&self) -> String {
+            "the test tablet".into()
+        }
+    }
+
+    fn document(name: &str, file_type: &str, folders: &[&str]) -> Document {
+        let item = serde_json::from_value(serde_json::json!({
+            "ID": format!("id-{}", name), "VissibleName": name, "Type": "DocumentType",
+            "fileType": file_type, "ModifiedClient": "2026-06-15T12:00:00.000Z",
+        }))
+        .unwrap();
+        let tablet_path = folders.iter().chain([&name]).fold(String::new(), |path, part| format!("{}/{}", path, part));
+        Document { item, tablet_path, folders: folders.iter().map(|f| f.to_string()).collect() }
+    }
+
+    fn highlight(text: &str, color: lines::Rgb) -> Highlight {
+        Highlight { text: text.into(), color, start: None, top: 0.0, left: 0.0 }
+    }
+
+    fn page(number: usize, source: Option<usize>, highlights: Vec<Highlight>, strokes: Option<lines::Page>) -> PageNotes {
+        PageNotes { number, source, highlights, strokes }
+    }
+
+    /// A PDF with a highlight, a page added on the tablet and written on, a
+    /// page left alone, a page drawn on and erased and one highlighted on
+    /// its own page number.
+    fn reading(_: &Item) -> Notes {
+        let written = lines::Page::parse(&lines::fixtures::v3(true)).unwrap();
+        Notes {
+            tags: vec!["to read".into(), "ml".into()],
+            pages: vec![
+                page(1, Some(1), vec![highlight("A first\n  highlighted passage\n", lines::Rgb(251, 247, 25))], None),
+                page(2, None, Vec::new(), Some(written)),
+                page(3, Some(2), Vec::new(), None),
+                page(4, Some(3), vec![highlight("Second", lines::Rgb(0, 255, 0))], Some(lines::Page::default())),
+            ],
+        }
+    }
+
+    fn untouched(_: &Item) -> Notes {
+        Notes { tags: Vec::new(), pages: vec![page(1, Some(1), Vec::new(), None)] }
+    }
+
+    async fn export_to(dir: &Path, backend: &Fake, documents: Vec<Document>, format: NotesFormat) -> Report {
+        let report = export(backend, documents, dir, format, false, false).await;
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        report
+    }
+
+    fn read(dir: &Path, path: &str) -> String {
+        std::fs::read_to_string(dir.join(path)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn exports_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = export_to(dir.path(), &Fake(reading), vec![document("Paper (draft)", "pdf", &["Work"])], NotesFormat::Markdown).await;
+        assert_eq!((report.documents, report.highlights, report.images, report.written.len()), (1, 2, 1, 2));
+        let expected = "# Paper (draft)\n\n\
+            Notes from `/Work/Paper (draft)` on the reMarkable, last changed 2026-06-15.\n\n\
+            ## Page 1\n\n\
+            > A first highlighted passage\n\n\
+            ## Page 2\n\n\
+            ![Handwritten notes on page 2](Paper%20%28draft%29/page-002.svg)\n\n\
+            ## Page 4 (p. 3 of the original)\n\n\
+            > Second\n";
+        assert_eq!(read(dir.path(), "Work/Paper (draft).md"), expected);
+        assert!(read(dir.path(), "Work/Paper (draft)/page-002.svg").starts_with("<svg"));
+
+        // Nothing changed, so nothing is written
+        let again = export_to(dir.path(), &Fake(reading), vec![document("Paper (draft)", "pdf", &["Work"])], NotesFormat::Markdown).await;
+        assert_eq!((again.written.len(), again.unchanged), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn exports_json() {
+        let dir = tempfile::tempdir().unwrap();
+        export_to(dir.path(), &Fake(reading), vec![document("Paper", "pdf", &[])], NotesFormat::Json).await;
+        let json: serde_json::Value = serde_json::from_str(&read(dir.path(), "Paper.json")).unwrap();
+        let expected = serde_json::json!({
+            "name": "Paper",
+            "id": "id-Paper",
+            "tablet_path": "/Paper",
+            "modified": "2026-06-15T12:00:00.000Z",
+            "tags": ["to read", "ml"],
+            "pages": [
+                { "page": 1, "source_page": 1, "highlights": [{ "text": "A first\n  highlighted passage\n", "color": "#fbf719" }] },
+                { "page": 2, "highlights": [], "image": "Paper/page-002.svg" },
+                { "page": 4, "source_page": 3, "highlights": [{ "text": "Second", "color": "#00ff00" }] },
+            ],
+        });
+        assert_eq!(json, expected);
+    }
+
+    #[tokio::test]
+    async fn documents_without_notes_get_no_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = export_to(dir.path(), &Fake(untouched), vec![document("Paper", "pdf", &["Work"])], NotesFormat::Markdown).await;
+        assert_eq!((report.documents, report.empty), (0, 1));
+        assert!(!dir.path().join("Work").exists());
+    }
+
+    fn vault() -> Vec<Document> {
+        let mut top = document("Top", "pdf", &[]);
+        let mut work = document("Work", "pdf", &["Work"]);
+        let mut plan = document("Plan", "notebook", &["Work", "Projects"]);
+        let mut beta = document("Beta", "pdf", &["Work", "Projects"]);
+        // Exported from `/Library`
+        for doc in [&mut top, &mut work, &mut plan, &mut beta] {
+            doc.tablet_path = format!("/Library{}", doc.tablet_path);
+        }
+        vec![top, work, plan, beta]
+    }
+
+    #[tokio::test]
+    async fn exports_an_obsidian_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = export_to(dir.path(), &Fake(reading), vault(), NotesFormat::Obsidian).await;
+        let mut notes: Vec<String> = report.written.iter().map(|p| slashed(p.strip_prefix(dir.path()).unwrap())).filter(|p| p.ends_with(".md")).collect();
+        notes.sort();
+        // The folder note claims `Work/Work.md` before the document of the same name
+        assert_eq!(notes, ["Top.md", "Work/Projects/Beta.md", "Work/Projects/Plan.md", "Work/Projects/Projects.md", "Work/Work (2).md", "Work/Work.md"]);
+        // Notebooks keep every page, drawn on or not
+        assert_eq!(report.images, 3 + 4);
+
+        let work = "---\ntitle: \"Work\"\ntablet_path: \"/Library/Work\"\n---\n\n\
+            - [[Work/Projects/Projects|Projects]]\n\
+            - [[Work/Work (2)|Work]]\n";
+        assert_eq!(read(dir.path(), "Work/Work.md"), work);
+        let projects = "---\ntitle: \"Projects\"\ntablet_path: \"/Library/Work/Projects\"\n---\n\n\
+            [[Work/Work|Work]]\n\n\
+            - [[Work/Projects/Plan|Plan]]\n\
+            - [[Work/Projects/Beta|Beta]]\n";
+        assert_eq!(read(dir.path(), "Work/Projects/Projects.md"), projects);
+
+        let plan = read(dir.path(), "Work/Projects/Plan.md");
+        assert!(plan.starts_with("---\ntitle: \"Plan\"\ntablet_path: \"/Library/Work/Projects/Plan\"\n"));
+        assert!(plan.contains("tags: [\"to-read\", \"ml\"]\nguid: \"id-Plan\"\n---\n\n[[Work/Work|Work]] / [[Work/Projects/Projects|Projects]]\n\n## Page 1\n"));
+        assert!(plan.contains("## Page 3 (p. 2 of the original)\n\n![[Work/Projects/Plan/page-003.svg]]\n"));
+        assert!(read(dir.path(), "Work/Work (2).md").contains("---\n\n[[Work/Work|Work]]\n\n## Page 1\n"));
+        // Nothing above the top level
+        assert!(read(dir.path(), "Top.md").contains("---\n\n## Page 1\n"));
+    }
+
+    #[tokio::test]
+    async fn a_second_export_leaves_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = export_to(dir.path(), &Fake(reading), vault(), NotesFormat::Obsidian).await;
+        // Backdated, so a rewrite would show
+        let old = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        for path in &first.written {
+            std::fs::File::options().write(true).open(path).unwrap().set_modified(old).unwrap();
+        }
+
+        let second = export_to(dir.path(), &Fake(reading), vault(), NotesFormat::Obsidian).await;
+        assert!(second.written.is_empty(), "{:?}", second.written);
+        assert_eq!((second.unchanged, second.removed), (first.written.len(), 0));
+        for path in &first.written {
+            assert_eq!(std::fs::metadata(path).unwrap().modified().unwrap(), old, "{}", path.display());
+        }
+    }
+}
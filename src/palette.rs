@@ -0,0 +1,382 @@
+use crate::{
+    device,
+    keymap::{Action, Context},
+};
+
+// --- Commands ---
+
+/// What a palette line asks for once parsed.
+pub enum Command {
+    Action(Action),
+    Cd(String),
+    Download(Option<String>),
+    Upload(Option<String>),
+    Rename(Option<String>),
+    Mkdir(Option<String>),
+    EditTags(Option<String>),
+    Tagged(Option<String>),
+    Sync(Option<String>),
+    Notes(Option<String>),
+    Profile(Option<String>),
+    Device(device::Request),
+}
+
+/// What a spec runs: a keymap action or one of the palette-only commands.
+enum Target {
+    Action(Action),
+    Cd,
+    Sync,
+    Notes,
+    Profile,
+    /// `templates` or `splash`, by name.
+    Device(&'static str),
+}
+
+pub struct CommandSpec {
+    /// What the user types, e.g. `sort modified`.
+    pub name: String,
+    pub args: &'static str,
+    pub description: &'static str,
+    target: Target,
+}
+
+impl CommandSpec {
+    /// The keymap action this spec runs, if it is one.
+    pub fn action(&self) -> Option<Action> {
+        match self.target {
+            Target::Action(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    /// The palette name of an action is its config name with spaces.
+    pub fn name_for(action: Action) -> String {
+        action.name().replace('_', " ")
+    }
+
+    fn build(&self, args: &str) -> Result<Command, String> {
+        let args = args.trim();
+        let arg = (!args.is_empty()).then(|| args.to_string());
+        match self.target {
+            Target::Action(Action::Download) => Ok(Command::Download(arg)),
+            Target::Action(Action::Upload) => Ok(Command::Upload(arg)),
+            Target::Action(Action::Rename) => Ok(Command::Rename(arg)),
+            Target::Action(Action::Mkdir) => Ok(Command::Mkdir(arg)),
+            Target::Action(Action::EditTags) => Ok(Command::EditTags(arg)),
+            Target::Action(Action::Tagged) => Ok(Command::Tagged(arg)),
+            Target::Action(action) if arg.is_none() => Ok(Command::Action(action)),
+            Target::Action(_) => Err(format!("`{}` takes no arguments", self.name)),
+            Target::Cd => arg.map(Command::Cd).ok_or_else(|| "Usage: cd <path>".to_string()),
+            Target::Sync => Ok(Command::Sync(arg)),
+            Target::Notes => Ok(Command::Notes(arg)),
+            Target::Profile => Ok(Command::Profile(arg)),
+            Target::Device(name) => device::Request::parse(name, &device::words(args)).map(Command::Device).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Every browsing action, plus commands that only make sense with an argument.
+pub fn commands() -> Vec<CommandSpec> {
+    let mut specs: Vec<CommandSpec> = Action::ALL
+        .iter()
+        .filter(|a| a.context() == Context::Browse && **a != Action::Palette)
+        .map(|&action| CommandSpec {
+            name: CommandSpec::name_for(action),
+            args: match action {
+                Action::Download | Action::Upload => "[path]",
+                Action::Rename | Action::Mkdir => "[name]",
+                Action::EditTags => "[tags]",
+                Action::Tagged => "[tag]",
+                _ => "",
+            },
+            description: action.description(),
+            target: Target::Action(action),
+        })
+        .collect();
+
+    specs.push(CommandSpec {
+        name: "cd".into(),
+        args: "<path>",
+        description: "Go to a tablet folder, e.g. /Work, Notes or ..",
+        target: Target::Cd,
+    });
+    specs.push(CommandSpec {
+        name: "sync".into(),
+        args: "[name]",
+        description: "Review and run a sync of the [[sync]] pairs",
+        target: Target::Sync,
+    });
+    specs.push(CommandSpec {
+        name: "notes".into(),
+        args: "[dir]",
+        description: "Export highlights and handwriting of the selection as notes",
+        target: Target::Notes,
+    });
+    specs.push(CommandSpec {
+        name: "profile".into(),
+        args: "[name]",
+        description: "Show the profiles, or switch to another one",
+        target: Target::Profile,
+    });
+    specs.push(CommandSpec {
+        name: "templates".into(),
+        args: "[add|remove|backup|restore ...]",
+        description: "Show or change the page templates (ssh profiles)",
+        target: Target::Device("templates"),
+    });
+    specs.push(CommandSpec {
+        name: "splash".into(),
+        args: "[set|restore ...]",
+        description: "Show, replace or restore the splash screens (ssh profiles)",
+        target: Target::Device("splash"),
+    });
+    specs
+}
+
+/// Subsequence match of `pattern` in `candidate`, ignoring case. Higher is
+/// better; consecutive runs and matches at word starts score extra.
+pub fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last_match: Option<usize> = None;
+
+    for p in pattern.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (pos..candidate.len()).find(|&i| candidate[i] == p)?;
+        score += 1;
+        if last_match.map_or(found == 0, |last| found == last + 1) {
+            score += 5;
+        }
+        if found == 0 || candidate[found - 1] == ' ' {
+            score += 3;
+        }
+        score -= (found - pos) as i64;
+        last_match = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Parses a palette line. An exact command name wins; otherwise the first
+/// word is fuzzy-matched, so `dow ~/notes/` means `download ~/notes/`. Equally good
+/// matches are refused as ambiguous rather than guessed between.
+pub fn parse(specs: &[CommandSpec], line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Err("No command given.".into());
+    }
+
+    let exact = specs
+        .iter()
+        .filter(|spec| line == spec.name || line.starts_with(&format!("{} ", spec.name)))
+        .max_by_key(|spec| spec.name.len());
+    if let Some(spec) = exact {
+        return spec.build(&line[spec.name.len()..]);
+    }
+
+    let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let ranked = rank(specs.iter().filter_map(|spec| fuzzy_score(word, &spec.name).map(|score| (score, spec))).collect());
+    match ranked.as_slice() {
+        [] => Err(format!("Unknown command `{}`", word)),
+        // Guessing between equally good matches could run something drastic
+        [(best, _), (next, _), ..] if best == next => {
+            let tied: Vec<&str> = ranked.iter().take_while(|(score, _)| score == best).map(|(_, spec)| spec.name.as_str()).collect();
+            Err(format!("`{}` is ambiguous: {}", word, tied.join(", ")))
+        }
+        [(_, spec), ..] => spec.build(rest),
+    }
+}
+
+/// Best score first, then by name, the order suggestions are listed in.
+fn rank(mut scored: Vec<(i64, &CommandSpec)>) -> Vec<(i64, &CommandSpec)> {
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.cmp(&b.1.name)));
+    scored
+}
+
+// --- State ---
+
+#[derive(Default)]
+pub struct Palette {
+    pub input: String,
+    /// Index into the current suggestions, moved by repeated completion.
+    selected: usize,
+    /// What was typed before completion started cycling through matches.
+    completion_base: Option<String>,
+    history: Vec<String>,
+    history_pos: Option<usize>,
+}
+
+impl Palette {
+    pub fn open(&mut self) {
+        self.set_input(String::new());
+        self.history_pos = None;
+    }
+
+    fn set_input(&mut self, input: String) {
+        self.input = input;
+        self.selected = 0;
+        self.completion_base = None;
+    }
+
+    pub fn push(&mut self, c: char) {
+        let mut input = std::mem::take(&mut self.input);
+        input.push(c);
+        self.set_input(input);
+    }
+
+    pub fn pop(&mut self) {
+        let mut input = std::mem::take(&mut self.input);
+        input.pop();
+        self.set_input(input);
+    }
+
+    /// Commands matching what has been typed so far, best first.
+    pub fn suggestions<'a>(&self, specs: &'a [CommandSpec]) -> Vec<&'a CommandSpec> {
+        let typed = self.completion_base.as_deref().unwrap_or(&self.input).trim_start();
+        let scored = specs
+            .iter()
+            .filter_map(|spec| {
+                // Once arguments are being typed only the command part counts
+                let pattern = if typed.starts_with(&format!("{} ", spec.name)) { &spec.name } else { typed };
+                fuzzy_score(pattern, &spec.name).map(|score| (score, spec))
+            })
+            .collect();
+        rank(scored).into_iter().map(|(_, spec)| spec).collect()
+    }
+
+    /// The suggestion the last completion filled in, or the first one.
+    pub fn highlighted(&self, count: usize) -> usize {
+        match (self.completion_base.is_some(), count) {
+            (_, 0) | (false, _) => 0,
+            (true, _) => (self.selected + count - 1) % count,
+        }
+    }
+
+    /// Fills in the highlighted suggestion; repeating cycles through the rest.
+    pub fn complete(&mut self, specs: &[CommandSpec]) {
+        let suggestions = self.suggestions(specs);
+        if suggestions.is_empty() {
+            return;
+        }
+        let spec = suggestions[self.selected % suggestions.len()];
+        if self.completion_base.is_none() {
+            self.completion_base = Some(self.input.clone());
+        }
+        self.input = if spec.args.is_empty() { spec.name.clone() } else { format!("{} ", spec.name) };
+        self.selected = (self.selected + 1) % suggestions.len();
+    }
+
+    /// Fills in a suggestion picked directly, e.g. by clicking it.
+    pub fn choose(&mut self, specs: &[CommandSpec], index: usize) {
+        let base = self.completion_base.take().unwrap_or_else(|| self.input.clone());
+        self.input = base;
+        self.selected = index;
+        self.complete(specs);
+    }
+
+    pub fn history_prev(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let pos = match self.history_pos {
+            Some(0) => 0,
+            Some(pos) => pos - 1,
+            None => self.history.len() - 1,
+        };
+        self.history_pos = Some(pos);
+        self.set_input(self.history[pos].clone());
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_pos {
+            Some(pos) if pos + 1 < self.history.len() => {
+                self.history_pos = Some(pos + 1);
+                self.set_input(self.history[pos + 1].clone());
+            }
+            Some(_) => {
+                self.history_pos = None;
+                self.set_input(String::new());
+            }
+            None => {}
+        }
+    }
+
+    /// Takes the typed line and records it in the history.
+    pub fn submit(&mut self) -> String {
+        let line = self.input.trim().to_string();
+        self.set_input(String::new());
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.history_pos = None;
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(specs: &[&CommandSpec]) -> Vec<String> {
+        specs.iter().map(|spec| spec.name.clone()).collect()
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_runs_and_word_starts() {
+        assert_eq!(fuzzy_score("xyz", "download"), None);
+        assert_eq!(fuzzy_score("DOWN", "download"), fuzzy_score("down", "download"));
+        assert!(fuzzy_score("down", "download") > fuzzy_score("down", "sort modified"));
+        // Word starts count extra
+        assert!(fuzzy_score("sm", "sort modified") > fuzzy_score("sm", "sort name"));
+        assert!(fuzzy_score("sn", "sort name") > fuzzy_score("sn", "sort modified"));
+    }
+
+    #[test]
+    fn parse_exact_and_fuzzy() {
+        let specs = commands();
+        assert!(matches!(parse(&specs, "download ~/notes/"), Ok(Command::Download(Some(path))) if path == "~/notes/"));
+        assert!(matches!(parse(&specs, "down ~/notes/"), Err(e) if e == "`down` takes no arguments"));
+        assert!(matches!(parse(&specs, "downl ~/notes/"), Ok(Command::Download(Some(path))) if path == "~/notes/"));
+        assert!(matches!(parse(&specs, "cd"), Err(e) if e.starts_with("Usage")));
+        assert!(matches!(parse(&specs, "qqqq"), Err(e) if e == "Unknown command `qqqq`"));
+        assert!(parse(&specs, "   ").is_err());
+    }
+
+    #[test]
+    fn ties_are_refused_rather_than_guessed() {
+        let specs = commands();
+        let Err(e) = parse(&specs, "d") else { panic!("`d` should be ambiguous") };
+        assert!(e.starts_with("`d` is ambiguous: "), "{}", e);
+        let tied: Vec<&str> = e["`d` is ambiguous: ".len()..].split(", ").collect();
+        let mut sorted = tied.clone();
+        sorted.sort();
+        assert_eq!(tied, sorted);
+        assert!(tied.contains(&"delete") && tied.contains(&"download") && tied.contains(&"dry run"));
+    }
+
+    #[test]
+    fn suggestions_list_ties_by_name() {
+        let specs = commands();
+        let mut palette = Palette::default();
+        palette.push('d');
+        let suggestions = names(&palette.suggestions(&specs));
+        let top: Vec<&String> = suggestions.iter().take_while(|n| ["delete", "down", "download", "dry run"].contains(&n.as_str())).collect();
+        let mut sorted = top.clone();
+        sorted.sort();
+        assert_eq!(top, sorted);
+        assert!(top.len() >= 3);
+    }
+
+    #[test]
+    fn enter_runs_the_highlighted_suggestion() {
+        let specs = commands();
+        let mut palette = Palette::default();
+        palette.push('d');
+        palette.complete(&specs);
+        let suggestions = palette.suggestions(&specs);
+        let highlighted = suggestions[palette.highlighted(suggestions.len())].name.clone();
+        assert_eq!(palette.input.trim(), highlighted);
+        let Ok(Command::Action(action)) = parse(&specs, &palette.submit()) else { panic!("`{}` should run an action", highlighted) };
+        assert_eq!(CommandSpec::name_for(action), highlighted);
+    }
+}
@@ -0,0 +1,326 @@
+use crate::lines::{Layer, Page, Pen, Rgb, Stroke, HEIGHT, WIDTH};
+use anyhow::{anyhow, Context, Result};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::{collections::BTreeMap, fmt::Write};
+
+// --- Drawing strokes ---
+//
+// Each stroke is split into runs of equal width and opacity, so pens whose
+// line varies with pressure still come out as a handful of paths. SVG and
+// PDF output draw the same runs.
+
+/// PDF points per tablet pixel for pages without a background (226 DPI).
+const POINTS_PER_PIXEL: f32 = 72.0 / 226.0;
+
+/// Name of the strokes overlay in a page's resources.
+const INK: &str = "RmInk";
+
+/// Part of a stroke drawn with one width, colour and opacity.
+struct Run {
+    points: Vec<(f32, f32)>,
+    width: f32,
+    color: Rgb,
+    opacity: f32,
+    /// Highlighters and shaders tint what is underneath instead of covering it.
+    multiply: bool,
+}
+
+/// Width and opacity of `stroke` at point `i`, rounded so that runs merge.
+fn pen_style(stroke: &Stroke, i: usize) -> (f32, f32) {
+    let point = &stroke.points[i];
+    let (width, opacity) = match stroke.pen {
+        Pen::Fineliner => (stroke.thickness * 1.8, 1.0),
+        Pen::Highlighter => (if point.width > 0.0 { point.width } else { 30.0 }, 0.4),
+        Pen::Shader => (point.width, 0.15),
+        Pen::Pencil | Pen::MechanicalPencil => (point.width, 0.4 + 0.6 * point.pressure.clamp(0.0, 1.0)),
+        Pen::Eraser => (point.width * 2.0, 1.0),
+        _ => (point.width, 1.0),
+    };
+    ((width.max(0.5) * 2.0).round() / 2.0, (opacity * 20.0_f32).round() / 20.0)
+}
+
+/// `stroke` as runs, with x moved right by `offset`.
+fn runs(stroke: &Stroke, offset: f32) -> Vec<Run> {
+    if stroke.pen == Pen::EraseArea || stroke.points.is_empty() {
+        return Vec::new();
+    }
+    let color = if stroke.pen == Pen::Eraser { Rgb(255, 255, 255) } else { stroke.color };
+    let multiply = matches!(stroke.pen, Pen::Highlighter | Pen::Shader);
+    let at = |i: usize| (stroke.points[i].x + offset, stroke.points[i].y);
+
+    let mut runs: Vec<Run> = Vec::new();
+    // A lone point still leaves a dot
+    let last = stroke.points.len().max(2) - 1;
+    for i in 1..=last {
+        let i = i.min(stroke.points.len() - 1);
+        let (width, opacity) = pen_style(stroke, i);
+        match runs.last_mut() {
+            Some(run) if run.width == width && run.opacity == opacity => run.points.push(at(i)),
+            _ => runs.push(Run { points: vec![at(i.saturating_sub(1)), at(i)], width, color, opacity, multiply }),
+        }
+    }
+    runs
+}
+
+fn layer_runs(layer: &Layer, offset: f32) -> impl Iterator<Item = Run> + '_ {
+    layer.strokes.iter().flat_map(move |s| runs(s, offset))
+}
+
+/// Height of `page` in pixels: the screen, or more if it was scrolled.
+fn page_height(page: &Page) -> f32 {
+    HEIGHT.max(page.bottom().ceil())
+}
+
+// --- SVG ---
+
+/// `page` as an SVG document the size of the tablet's screen. Hidden layers
+/// are kept but not shown.
+pub fn svg(page: &Page) -> String {
+    let height = page_height(page);
+    let offset = if page.centered { WIDTH / 2.0 } else { 0.0 };
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+        w = WIDTH,
+        h = height
+    );
+    for layer in &page.layers {
+        out.push_str(if layer.visible { "<g>" } else { "<g display=\"none\">" });
+        if let Some(name) = &layer.name {
+            let _ = write!(out, "<title>{}</title>", escape(name));
+        }
+        out.push('\n');
+        for run in layer_runs(layer, offset) {
+            let points: Vec<String> = run.points.iter().map(|(x, y)| format!("{:.2},{:.2}", x, y)).collect();
+            let Rgb(r, g, b) = run.color;
+            let _ = write!(
+                out,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"#{:02x}{:02x}{:02x}\" stroke-width=\"{}\" \
+                 stroke-linecap=\"round\" stroke-linejoin=\"round\"",
+                points.join(" "),
+                r,
+                g,
+                b,
+                run.width
+            );
+            if run.opacity < 1.0 {
+                let _ = write!(out, " stroke-opacity=\"{}\"", run.opacity);
+            }
+            if run.multiply {
+                out.push_str(" style=\"mix-blend-mode:multiply\"");
+            }
+            out.push_str("/>\n");
+        }
+        out.push_str("</g>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// --- PDF ---
+
+/// One page of an exported document.
+pub struct PdfPage {
+    pub strokes: Option<Page>,
+    /// Page of the background PDF underneath, counting from 0.
+    pub background: Option<usize>,
+}
+
+/// The pages as a PDF. With a `background`, strokes are drawn over its
+/// pages and pages without a background page are left out; otherwise every
+/// page is drawn on white.
+pub fn pdf(pages: &[PdfPage], background: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut doc = match background {
+        Some(bytes) => {
+            let mut doc = Document::load_mem(bytes).context("Could not read the document's PDF")?;
+            let existing = doc.get_pages();
+            for page in pages {
+                let (Some(strokes), Some(index)) = (&page.strokes, page.background) else { continue };
+                let Some(&page_id) = existing.get(&(index as u32 + 1)) else { continue };
+                let media_box = media_box(&doc, page_id)?;
+                // The tablet fits the page to its screen
+                let [x0, y0, x1, y1] = media_box;
+                let scale = ((x1 - x0) / WIDTH).max((y1 - y0) / HEIGHT);
+                let form = ink(strokes, media_box, scale);
+                overlay(&mut doc, page_id, form)?;
+            }
+            doc
+        }
+        None => blank(pages),
+    };
+    doc.compress();
+    let mut out = Vec::new();
+    doc.save_to(&mut out)?;
+    Ok(out)
+}
+
+/// A new document with one white page per entry.
+fn blank(pages: &[PdfPage]) -> Document {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let mut kids = Vec::new();
+    for page in pages {
+        let height = page.strokes.as_ref().map_or(HEIGHT, page_height);
+        let media_box = [0.0, 0.0, WIDTH * POINTS_PER_PIXEL, height * POINTS_PER_PIXEL];
+        let mut resources = Dictionary::new();
+        let mut content = Vec::new();
+        if let Some(strokes) = &page.strokes {
+            let form_id = doc.add_object(ink(strokes, media_box, POINTS_PER_PIXEL));
+            resources.set("XObject", dictionary! { INK => form_id });
+            content = format!("/{} Do\n", INK).into_bytes();
+        }
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content));
+        kids.push(Object::from(doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => media_box.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>(),
+            "Resources" => resources,
+            "Contents" => content_id,
+        })));
+    }
+    let count = kids.len() as i64;
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }));
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    doc
+}
+
+/// `page`'s strokes as a form XObject covering `media_box`, drawn from its
+/// top left at `scale` points per pixel.
+fn ink(page: &Page, [x0, y0, x1, y1]: [f32; 4], scale: f32) -> Stream {
+    let (width, height) = (x1 - x0, y1 - y0);
+    let offset = if page.centered { width / scale / 2.0 } else { 0.0 };
+
+    let mut states = BTreeMap::new();
+    let mut content = format!("q {s:.5} 0 0 {ns:.5} {x0:.2} {top:.2} cm 1 J 1 j\n", s = scale, ns = -scale, top = y0 + height);
+    for layer in page.layers.iter().filter(|l| l.visible) {
+        for run in layer_runs(layer, offset) {
+            let Rgb(r, g, b) = run.color;
+            let translucent = run.opacity < 1.0 || run.multiply;
+            if translucent {
+                let name = format!("A{}{}", (run.opacity * 100.0) as u32, if run.multiply { "m" } else { "" });
+                let _ = write!(content, "q /{} gs ", name);
+                states.insert(name, (run.opacity, run.multiply));
+            }
+            let _ = write!(content, "{:.3} {:.3} {:.3} RG {} w", r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, run.width);
+            for (i, (x, y)) in run.points.iter().enumerate() {
+                let _ = write!(content, " {:.2} {:.2} {}", x, y, if i == 0 { "m" } else { "l" });
+            }
+            content.push_str(if translucent { " S Q\n" } else { " S\n" });
+        }
+    }
+    content.push_str("Q\n");
+
+    let mut ext = Dictionary::new();
+    for (name, (opacity, multiply)) in states {
+        let mut state = dictionary! { "Type" => "ExtGState", "CA" => Object::Real(opacity), "ca" => Object::Real(opacity) };
+        if multiply {
+            state.set("BM", Object::Name(b"Multiply".to_vec()));
+        }
+        ext.set(name, state);
+    }
+    let dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Form",
+        "BBox" => vec![Object::Real(x0), Object::Real(y0), Object::Real(x1), Object::Real(y1)],
+        "Resources" => dictionary! { "ExtGState" => ext },
+    };
+    Stream::new(dict, content.into_bytes())
+}
+
+/// Draws `form` on top of an existing page, isolated from whatever state
+/// the page's own content leaves behind.
+fn overlay(doc: &mut Document, page_id: ObjectId, form: Stream) -> Result<()> {
+    let form_id = doc.add_object(form);
+    let mut resources = match inherited(doc, page_id, b"Resources") {
+        Some(Object::Dictionary(dict)) => dict.clone(),
+        _ => Dictionary::new(),
+    };
+    let mut xobjects = match resources.get(b"XObject").ok().map(|o| doc.dereference(o)) {
+        Some(Ok((_, Object::Dictionary(dict)))) => dict.clone(),
+        _ => Dictionary::new(),
+    };
+    xobjects.set(INK, form_id);
+    resources.set("XObject", xobjects);
+
+    let mut contents: Vec<Object> = doc.get_page_contents(page_id).into_iter().map(Object::Reference).collect();
+    let open = doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+    let close = doc.add_object(Stream::new(Dictionary::new(), format!("\nQ\nq /{} Do Q\n", INK).into_bytes()));
+    contents.insert(0, open.into());
+    contents.push(close.into());
+
+    let page = doc.get_object_mut(page_id).and_then(Object::as_dict_mut)?;
+    page.set("Resources", resources);
+    page.set("Contents", contents);
+    Ok(())
+}
+
+/// `key` of a page, looked up through its parents as PDF inheritance works.
+fn inherited<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    for _ in 0..32 {
+        if let Ok(value) = node.get(key) {
+            return doc.dereference(value).ok().map(|(_, object)| object);
+        }
+        node = doc.get_dictionary(node.get(b"Parent").ok()?.as_reference().ok()?).ok()?;
+    }
+    None
+}
+
+fn media_box(doc: &Document, page_id: ObjectId) -> Result<[f32; 4]> {
+    let values = inherited(doc, page_id, b"MediaBox")
+        .and_then(|b| b.as_array().ok())
+        .map(|b| b.iter().filter_map(|v| doc.dereference(v).ok()?.1.as_float().ok()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    match values[..] {
+        [a, b, c, d] => Ok([a.min(c), b.min(d), a.max(c), b.max(d)]),
+        _ => Err(anyhow!("A page of the document's PDF has no usable size")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::fixtures;
+
+    #[test]
+    fn svg_is_well_formed() {
+        let page = Page::parse(&fixtures::v6()).unwrap();
+        let svg = svg(&page);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<g").count(), 2);
+        assert_eq!(svg.matches("</g>").count(), 2);
+        assert!(svg.contains("<g display=\"none\"><title>Hidden</title>"));
+        assert!(svg.contains("stroke=\"#00ff00\""));
+        // Every polyline closes itself and has an even list of coordinates
+        for line in svg.lines().filter(|l| l.starts_with("<polyline")) {
+            assert!(line.ends_with("/>"));
+            let points = line.split('"').nth(1).unwrap();
+            assert!(points.split(' ').all(|p| p.split(',').filter_map(|v| v.parse::<f32>().ok()).count() == 2));
+        }
+        assert!(svg.matches("<polyline").count() >= 3);
+    }
+
+    #[test]
+    fn pdf_has_a_page_per_entry() {
+        let pages = [
+            PdfPage { strokes: Some(Page::parse(&fixtures::v3(true)).unwrap()), background: None },
+            PdfPage { strokes: None, background: None },
+            PdfPage { strokes: Some(Page::parse(&fixtures::v6()).unwrap()), background: None },
+        ];
+        let bytes = pdf(&pages, None).unwrap();
+        let doc = Document::load_mem(&bytes).unwrap();
+        assert_eq!(doc.get_pages().len(), 3);
+
+        // Drawn over a background, pages without one are left out
+        let over = [PdfPage { strokes: Some(Page::parse(&fixtures::v6()).unwrap()), background: Some(1) }];
+        let doc = Document::load_mem(&pdf(&over, Some(&bytes)).unwrap()).unwrap();
+        assert_eq!(doc.get_pages().len(), 3);
+        assert!(pdf(&over, Some(b"not a pdf")).is_err());
+    }
+}
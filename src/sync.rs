@@ -0,0 +1,642 @@
+use crate::{backend::Backend, naming::Template, Item};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+// --- Two-way folder sync ---
+//
+// A pair's state file remembers what both sides looked like after the last
+// sync. Comparing each side against it tells which one changed, so running
+// a sync twice in a row transfers nothing the second time.
+
+/// Kept in the synced directory itself so it moves with the folder.
+const STATE_FILE: &str = ".remarkable-sync.json";
+
+/// A `[[sync]]` entry pairing a local directory with a tablet folder.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncConfig {
+    /// Used to pick the pair on the command line; defaults to the directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `~` is expanded. Created on the first sync if missing.
+    pub directory: String,
+    /// Tablet path, resolved to the folder's ID on the first sync. Later
+    /// syncs follow the ID, so renaming or moving the folder is fine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tablet_folder: Option<String>,
+    /// The folder's ID, for when the path is ambiguous.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+}
+
+impl SyncConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.directory)
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        PathBuf::from(crate::expand_path(&self.directory))
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct State {
+    /// `None` for the tablet root.
+    folder_guid: Option<String>,
+    /// Keyed by path relative to the directory, `/`-separated.
+    entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Entry {
+    /// `None` right after an upload, until the tablet's copy is seen.
+    guid: Option<String>,
+    tablet_modified: Option<String>,
+    size: u64,
+    mtime: u64,
+    /// Deleted here but kept on the tablet; left alone from then on.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    ignored: bool,
+}
+
+impl State {
+    fn load(dir: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(dir.join(STATE_FILE)) {
+            Ok(text) => Ok(Some(serde_json::from_str(&text).with_context(|| format!("Corrupt {}", STATE_FILE))?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Written to a temporary file first so a crash never leaves half a state.
+    fn save(&self, dir: &Path) -> Result<()> {
+        let tmp = dir.join(format!("{}.tmp", STATE_FILE));
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(tmp, dir.join(STATE_FILE))?;
+        Ok(())
+    }
+}
+
+// --- Plan ---
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    Download { path: String, item: Item },
+    Upload { path: String, folder: Option<String> },
+    /// Deleted on the tablet and unchanged here since the last sync.
+    DeleteLocal { path: String },
+    /// Both sides already agree; only the state file is updated.
+    Adopt { path: String, item: Item },
+    Conflict { path: String, reason: &'static str },
+    /// Nothing can be done; reported so it is not a surprise.
+    Skip { path: String, reason: &'static str },
+}
+
+impl Step {
+    pub fn path(&self) -> &str {
+        match self {
+            Step::Download { path, .. }
+            | Step::Upload { path, .. }
+            | Step::DeleteLocal { path }
+            | Step::Adopt { path, .. }
+            | Step::Conflict { path, .. }
+            | Step::Skip { path, .. } => path,
+        }
+    }
+
+    /// Whether running the step touches a file on either side.
+    pub fn transfers(&self) -> bool {
+        matches!(self, Step::Download { .. } | Step::Upload { .. } | Step::DeleteLocal { .. })
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Download { path, .. } => write!(f, "download  {}", path),
+            Step::Upload { path, .. } => write!(f, "upload    {}", path),
+            Step::DeleteLocal { path } => write!(f, "delete    {} (deleted on the tablet)", path),
+            Step::Adopt { path, .. } => write!(f, "in sync   {}", path),
+            Step::Conflict { path, reason } => write!(f, "CONFLICT  {}: {}", path, reason),
+            Step::Skip { path, reason } => write!(f, "skip      {}: {}", path, reason),
+        }
+    }
+}
+
+pub struct Plan {
+    pub pair: SyncConfig,
+    pub dir: PathBuf,
+    folder_guid: Option<String>,
+    state: State,
+    /// State entries for files gone from both sides.
+    gone: Vec<String>,
+    /// Files deleted here since the last sync, to be left alone from now on.
+    ignore: Vec<String>,
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    /// Steps worth showing: everything except files already in sync.
+    pub fn visible(&self) -> impl Iterator<Item = &Step> {
+        self.steps.iter().filter(|s| !matches!(s, Step::Adopt { .. }))
+    }
+
+    pub fn summary(&self) -> String {
+        let count = |f: fn(&Step) -> bool| self.steps.iter().filter(|s| f(s)).count();
+        format!(
+            "{}: {} to download, {} to upload, {} to delete locally, {} conflict(s), {} skipped",
+            self.pair.name(),
+            count(|s| matches!(s, Step::Download { .. })),
+            count(|s| matches!(s, Step::Upload { .. })),
+            count(|s| matches!(s, Step::DeleteLocal { .. })),
+            count(|s| matches!(s, Step::Conflict { .. })),
+            count(|s| matches!(s, Step::Skip { .. })),
+        )
+    }
+}
+
+fn stamp(path: &Path) -> Result<(u64, u64)> {
+    let meta = std::fs::metadata(path)?;
+    let mtime = meta.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    Ok((meta.len(), mtime))
+}
+
+/// Every PDF below `dir`, by `/`-separated relative path. Hidden files and
+/// folders are left alone.
+fn local_files(dir: &Path, prefix: &str, out: &mut BTreeMap<String, PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let key = format!("{}{}", prefix, name);
+        if path.is_dir() {
+            local_files(&path, &format!("{}/", key), out)?;
+        } else if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf")) {
+            out.insert(key, path);
+        }
+    }
+    Ok(())
+}
+
+/// Every document below the tablet folder `guid`, keyed the way a download
+/// would name it, plus the folders themselves.
+async fn tablet_files(
+    backend: &dyn Backend,
+    guid: Option<String>,
+    ascii_only: bool,
+) -> Result<(BTreeMap<String, Item>, BTreeMap<String, Option<String>>)> {
+    let template = Template::default();
+    let mut files = BTreeMap::new();
+    let mut folders = BTreeMap::from([(String::new(), guid.clone())]);
+    let mut pending = vec![(guid, Vec::<String>::new())];
+
+    while let Some((guid, path)) = pending.pop() {
+        for item in backend.list(&guid).await? {
+            if item.is_folder() {
+                let mut child = path.clone();
+                child.push(item.visible_name.clone());
+                // Where a download would put the folder's documents
+                let rendered = template.render(&item.fields(&child), false, ascii_only);
+                let key = rendered.parent().map(key_of).unwrap_or_default();
+                folders.insert(key, Some(item.id.clone()));
+                pending.push((Some(item.id.clone()), child));
+            } else {
+                let key = key_of(&template.render(&item.fields(&path), false, ascii_only));
+                // Two documents with the same name: the first one wins
+                files.entry(key).or_insert(item);
+            }
+        }
+    }
+    Ok((files, folders))
+}
+
+fn key_of(path: &Path) -> String {
+    path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// Compares both sides with the state of the last sync.
+pub async fn plan(backend: &dyn Backend, pair: &SyncConfig, ascii_only: bool) -> Result<Plan> {
+    let dir = pair.dir();
+    let state = State::load(&dir)?.unwrap_or_default();
+
+    let folder_guid = match (&pair.guid, &state.folder_guid, &pair.tablet_folder) {
+        (Some(guid), _, _) => Some(guid.clone()),
+        (None, Some(guid), _) => Some(guid.clone()),
+        (None, None, Some(path)) => {
+            let trail = crate::resolve_trail(backend, Vec::new(), path).await?;
+            trail.last().map(|(id, _)| id.clone())
+        }
+        (None, None, None) => return Err(anyhow!("Sync pair `{}` needs `tablet_folder` or `guid`", pair.name())),
+    };
+
+    let (remote, folders) = tablet_files(backend, folder_guid.clone(), ascii_only).await?;
+    let mut local = BTreeMap::new();
+    if dir.exists() {
+        local_files(&dir, "", &mut local)?;
+    }
+
+    let (steps, gone, ignore) = compare(&remote, &folders, &local, &state)?;
+    Ok(Plan { pair: pair.clone(), dir, folder_guid, state, gone, ignore, steps })
+}
+
+/// The steps that bring both sides together, plus the state entries to
+/// forget and to mark ignored.
+fn compare(
+    remote: &BTreeMap<String, Item>,
+    folders: &BTreeMap<String, Option<String>>,
+    local: &BTreeMap<String, PathBuf>,
+    state: &State,
+) -> Result<(Vec<Step>, Vec<String>, Vec<String>)> {
+    let keys: BTreeSet<&String> = remote.keys().chain(local.keys()).chain(state.entries.keys()).collect();
+    let mut steps = Vec::new();
+    let mut gone = Vec::new();
+    let mut ignore = Vec::new();
+    for key in keys {
+        let path = key.clone();
+        // A file put back after being deleted is treated as new
+        let seen = state.entries.get(key).filter(|e| !(e.ignored && local.contains_key(key)));
+        let step = match (remote.get(key), local.get(key), seen) {
+            (Some(item), None, None) => Some(Step::Download { path, item: item.clone() }),
+            (None, Some(_), None) => {
+                let parent = key.rsplit_once('/').map_or("", |(parent, _)| parent);
+                match folders.get(parent) {
+                    Some(folder) => Some(Step::Upload { path, folder: folder.clone() }),
+                    None => Some(Step::Skip { path, reason: "its folder does not exist on the tablet" }),
+                }
+            }
+            (Some(item), Some(file), None) => {
+                // Both appeared since the last sync: fine if ours came from there
+                let tablet_time = item.modified_at().map(|t| t.timestamp() as u64);
+                let (_, mtime) = stamp(file)?;
+                match tablet_time {
+                    Some(t) if mtime >= t => Some(Step::Adopt { path, item: item.clone() }),
+                    _ => Some(Step::Conflict { path, reason: "exists on both sides with different dates" }),
+                }
+            }
+            (Some(item), Some(file), Some(seen)) => {
+                let tablet_changed = seen.guid.is_some() && (seen.guid.as_ref() != Some(&item.id) || seen.tablet_modified != item.modified);
+                let local_changed = stamp(file)? != (seen.size, seen.mtime);
+                match (tablet_changed, local_changed) {
+                    (false, false) if seen.guid.is_none() => Some(Step::Adopt { path, item: item.clone() }),
+                    (false, false) => None,
+                    (true, false) => Some(Step::Download { path, item: item.clone() }),
+                    (false, true) => Some(Step::Skip { path, reason: "changed locally; the tablet copy cannot be replaced" }),
+                    (true, true) => Some(Step::Conflict { path, reason: "changed on both sides" }),
+                }
+            }
+            (None, Some(file), Some(seen)) => {
+                if seen.guid.is_none() {
+                    Some(Step::Skip { path, reason: "uploaded; waiting for the tablet to list it" })
+                } else if stamp(file)? == (seen.size, seen.mtime) {
+                    Some(Step::DeleteLocal { path })
+                } else {
+                    Some(Step::Conflict { path, reason: "deleted on the tablet but changed locally" })
+                }
+            }
+            (Some(_), None, Some(seen)) if seen.ignored => None,
+            (Some(_), None, Some(_)) => {
+                ignore.push(path.clone());
+                Some(Step::Skip { path, reason: "deleted locally; the tablet copy is kept" })
+            }
+            // Gone from both sides: just forget it
+            (None, None, _) => {
+                gone.push(path);
+                None
+            }
+        };
+        steps.extend(step);
+    }
+    Ok((steps, gone, ignore))
+}
+
+// --- Running ---
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub downloaded: usize,
+    pub uploaded: usize,
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} downloaded, {} uploaded, {} deleted", self.downloaded, self.uploaded, self.deleted)?;
+        if !self.errors.is_empty() {
+            write!(f, ", {} failed", self.errors.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Carries out `plan`, saving the state after every step so an interrupted
+/// sync picks up where it stopped.
+pub async fn run(backend: &dyn Backend, mut plan: Plan) -> Result<Report> {
+    let dir = plan.dir.clone();
+    std::fs::create_dir_all(&dir)?;
+    plan.state.folder_guid = plan.folder_guid.clone();
+    for path in &plan.gone {
+        plan.state.entries.remove(path);
+    }
+    for path in &plan.ignore {
+        if let Some(entry) = plan.state.entries.get_mut(path) {
+            entry.ignored = true;
+        }
+    }
+    let mut report = Report::default();
+
+    for step in std::mem::take(&mut plan.steps) {
+        let path = step.path().to_string();
+        let target = dir.join(&path);
+        let result = match step {
+            Step::Download { item, .. } => async {
+                if let Some(parent) = target.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                crate::stream_pdf(backend, &item, &target).await?;
+                report.downloaded += 1;
+                anyhow::Ok(Some(entry_for(&item, &target)?))
+            }
+            .await,
+            Step::Upload { folder, .. } => async {
+                let file_name = target.file_name().unwrap_or_default().to_string_lossy().to_string();
+                backend.upload(&folder, &target, &file_name).await?;
+                report.uploaded += 1;
+                let (size, mtime) = stamp(&target)?;
+                anyhow::Ok(Some(Entry { guid: None, tablet_modified: None, size, mtime, ignored: false }))
+            }
+            .await,
+            Step::DeleteLocal { .. } => async {
+                tokio::fs::remove_file(&target).await?;
+                crate::integrity::forget(&target)?;
+                report.deleted += 1;
+                anyhow::Ok(None)
+            }
+            .await,
+            Step::Adopt { item, .. } => entry_for(&item, &target).map(Some),
+            Step::Conflict { .. } | Step::Skip { .. } => match plan.state.entries.get(&path) {
+                Some(entry) => Ok(Some(entry.clone())),
+                None => Ok(None),
+            },
+        };
+
+        match result {
+            Ok(Some(entry)) => {
+                plan.state.entries.insert(path, entry);
+            }
+            Ok(None) => {
+                plan.state.entries.remove(&path);
+            }
+            Err(e) => report.errors.push(format!("{}: {:#}", path, e)),
+        }
+        plan.state.save(&dir)?;
+    }
+    plan.state.save(&dir)?;
+    if let Err(e) = backend.settle().await {
+        report.errors.push(format!("tablet not refreshed: {:#}", e));
+    }
+    Ok(report)
+}
+
+fn entry_for(item: &Item, file: &Path) -> Result<Entry> {
+    let (size, mtime) = stamp(file)?;
+    Ok(Entry { guid: Some(item.id.clone()), tablet_modified: item.modified.clone(), size, mtime, ignored: false })
+}
+
+/// The pairs `name` selects: one by name or directory, or all of them.
+pub fn select<'a>(pairs: &'a [SyncConfig], name: Option<&str>) -> Result<Vec<&'a SyncConfig>> {
+    if pairs.is_empty() {
+        return Err(anyhow!("No [[sync]] pairs configured"));
+    }
+    match name {
+        None => Ok(pairs.iter().collect()),
+        Some(name) => pairs
+            .iter()
+            .find(|p| p.name() == name || p.directory == name)
+            .map(|p| vec![p])
+            .ok_or_else(|| anyhow!("No sync pair named `{}`", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Profile;
+    use std::time::Duration;
+
+    const PDF: &[u8] = b"%PDF-1.4\nstartxref\n0\n%%EOF\n";
+
+    /// A tablet document modified at `secs` past the epoch.
+    fn item(id: &str, secs: i64) -> Item {
+        let modified = chrono::DateTime::from_timestamp(secs, 0).unwrap().to_rfc3339();
+        serde_json::from_value(serde_json::json!({ "ID": id, "VissibleName": id, "Type": "DocumentType", "ModifiedClient": modified }))
+            .unwrap()
+    }
+
+    /// What a sync would have recorded for `item` and the local `file`.
+    fn seen(item: &Item, file: &Path) -> Entry {
+        entry_for(item, file).unwrap()
+    }
+
+    /// Both sides of a pair: the tablet listing and a local directory.
+    struct Sides {
+        local_dir: tempfile::TempDir,
+        remote: BTreeMap<String, Item>,
+        local: BTreeMap<String, PathBuf>,
+        state: State,
+    }
+
+    impl Sides {
+        fn new() -> Self {
+            Self { local_dir: tempfile::tempdir().unwrap(), remote: BTreeMap::new(), local: BTreeMap::new(), state: State::default() }
+        }
+
+        fn put_tablet(&mut self, name: &str, secs: i64) -> Item {
+            let item = item(name, secs);
+            self.remote.insert(name.to_string(), item.clone());
+            item
+        }
+
+        fn put_local(&mut self, name: &str, secs: u64) -> PathBuf {
+            let path = self.local_dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, PDF).unwrap();
+            std::fs::File::options().write(true).open(&path).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
+            self.local.insert(name.to_string(), path.clone());
+            path
+        }
+
+        /// A file both sides had at the last sync.
+        fn synced(&mut self, name: &str) -> (Item, PathBuf) {
+            let item = self.put_tablet(name, 1_000_000);
+            let file = self.put_local(name, 1_000_000);
+            self.state.entries.insert(name.to_string(), seen(&item, &file));
+            (item, file)
+        }
+
+        fn change_local(&self, name: &str) {
+            std::fs::write(&self.local[name], [PDF, b"% more"].concat()).unwrap();
+        }
+
+        fn compare(&self) -> (Vec<String>, Vec<String>, Vec<String>) {
+            let folders = BTreeMap::from([(String::new(), Some("sync".to_string()))]);
+            let (steps, gone, ignore) = compare(&self.remote, &folders, &self.local, &self.state).unwrap();
+            let steps = steps.iter().map(|s| s.to_string().split_whitespace().collect::<Vec<_>>().join(" ")).collect();
+            (steps, gone, ignore)
+        }
+    }
+
+    impl Sides {
+        /// Runs the plan against an empty tablet.
+        async fn run(&mut self) -> Report {
+            let dir = self.local_dir.path().to_path_buf();
+            let pair = SyncConfig { name: None, directory: dir.display().to_string(), tablet_folder: None, guid: None };
+            let (steps, gone, ignore) = compare(&self.remote, &BTreeMap::new(), &self.local, &self.state).unwrap();
+            let state = std::mem::take(&mut self.state);
+            let plan = Plan { pair, dir: dir.clone(), folder_guid: None, state, gone, ignore, steps };
+            let tablet = tempfile::tempdir().unwrap();
+            let backend = Profile::Local { path: tablet.path().display().to_string() }.open();
+            let report = run(backend.as_ref(), plan).await.unwrap();
+            self.state = State::load(&dir).unwrap().unwrap();
+            report
+        }
+    }
+
+    #[test]
+    fn plans_a_first_sync() {
+        let mut sides = Sides::new();
+        sides.put_tablet("tablet.pdf", 1_000_000);
+        sides.put_local("local.pdf", 1_000_000);
+        sides.put_local("Elsewhere/orphan.pdf", 1_000_000);
+        sides.put_tablet("copied.pdf", 1_000_000);
+        sides.put_local("copied.pdf", 1_000_060);
+        sides.put_tablet("older.pdf", 2_000_000);
+        sides.put_local("older.pdf", 1_000_000);
+
+        let (steps, gone, ignore) = sides.compare();
+        assert_eq!(
+            steps,
+            [
+                "skip Elsewhere/orphan.pdf: its folder does not exist on the tablet",
+                "in sync copied.pdf",
+                "upload local.pdf",
+                "CONFLICT older.pdf: exists on both sides with different dates",
+                "download tablet.pdf",
+            ]
+        );
+        assert!(gone.is_empty() && ignore.is_empty());
+    }
+
+    #[test]
+    fn plans_against_the_last_sync() {
+        let mut sides = Sides::new();
+        for name in ["both.pdf", "deleted-here.pdf", "edited-here.pdf", "edited-there.pdf", "gone.pdf", "removed.pdf", "same.pdf", "stale.pdf"] {
+            sides.synced(name);
+        }
+        let waiting = sides.put_local("waiting.pdf", 1_000_000);
+        let (size, mtime) = stamp(&waiting).unwrap();
+        sides.state.entries.insert("waiting.pdf".into(), Entry { guid: None, tablet_modified: None, size, mtime, ignored: false });
+
+        sides.put_tablet("both.pdf", 2_000_000);
+        sides.change_local("both.pdf");
+        sides.local.remove("deleted-here.pdf");
+        sides.change_local("edited-here.pdf");
+        sides.put_tablet("edited-there.pdf", 2_000_000);
+        sides.remote.remove("gone.pdf");
+        sides.local.remove("gone.pdf");
+        sides.remote.remove("removed.pdf");
+        sides.remote.remove("stale.pdf");
+        sides.change_local("stale.pdf");
+
+        let (steps, gone, ignore) = sides.compare();
+        assert_eq!(
+            steps,
+            [
+                "CONFLICT both.pdf: changed on both sides",
+                "skip deleted-here.pdf: deleted locally; the tablet copy is kept",
+                "skip edited-here.pdf: changed locally; the tablet copy cannot be replaced",
+                "download edited-there.pdf",
+                "delete removed.pdf (deleted on the tablet)",
+                "CONFLICT stale.pdf: deleted on the tablet but changed locally",
+                "skip waiting.pdf: uploaded; waiting for the tablet to list it",
+            ]
+        );
+        assert_eq!(gone, ["gone.pdf"]);
+        assert_eq!(ignore, ["deleted-here.pdf"]);
+    }
+
+    #[test]
+    fn a_file_deleted_here_is_skipped_once() {
+        let mut sides = Sides::new();
+        sides.synced("paper.pdf");
+        sides.local.remove("paper.pdf");
+        assert_eq!(sides.compare().2, ["paper.pdf"]);
+
+        sides.state.entries.get_mut("paper.pdf").unwrap().ignored = true;
+        let (steps, _, ignore) = sides.compare();
+        assert!(steps.is_empty() && ignore.is_empty(), "{:?}", steps);
+
+        // Putting it back makes it a file both sides have again
+        sides.put_local("paper.pdf", 1_000_060);
+        assert_eq!(sides.compare().0, ["in sync paper.pdf"]);
+    }
+
+    #[tokio::test]
+    async fn running_marks_deleted_files_ignored() {
+        let mut sides = Sides::new();
+        sides.synced("paper.pdf");
+        std::fs::remove_file(sides.local.remove("paper.pdf").unwrap()).unwrap();
+
+        let report = sides.run().await;
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert!(sides.state.entries["paper.pdf"].ignored);
+    }
+
+    #[tokio::test]
+    async fn deleting_a_file_forgets_its_checksum() {
+        let mut sides = Sides::new();
+        let (_, file) = sides.synced("paper.pdf");
+        crate::integrity::record(sides.local_dir.path(), &file, "00").unwrap();
+        sides.remote.remove("paper.pdf");
+
+        let report = sides.run().await;
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert!(!file.exists());
+        assert_eq!(std::fs::read_to_string(sides.local_dir.path().join(crate::integrity::MANIFEST)).unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn a_second_sync_transfers_nothing() {
+        let tablet = tempfile::tempdir().unwrap();
+        let local = tempfile::tempdir().unwrap();
+        let backend = Profile::Local { path: tablet.path().display().to_string() }.open();
+        let source = tempfile::tempdir().unwrap();
+        std::fs::write(source.path().join("tablet.pdf"), PDF).unwrap();
+        backend.upload(&None, &source.path().join("tablet.pdf"), "tablet.pdf").await.unwrap();
+        std::fs::write(local.path().join("local.pdf"), PDF).unwrap();
+
+        let pair = SyncConfig { name: None, directory: local.path().display().to_string(), tablet_folder: Some("/".into()), guid: None };
+        let sync = || async {
+            let report = run(backend.as_ref(), plan(backend.as_ref(), &pair, false).await.unwrap()).await.unwrap();
+            assert!(report.errors.is_empty(), "{:?}", report.errors);
+        };
+        sync().await;
+        // The upload is adopted once the tablet lists it
+        let steps: Vec<String> = plan(backend.as_ref(), &pair, false).await.unwrap().steps.iter().map(|s| s.to_string()).collect();
+        assert_eq!(steps.len(), 1);
+        assert!(steps[0].starts_with("in sync") && steps[0].ends_with("local.pdf"), "{:?}", steps);
+        sync().await;
+
+        let again = plan(backend.as_ref(), &pair, false).await.unwrap();
+        assert!(again.steps.is_empty(), "{:?}", again.steps.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        assert_eq!(std::fs::read(local.path().join("tablet.pdf")).unwrap(), PDF);
+    }
+}
@@ -0,0 +1,116 @@
+use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::process::Command;
+
+// --- Local viewer ---
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ViewerConfig {
+    /// Shell command with `{file}` standing for the PDF, e.g. `zathura {file}`.
+    /// Unset uses the system's default application.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// The viewer runs in this terminal (e.g. `termpdf.py {file}`), so the UI
+    /// steps aside until it exits.
+    pub terminal: bool,
+}
+
+/// `$XDG_CACHE_HOME/remarkable/view`, falling back to `~/.cache`, then the
+/// system temp directory.
+pub fn cache_dir() -> PathBuf {
+    let base = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".cache"),
+            None => std::env::temp_dir(),
+        },
+    };
+    base.join("remarkable").join("view")
+}
+
+/// Where a document is cached, and whether that copy is still current. A
+/// copy is current when it was fetched at the same tablet modification time.
+pub fn cached(guid: &str, modified: Option<&str>) -> (PathBuf, bool) {
+    let dir = cache_dir();
+    let pdf = dir.join(format!("{}.pdf", guid));
+    let fresh = match (modified, std::fs::read_to_string(dir.join(format!("{}.modified", guid)))) {
+        (Some(modified), Ok(stamp)) => stamp == modified && pdf.exists(),
+        _ => false,
+    };
+    (pdf, fresh)
+}
+
+/// Records the modification time a freshly fetched copy belongs to.
+pub fn mark_fresh(guid: &str, modified: Option<&str>) -> io::Result<()> {
+    let stamp = cache_dir().join(format!("{}.modified", guid));
+    match modified {
+        Some(modified) => std::fs::write(stamp, modified),
+        None => Ok(()),
+    }
+}
+
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+fn command_for(config: &ViewerConfig, file: &Path) -> Command {
+    match &config.command {
+        Some(template) => {
+            let line = if template.contains("{file}") {
+                template.replace("{file}", &quote(file))
+            } else {
+                format!("{} {}", template, quote(file))
+            };
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(line);
+            cmd
+        }
+        None => {
+            let mut cmd = Command::new(if cfg!(target_os = "macos") { "open" } else { "xdg-open" });
+            cmd.arg(file);
+            cmd
+        }
+    }
+}
+
+/// Opens `file` in the configured viewer. A terminal viewer gets the
+/// terminal to itself until it exits; anything else runs detached, with a
+/// task waiting on it so it does not linger as a zombie.
+pub async fn open(config: &ViewerConfig, file: &Path, mouse: bool) -> io::Result<()> {
+    let mut cmd = command_for(config, file);
+    if !config.terminal {
+        let mut child = cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+        tokio::spawn(async move { child.wait().await });
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout();
+    disable_raw_mode()?;
+    if mouse {
+        execute!(stdout, DisableMouseCapture)?;
+    }
+    execute!(stdout, LeaveAlternateScreen)?;
+
+    let status = cmd.status().await;
+
+    enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen)?;
+    if mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+
+    match status? {
+        s if s.success() => Ok(()),
+        s => Err(io::Error::other(format!("viewer exited with {}", s))),
+    }
+}
@@ -0,0 +1,310 @@
+use crate::backend::{self, Backend};
+use crate::conflict::DuplicatePolicy;
+use crate::dryrun::{Direction, Planned, Preview};
+use crate::duplicate;
+use crate::ledger::{self, Ledger, UploadRecord};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc;
+
+// --- Watch folders ---
+
+const POLL: Duration = Duration::from_secs(2);
+/// A file counts as written once it has not changed for this long.
+const SETTLE: Duration = Duration::from_secs(3);
+
+/// A `[[watch]]` entry: new files in `directory` are uploaded to `tablet_folder`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchConfig {
+    /// Local directory to watch (not recursive). `~` is expanded.
+    pub directory: String,
+    /// Destination as a tablet path, e.g. `/Scans`.
+    #[serde(default = "WatchConfig::default_folder")]
+    pub tablet_folder: String,
+    /// File extensions to pick up, without the dot.
+    #[serde(default = "WatchConfig::default_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl WatchConfig {
+    fn default_folder() -> String {
+        "/".into()
+    }
+
+    fn default_extensions() -> Vec<String> {
+        vec!["pdf".into(), "epub".into()]
+    }
+
+    /// The tablet folder in `/A/B` form, empty for the root.
+    pub fn folder(&self) -> String {
+        self.tablet_folder.split('/').filter(|c| !c.is_empty()).map(|c| format!("/{}", c)).collect()
+    }
+
+    fn wants(&self, path: &Path) -> bool {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        self.extensions.iter().any(|wanted| wanted.eq_ignore_ascii_case(&ext))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    Uploaded { local: PathBuf, tablet_folder: String },
+    Failed { local: PathBuf, error: String },
+    /// Left alone because the folder has a document of that name.
+    Skipped { local: PathBuf, reason: String },
+    /// The tablet could not be reached; files wait until it can.
+    Offline { queued: usize },
+    Online,
+}
+
+/// Size and modification time, to notice when a file changes.
+type Stamp = (u64, SystemTime);
+
+/// How an upload attempt ended.
+enum Outcome {
+    Uploaded,
+    /// The ledger has it in this folder already.
+    Known,
+    /// Skipped as a duplicate of the named document.
+    Duplicate(String),
+}
+
+#[derive(PartialEq)]
+enum FileState {
+    /// Seen with this stamp, not yet settled or not yet uploaded.
+    Pending(Stamp),
+    /// Uploaded, already in the ledger or failed for good at this stamp.
+    Done(Stamp),
+}
+
+/// Watches one directory until the task is dropped.
+pub async fn run(
+    watch: WatchConfig,
+    backend: Arc<dyn Backend>,
+    ledger: Arc<Mutex<Ledger>>,
+    on_duplicate: DuplicatePolicy,
+    events: mpsc::Sender<WatchEvent>,
+) {
+    let dir = PathBuf::from(crate::expand_path(&watch.directory));
+    let folder = watch.folder();
+    let mut files: HashMap<PathBuf, FileState> = HashMap::new();
+    let mut folder_id: Option<Option<String>> = None;
+    let mut offline = false;
+
+    loop {
+        tokio::time::sleep(POLL).await;
+
+        let Ok(read) = std::fs::read_dir(&dir) else { continue };
+        let now = SystemTime::now();
+        let mut ready = Vec::new();
+        let mut present = Vec::new();
+        for entry in read.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else { continue };
+            if !meta.is_file() || !watch.wants(&path) {
+                continue;
+            }
+            let Ok(modified) = meta.modified() else { continue };
+            let stamp = (meta.len(), modified);
+            present.push(path.clone());
+            if observe(&mut files, &path, stamp, now) {
+                ready.push((path, stamp));
+            }
+        }
+        files.retain(|path, _| present.contains(path));
+
+        let mut uploaded = false;
+        for (path, stamp) in ready {
+            match upload(backend.as_ref(), &folder, &mut folder_id, &ledger, on_duplicate, &path).await {
+                Ok(outcome) => {
+                    if offline {
+                        offline = false;
+                        let _ = events.send(WatchEvent::Online).await;
+                    }
+                    files.insert(path.clone(), FileState::Done(stamp));
+                    let event = match outcome {
+                        Outcome::Uploaded => {
+                            uploaded = true;
+                            WatchEvent::Uploaded { local: path, tablet_folder: folder.clone() }
+                        }
+                        Outcome::Duplicate(reason) => WatchEvent::Skipped { local: path, reason },
+                        Outcome::Known => continue,
+                    };
+                    let _ = events.send(event).await;
+                }
+                Err(e) if is_unreachable(&e) => {
+                    // Keep it pending and try again next poll
+                    folder_id = None;
+                    if !offline {
+                        offline = true;
+                        let queued = files.values().filter(|s| matches!(s, FileState::Pending(_))).count();
+                        let _ = events.send(WatchEvent::Offline { queued }).await;
+                    }
+                    break;
+                }
+                Err(e) => {
+                    files.insert(path.clone(), FileState::Done(stamp));
+                    let _ = events.send(WatchEvent::Failed { local: path, error: format!("{:#}", e) }).await;
+                }
+            }
+        }
+        if uploaded {
+            if let Err(e) = backend.settle().await {
+                let _ = events.send(WatchEvent::Failed { local: dir.clone(), error: format!("{:#}", e) }).await;
+            }
+        }
+    }
+}
+
+/// Notes that `path` was seen with `stamp` at `now`, and says whether it is
+/// done being written and due for an upload.
+fn observe(files: &mut HashMap<PathBuf, FileState>, path: &Path, stamp: Stamp, now: SystemTime) -> bool {
+    match files.get(path) {
+        Some(FileState::Done(done)) if *done == stamp => false,
+        // Unchanged since the last poll and quiet for a while: done writing
+        Some(FileState::Pending(seen)) if *seen == stamp && now.duration_since(stamp.1).unwrap_or_default() >= SETTLE => true,
+        _ => {
+            files.insert(path.to_path_buf(), FileState::Pending(stamp));
+            false
+        }
+    }
+}
+
+/// What the watcher would upload right now: every wanted file in the
+/// directory that the ledger has not seen in this folder.
+pub fn preview(watch: &WatchConfig, ledger: &Ledger) -> Result<Preview> {
+    let dir = PathBuf::from(crate::expand_path(&watch.directory));
+    let folder = watch.folder();
+    let mut preview = Preview::new(Direction::Upload);
+    let read = std::fs::read_dir(&dir).with_context(|| format!("Could not read {}", dir.display()))?;
+    let mut paths: Vec<PathBuf> = read.flatten().map(|e| e.path()).filter(|p| p.is_file() && watch.wants(p)).collect();
+    paths.sort();
+    for path in paths {
+        if ledger.contains(&ledger::sha256_file(&path)?, &folder) {
+            continue;
+        }
+        preview.files.push(Planned {
+            target: format!("{}/{}", folder, path.file_name().unwrap_or_default().to_string_lossy()),
+            size: std::fs::metadata(&path).ok().map(|m| m.len()),
+            source: path.display().to_string(),
+            conflict: None,
+        });
+    }
+    Ok(preview)
+}
+
+/// Uploads `path` unless the ledger already has it or `on_duplicate` says
+/// to leave a same-named document alone. There is nobody to ask, so
+/// `prompt` skips.
+async fn upload(
+    backend: &dyn Backend,
+    folder: &str,
+    folder_id: &mut Option<Option<String>>,
+    ledger: &Mutex<Ledger>,
+    on_duplicate: DuplicatePolicy,
+    path: &Path,
+) -> Result<Outcome> {
+    let sha256 = ledger::sha256_file(path)?;
+    if ledger.lock().unwrap().contains(&sha256, folder) {
+        return Ok(Outcome::Known);
+    }
+
+    let id = match folder_id {
+        Some(id) => id.clone(),
+        None => {
+            let trail = crate::resolve_trail(backend, Vec::new(), folder).await?;
+            let id = trail.last().map(|(id, _)| id.clone());
+            *folder_id = Some(id.clone());
+            id
+        }
+    };
+
+    let listing = backend.list(&id).await?;
+    let file_name = path.file_name().ok_or_else(|| anyhow!("Invalid filename"))?.to_string_lossy().to_string();
+    let mut upload_name = file_name.clone();
+    if let Some(duplicate) = duplicate::find(&listing, path, None, folder)? {
+        match on_duplicate {
+            DuplicatePolicy::Prompt | DuplicatePolicy::Skip => return Ok(Outcome::Duplicate(duplicate.describe())),
+            DuplicatePolicy::Rename => upload_name = duplicate::suffixed(&listing, &file_name),
+            DuplicatePolicy::Anyway => {}
+        }
+    }
+    backend.upload(&id, path, &upload_name).await?;
+
+    ledger.lock().unwrap().record(UploadRecord {
+        sha256,
+        size: std::fs::metadata(path)?.len(),
+        local_path: path.to_path_buf(),
+        tablet_folder: folder.to_string(),
+        uploaded_at: chrono::Utc::now(),
+    })?;
+    Ok(Outcome::Uploaded)
+}
+
+fn is_unreachable(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause.is::<backend::Unreachable>()
+            || cause.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_connect() || e.is_timeout())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Profile;
+
+    #[test]
+    fn files_wait_until_they_stop_changing() {
+        let mut files = HashMap::new();
+        let path = Path::new("/scans/a.pdf");
+        let written = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let stamp = (10, written);
+
+        // First sighting, then still too fresh
+        assert!(!observe(&mut files, path, stamp, written + Duration::from_secs(10)));
+        assert!(!observe(&mut files, path, stamp, written + SETTLE / 2));
+        // Grew since the last poll: starts over
+        let grown = (20, written + Duration::from_secs(1));
+        assert!(!observe(&mut files, path, grown, written + Duration::from_secs(10)));
+        assert!(observe(&mut files, path, grown, written + Duration::from_secs(10)));
+
+        // Nothing more once handled, until it changes again
+        files.insert(path.to_path_buf(), FileState::Done(grown));
+        assert!(!observe(&mut files, path, grown, written + Duration::from_secs(60)));
+        let rewritten = (30, written + Duration::from_secs(30));
+        assert!(!observe(&mut files, path, rewritten, written + Duration::from_secs(60)));
+        assert!(observe(&mut files, path, rewritten, written + Duration::from_secs(60)));
+    }
+
+    #[tokio::test]
+    async fn the_ledger_stops_a_second_upload() {
+        let tablet = tempfile::tempdir().unwrap();
+        let local = tempfile::tempdir().unwrap();
+        let backend = Profile::Local { path: tablet.path().display().to_string() }.open();
+        let ledger = Mutex::new(Ledger::open(local.path().join("uploads.jsonl")).unwrap());
+        let scan = local.path().join("scan.pdf");
+        std::fs::write(&scan, b"%PDF-1.4 scan").unwrap();
+
+        let mut folder_id = None;
+        let first = upload(backend.as_ref(), "", &mut folder_id, &ledger, DuplicatePolicy::Anyway, &scan).await.unwrap();
+        assert!(matches!(first, Outcome::Uploaded));
+        // A new watcher, e.g. after a restart, reads the same ledger
+        let ledger = Mutex::new(Ledger::open(local.path().join("uploads.jsonl")).unwrap());
+        let mut folder_id = None;
+        let second = upload(backend.as_ref(), "", &mut folder_id, &ledger, DuplicatePolicy::Anyway, &scan).await.unwrap();
+        assert!(matches!(second, Outcome::Known));
+        assert_eq!(backend.list(&None).await.unwrap().len(), 1);
+
+        // The same file is still new to another folder
+        let hash = ledger::sha256_file(&scan).unwrap();
+        assert!(ledger.lock().unwrap().contains(&hash, ""));
+        assert!(!ledger.lock().unwrap().contains(&hash, "/Scans"));
+    }
+}